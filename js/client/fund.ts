@@ -26,9 +26,84 @@ const PoolRequestTag = new BN('220a6cbdcd1cc4cf', 'hex');
 
 export class PoolRequestInner extends Assignable {}
 
+export class AssetSwap extends Assignable {
+  programId: PublicKey;
+  swap: PublicKey;
+  intermediate: IntermediateHop | null;
+}
+
+export class IntermediateHop extends Assignable {
+  swap: PublicKey;
+  vault: PublicKey;
+  mint: PublicKey;
+}
+
+export class AssetOracle extends Assignable {
+  programId: PublicKey;
+  priceAccount: PublicKey;
+  assetDecimals: number;
+  basicAssetDecimals: number;
+  maxPriceAge: BN;
+  maxConfidenceBps: number;
+}
+
+export class AssetMarket extends Assignable {
+  programId: PublicKey;
+  market: PublicKey;
+  openOrders: PublicKey;
+}
+
+export class KeeperRewardAmount extends Assignable {
+  amount: BN;
+}
+
+export class KeeperRewardNone extends Assignable {}
+
+export class KeeperReward extends Assignable {
+  none?: KeeperRewardNone;
+  basicAsset?: KeeperRewardAmount;
+  fundTokens?: KeeperRewardAmount;
+}
+
+export class CrankConfig extends Assignable {
+  enabled: number;
+  minInterval: BN;
+  driftThresholdBps: number;
+  reward: KeeperReward;
+}
+
+export enum PendingTrade {
+  None = 0,
+  Sell = 1,
+  Buy = 2,
+}
+
+export class ExchangeRate extends Assignable {
+  asset: BN;
+  basicAsset: BN;
+}
+
+export class RebalancePlan extends Assignable {
+  targetAmounts: BN[];
+  exchangeRates: ExchangeRate[];
+  pendingTrades: PendingTrade[];
+  lastKeeperSlot: BN | null;
+}
+
+export class PlannedTrade extends Assignable {
+  currentValue: BN;
+  targetValue: BN;
+  direction: PendingTrade;
+  amountIn: BN;
+  minimumAmountOut: BN;
+}
+
 export class InitializeFundData extends Assignable {
-  slippageDivider: BN;
+  rebalanceThresholdBps: number;
+  maxSlippageBps: number;
+  maxOracleDeviationBps: number;
   assetWeights: number[];
+  assetSwaps: AssetSwap[];
   initialSupply: BN;
 }
 
@@ -57,9 +132,23 @@ export class ParamDesc extends Assignable {
 }
 
 export class FundState extends Assignable {
+  tag: number;
   paused: number;
-  slippageDivider: BN;
+  rebalanceThresholdBps: number;
+  maxSlippageBps: number;
+  maxOracleDeviationBps: number;
+  maxNavLossBps: number;
+  maxSliceValue: BN;
   assetWeights: number[];
+  basicAssetWeight: number;
+  assetSwaps: AssetSwap[];
+  assetOracles: (AssetOracle | null)[];
+  assetMarkets: (AssetMarket | null)[];
+  crank: CrankConfig;
+  rebalanceCooldown: BN;
+  lastRebalanceSlot: BN;
+  lastRebalanceTimestamp: BN;
+  rebalancePlan: RebalancePlan | null;
   basicAsset: AssetInfo;
 }
 
@@ -114,8 +203,11 @@ export const schema = new Map<Function, any>([
     {
       kind: 'struct',
       fields: [
-        ['slippageDivider', 'u64'],
+        ['rebalanceThresholdBps', 'u16'],
+        ['maxSlippageBps', 'u16'],
+        ['maxOracleDeviationBps', 'u16'],
         ['assetWeights', ['u32']],
+        ['assetSwaps', [AssetSwap]],
         ['initialSupply', 'u64'],
       ],
     },
@@ -179,13 +271,152 @@ export const schema = new Map<Function, any>([
     {
       kind: 'struct',
       fields: [
+        ['tag', 'u32'],
         ['paused', 'u8'],
-        ['slippageDivider', 'u64'],
+        ['rebalanceThresholdBps', 'u16'],
+        ['maxSlippageBps', 'u16'],
+        ['maxOracleDeviationBps', 'u16'],
+        ['maxNavLossBps', 'u16'],
+        ['maxSliceValue', 'u64'],
         ['assetWeights', ['u32']],
+        ['basicAssetWeight', 'u32'],
+        ['assetSwaps', [AssetSwap]],
+        ['assetOracles', [{ kind: 'option', type: AssetOracle }]],
+        ['assetMarkets', [{ kind: 'option', type: AssetMarket }]],
+        ['crank', CrankConfig],
+        // i64, always positive
+        ['rebalanceCooldown', 'u64'],
+        ['lastRebalanceSlot', 'u64'],
+        // i64, always positive
+        ['lastRebalanceTimestamp', 'u64'],
+        ['rebalancePlan', { kind: 'option', type: RebalancePlan }],
         ['basicAsset', AssetInfo],
       ],
     },
   ],
+  [
+    AssetSwap,
+    {
+      kind: 'struct',
+      fields: [
+        ['programId', PubKey],
+        ['swap', PubKey],
+        ['intermediate', { kind: 'option', type: IntermediateHop }],
+      ],
+    },
+  ],
+  [
+    IntermediateHop,
+    {
+      kind: 'struct',
+      fields: [
+        ['swap', PubKey],
+        ['vault', PubKey],
+        ['mint', PubKey],
+      ],
+    },
+  ],
+  [
+    AssetOracle,
+    {
+      kind: 'struct',
+      fields: [
+        ['programId', PubKey],
+        ['priceAccount', PubKey],
+        ['assetDecimals', 'u8'],
+        ['basicAssetDecimals', 'u8'],
+        ['maxPriceAge', 'u64'],
+        ['maxConfidenceBps', 'u16'],
+      ],
+    },
+  ],
+  [
+    AssetMarket,
+    {
+      kind: 'struct',
+      fields: [
+        ['programId', PubKey],
+        ['market', PubKey],
+        ['openOrders', PubKey],
+      ],
+    },
+  ],
+  [
+    CrankConfig,
+    {
+      kind: 'struct',
+      fields: [
+        ['enabled', 'u8'],
+        // i64, always positive
+        ['minInterval', 'u64'],
+        ['driftThresholdBps', 'u16'],
+        ['reward', KeeperReward],
+      ],
+    },
+  ],
+  [
+    KeeperReward,
+    {
+      kind: 'enum',
+      field: 'enum',
+      values: [
+        ['none', KeeperRewardNone],
+        ['basicAsset', KeeperRewardAmount],
+        ['fundTokens', KeeperRewardAmount],
+      ],
+    },
+  ],
+  [
+    RebalancePlan,
+    {
+      kind: 'struct',
+      fields: [
+        ['targetAmounts', ['u64']],
+        ['exchangeRates', [ExchangeRate]],
+        // Borsh enum without fields, the variant index
+        ['pendingTrades', ['u8']],
+        ['lastKeeperSlot', { kind: 'option', type: 'u64' }],
+      ],
+    },
+  ],
+  [
+    ExchangeRate,
+    {
+      kind: 'struct',
+      fields: [
+        ['asset', 'u128'],
+        ['basicAsset', 'u128'],
+      ],
+    },
+  ],
+  [
+    PlannedTrade,
+    {
+      kind: 'struct',
+      fields: [
+        ['currentValue', 'u64'],
+        ['targetValue', 'u64'],
+        // Borsh enum without fields, the variant index
+        ['direction', 'u8'],
+        ['amountIn', 'u64'],
+        ['minimumAmountOut', 'u64'],
+      ],
+    },
+  ],
+  [
+    KeeperRewardNone,
+    {
+      kind: 'struct',
+      fields: [],
+    },
+  ],
+  [
+    KeeperRewardAmount,
+    {
+      kind: 'struct',
+      fields: [['amount', 'u64']],
+    },
+  ],
   [
     ExecutePoolAction,
     {
@@ -230,8 +461,11 @@ export class Fund {
     poolName: string,
     feeRate: number,
     assetWeights: number[],
+    assetSwaps: AssetSwap[],
     initialSupply: BN,
-    slippageDivider: BN,
+    rebalanceThresholdBps: number,
+    maxSlippageBps: number,
+    maxOracleDeviationBps: number,
   ): TransactionInstruction {
     const keys = [
       { pubkey: fundAccount, isSigner: false, isWritable: true },
@@ -248,8 +482,11 @@ export class Fund {
     ];
 
     const initializeFundData = new InitializeFundData({
-      slippageDivider,
+      rebalanceThresholdBps,
+      maxSlippageBps,
+      maxOracleDeviationBps,
       assetWeights,
+      assetSwaps,
       initialSupply,
     });
 
@@ -295,6 +532,8 @@ export class Fund {
     initializerFeeAccount: PublicKey,
     refferFeeVault: PublicKey,
     tokenProgramId: PublicKey,
+    basicAssetVault: PublicKey,
+    userBasicAssetAccount: PublicKey,
     amount: BN,
   ): TransactionInstruction {
     return Fund.createPoolActionInstruction(
@@ -310,6 +549,8 @@ export class Fund {
       initializerFeeAccount,
       refferFeeVault,
       tokenProgramId,
+      basicAssetVault,
+      userBasicAssetAccount,
       PoolAction.Create,
       amount,
     );
@@ -328,6 +569,8 @@ export class Fund {
     initializerFeeAccount: PublicKey,
     refferFeeVault: PublicKey,
     tokenProgramId: PublicKey,
+    basicAssetVault: PublicKey,
+    userBasicAssetAccount: PublicKey,
     amount: BN,
   ): TransactionInstruction {
     return Fund.createPoolActionInstruction(
@@ -343,6 +586,8 @@ export class Fund {
       initializerFeeAccount,
       refferFeeVault,
       tokenProgramId,
+      basicAssetVault,
+      userBasicAssetAccount,
       PoolAction.Redeem,
       amount,
     );
@@ -361,6 +606,8 @@ export class Fund {
     initializerFeeAccount: PublicKey,
     refferFeeVault: PublicKey,
     tokenProgramId: PublicKey,
+    basicAssetVault: PublicKey,
+    userBasicAssetAccount: PublicKey,
     poolActionType: number,
     amount: BN,
   ): TransactionInstruction {
@@ -376,6 +623,9 @@ export class Fund {
       { pubkey: initializerFeeAccount, isSigner: false, isWritable: true },
       { pubkey: refferFeeVault, isSigner: false, isWritable: true },
       { pubkey: tokenProgramId, isSigner: false, isWritable: false },
+      // Share of the fund vault of basic asset, transferred with the assets
+      { pubkey: basicAssetVault, isSigner: false, isWritable: true },
+      { pubkey: userBasicAssetAccount, isSigner: false, isWritable: true },
     ];
 
     const executePoolAction = new ExecutePoolAction({
@@ -0,0 +1,76 @@
+use anyhow::{anyhow, bail, Result};
+use fund::valuation::user_fees;
+use serum_pool::{context::Fees, schema::PoolState};
+use solana_program::pubkey::Pubkey;
+
+use crate::instruction::FeeAccounts;
+
+/// Fund tokens and assets moved by a redemption.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Redemption {
+    /// Fund tokens transferred from the user to the fee accounts.
+    pub fees: Fees,
+    /// Fund tokens burned, the redeemed amount less the fees.
+    pub burned: u64,
+    /// Asset tokens received by the user, in the `PoolState::assets` order.
+    pub asset_amounts: Vec<u64>,
+    /// Basic asset tokens received by the user from the fund vault of basic asset.
+    pub basic_asset_amount: u64,
+}
+
+/// Fees of a creation or a redemption of `amount` fund tokens, the same as the fund program charges, see
+/// `fund::valuation::user_fees`.
+pub fn fees(
+    pool_state: &PoolState,
+    fee_accounts: &FeeAccounts,
+    user_fund_token_account: &Pubkey,
+    amount: u64,
+) -> Result<Fees> {
+    Ok(user_fees(
+        pool_state.fee_rate,
+        (
+            &fee_accounts.lqd_fee_vault,
+            &fee_accounts.initializer_fee_vault,
+            &fee_accounts.referrer_fee_account,
+        ),
+        user_fund_token_account,
+        amount,
+    )?)
+}
+
+/// Redemption of `amount` fund tokens from the fund `vault_balances`, `basic_asset_balance` and `fund_token_supply`
+/// before the redemption, the same way as `Pool::process_redemption`. The assets are rounded down, in favor of the
+/// fund.
+pub fn redemption(
+    pool_state: &PoolState,
+    fee_accounts: &FeeAccounts,
+    user_fund_token_account: &Pubkey,
+    vault_balances: &[u64],
+    basic_asset_balance: u64,
+    fund_token_supply: u64,
+    amount: u64,
+) -> Result<Redemption> {
+    if fund_token_supply == 0 {
+        bail!("Fund is empty");
+    }
+    let fees = fees(pool_state, fee_accounts, user_fund_token_account, amount)?;
+    let burned = amount - fees.total_fee();
+    let asset_amounts = vault_balances
+        .iter()
+        .map(|&balance| {
+            let asset_amount = balance as u128 * burned as u128 / fund_token_supply as u128;
+            if asset_amount > i64::MAX as u128 {
+                return Err(anyhow!("Redeemed asset amount {} overflow", asset_amount));
+            }
+            Ok(asset_amount as u64)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let basic_asset_amount = (basic_asset_balance as u128 * burned as u128 / fund_token_supply as u128) as u64;
+
+    Ok(Redemption {
+        fees,
+        burned,
+        asset_amounts,
+        basic_asset_amount,
+    })
+}
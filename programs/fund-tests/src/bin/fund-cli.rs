@@ -0,0 +1,717 @@
+use std::{env, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use fund::state::FundState;
+use fund_tests::{
+    basket,
+    client::{Client, FundClient},
+    instruction::{self, FeeAccounts, InitializeAccounts, UserAccounts},
+    spec::FundSpec,
+    token,
+};
+use serum_pool::schema::{fee_owner::ID as POOL_FEE_OWNER_ID, PoolAction, PoolState};
+use solana_client::rpc_client::RpcClient;
+use solana_program::{instruction::Instruction, program_pack::Pack, pubkey::Pubkey};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::{read_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token::state::Mint;
+
+fn main() -> Result<()> {
+    dotenv::from_filename(".env.local").ok();
+
+    let fund_arg = Arg::with_name("fund")
+        .long("fund")
+        .value_name("PUBKEY")
+        .takes_value(true)
+        .required(true)
+        .help("Fund account");
+    let admin_arg = Arg::with_name("admin")
+        .long("admin")
+        .value_name("KEYPAIR")
+        .takes_value(true)
+        .help("Fund admin keypair file, the payer by default");
+    let owner_arg = Arg::with_name("owner")
+        .long("owner")
+        .value_name("KEYPAIR")
+        .takes_value(true)
+        .help("Owner keypair file of the user token accounts, the payer by default");
+    let spec_arg = Arg::with_name("spec")
+        .long("spec")
+        .value_name("FILE")
+        .takes_value(true)
+        .required(true)
+        .help("Fund spec file, JSON or TOML by the .toml extension");
+    let amount_arg = Arg::with_name("amount")
+        .long("amount")
+        .value_name("AMOUNT")
+        .takes_value(true)
+        .required(true)
+        .help("Amount of fund tokens");
+    let referrer_arg = Arg::with_name("referrer")
+        .long("referrer")
+        .value_name("PUBKEY")
+        .takes_value(true)
+        .help("Fund token account receiving the referrer fee, the initializer fee vault by default");
+
+    let matches = App::new("fund-cli")
+        .about("Manages DEX traded funds")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .global(true)
+                .help("RPC URL, RPC_URL by default"),
+        )
+        .arg(
+            Arg::with_name("keypair")
+                .long("keypair")
+                .value_name("KEYPAIR")
+                .takes_value(true)
+                .global(true)
+                .help("Payer keypair file, PAYER_KEYPAIR_FILE by default"),
+        )
+        .arg(
+            Arg::with_name("program_id")
+                .long("program-id")
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .global(true)
+                .help("Fund program ID, FUND_PROGRAM_ID by default"),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Creates a fund from a JSON fund spec, the admin transfers the initial assets")
+                .arg(spec_arg.clone())
+                .arg(admin_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("deposit")
+                .alias("create")
+                .about("Creates fund tokens for the fund basket of assets")
+                .arg(fund_arg.clone())
+                .arg(amount_arg.clone())
+                .arg(owner_arg.clone())
+                .arg(referrer_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("redeem")
+                .about("Redeems fund tokens for the fund basket of assets")
+                .arg(fund_arg.clone())
+                .arg(amount_arg.clone())
+                .arg(owner_arg.clone())
+                .arg(referrer_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("zap-in")
+                .about("Creates fund tokens for the basic asset, buying the fund assets by their weights")
+                .arg(fund_arg.clone())
+                .arg(
+                    Arg::with_name("amount")
+                        .long("amount")
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Amount of basic asset tokens"),
+                )
+                .arg(
+                    Arg::with_name("min_tokens_out")
+                        .long("min-tokens-out")
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .help("Minimum fund tokens received, the quote at the fund maximum slippage by default"),
+                )
+                .arg(owner_arg.clone())
+                .arg(referrer_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("zap-out")
+                .about("Redeems fund tokens for the basic asset, selling the redeemed fund assets")
+                .arg(fund_arg.clone())
+                .arg(amount_arg)
+                .arg(
+                    Arg::with_name("minimum_amount_out")
+                        .long("minimum-amount-out")
+                        .value_name("AMOUNT")
+                        .takes_value(true)
+                        .help("Minimum basic asset tokens received, the quote at the fund maximum slippage by default"),
+                )
+                .arg(owner_arg.clone())
+                .arg(referrer_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("swap")
+                .about("Swaps assets in kind with the fund at the oracle prices, towards the target weights")
+                .arg(fund_arg.clone())
+                .arg(
+                    Arg::with_name("inputs")
+                        .long("inputs")
+                        .value_name("AMOUNTS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Comma separated amounts deposited of each fund asset"),
+                )
+                .arg(
+                    Arg::with_name("outputs")
+                        .long("outputs")
+                        .value_name("AMOUNTS")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Comma separated amounts withdrawn of each fund asset"),
+                )
+                .arg(owner_arg)
+                .arg(referrer_arg),
+        )
+        .subcommand(
+            SubCommand::with_name("rebalance")
+                .about("Rebalances the fund assets to their target weights")
+                .arg(fund_arg.clone())
+                .arg(admin_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("pause")
+                .about("Pauses creations and redemptions")
+                .arg(fund_arg.clone())
+                .arg(admin_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("unpause")
+                .about("Resumes creations and redemptions")
+                .arg(fund_arg.clone())
+                .arg(admin_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("update-fee")
+                .about("Modifies the fund fee rate")
+                .arg(fund_arg.clone())
+                .arg(admin_arg.clone())
+                .arg(
+                    Arg::with_name("fee_rate")
+                        .long("fee-rate")
+                        .value_name("FEE_RATE")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Fee rate, in millionths"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("update-admin")
+                .about("Transfers the fund admin permission")
+                .arg(fund_arg.clone())
+                .arg(admin_arg)
+                .arg(
+                    Arg::with_name("new_admin")
+                        .long("new-admin")
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("New admin keypair file"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Prints the fund state, balances and rebalance plan")
+                .arg(fund_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Prints the differences of the fund state from a fund spec")
+                .arg(fund_arg)
+                .arg(spec_arg),
+        )
+        .get_matches();
+
+    let (command, command_matches) = matches.subcommand();
+    let command_matches = command_matches.ok_or_else(|| anyhow!("Missing command"))?;
+
+    let program_id = Pubkey::from_str(&config_value(command_matches, "program_id", "FUND_PROGRAM_ID")?)?;
+    let payer_keypair_file = config_value(command_matches, "keypair", "PAYER_KEYPAIR_FILE")?;
+    let rpc_client = RpcClient::new_with_commitment(
+        config_value(command_matches, "url", "RPC_URL")?,
+        CommitmentConfig::confirmed(),
+    );
+    let mut client = Client {
+        client: rpc_client,
+        payer: read_keypair(&payer_keypair_file)?,
+    };
+
+    match command {
+        "init" => {
+            let admin = signer_of(command_matches, "admin", &payer_keypair_file)?;
+            let spec = FundSpec::from_file(command_matches.value_of("spec").unwrap())?;
+            init(&mut client, &program_id, &admin, &spec)
+        },
+        "deposit" | "redeem" => {
+            let fund = pubkey_of(command_matches, "fund")?;
+            let owner = signer_of(command_matches, "owner", &payer_keypair_file)?;
+            let amount = u64::from_str(command_matches.value_of("amount").unwrap())?;
+            let referrer = command_matches.value_of("referrer").map(Pubkey::from_str).transpose()?;
+            let action = if command == "deposit" {
+                PoolAction::Create(amount)
+            } else {
+                PoolAction::Redeem(amount)
+            };
+            execute(&mut client, &program_id, &fund, &owner, referrer.as_ref(), action)
+        },
+        "zap-in" => {
+            let fund = pubkey_of(command_matches, "fund")?;
+            let owner = signer_of(command_matches, "owner", &payer_keypair_file)?;
+            let amount = u64::from_str(command_matches.value_of("amount").unwrap())?;
+            let min_tokens_out = command_matches
+                .value_of("min_tokens_out")
+                .map(u64::from_str)
+                .transpose()?;
+            let referrer = command_matches.value_of("referrer").map(Pubkey::from_str).transpose()?;
+            zap_in(
+                &mut client,
+                &program_id,
+                &fund,
+                &owner,
+                referrer.as_ref(),
+                amount,
+                min_tokens_out,
+            )
+        },
+        "zap-out" => {
+            let fund = pubkey_of(command_matches, "fund")?;
+            let owner = signer_of(command_matches, "owner", &payer_keypair_file)?;
+            let amount = u64::from_str(command_matches.value_of("amount").unwrap())?;
+            let minimum_amount_out = command_matches
+                .value_of("minimum_amount_out")
+                .map(u64::from_str)
+                .transpose()?;
+            let referrer = command_matches.value_of("referrer").map(Pubkey::from_str).transpose()?;
+            zap_out(
+                &mut client,
+                &program_id,
+                &fund,
+                &owner,
+                referrer.as_ref(),
+                amount,
+                minimum_amount_out,
+            )
+        },
+        "swap" => {
+            let fund = pubkey_of(command_matches, "fund")?;
+            let owner = signer_of(command_matches, "owner", &payer_keypair_file)?;
+            let inputs = amounts_of(command_matches, "inputs")?;
+            let outputs = amounts_of(command_matches, "outputs")?;
+            let referrer = command_matches.value_of("referrer").map(Pubkey::from_str).transpose()?;
+            swap(
+                &mut client,
+                &program_id,
+                &fund,
+                &owner,
+                referrer.as_ref(),
+                &inputs,
+                &outputs,
+            )
+        },
+        "rebalance" => {
+            let fund = pubkey_of(command_matches, "fund")?;
+            let admin = signer_of(command_matches, "admin", &payer_keypair_file)?;
+            let planner = client.get_rebalance_planner(&fund)?;
+            let instruction = planner.rebalance_instruction(&program_id, &admin.pubkey())?;
+            send(&mut client, &[instruction], &[&admin])?;
+            show(&client, &fund)
+        },
+        "pause" | "unpause" | "update-fee" | "update-admin" => {
+            let fund = pubkey_of(command_matches, "fund")?;
+            let admin = signer_of(command_matches, "admin", &payer_keypair_file)?;
+            match command {
+                "pause" => {
+                    let instruction = instruction::pause(&program_id, &fund, &admin.pubkey())?;
+                    send(&mut client, &[instruction], &[&admin])?;
+                },
+                "unpause" => {
+                    let (pool_state, _) = client.get_fund_state(&fund)?;
+                    let instruction = instruction::unpause(&program_id, &fund, &admin.pubkey(), &pool_state)?;
+                    send(&mut client, &[instruction], &[&admin])?;
+                },
+                "update-fee" => {
+                    let fee_rate = u32::from_str(command_matches.value_of("fee_rate").unwrap())?;
+                    let instruction = instruction::update_fee(&program_id, &fund, &admin.pubkey(), fee_rate)?;
+                    send(&mut client, &[instruction], &[&admin])?;
+                },
+                _ => {
+                    let new_admin = read_keypair(command_matches.value_of("new_admin").unwrap())?;
+                    let instruction =
+                        instruction::update_admin(&program_id, &fund, &admin.pubkey(), &new_admin.pubkey())?;
+                    send(&mut client, &[instruction], &[&admin, &new_admin])?;
+                },
+            }
+            show(&client, &fund)
+        },
+        "show" => show(&client, &pubkey_of(command_matches, "fund")?),
+        "diff" => {
+            let spec = FundSpec::from_file(command_matches.value_of("spec").unwrap())?;
+            let (pool_state, fund_state) = client.get_fund_state(&pubkey_of(command_matches, "fund")?)?;
+            let differences = spec.diff(&pool_state, &fund_state);
+            if differences.is_empty() {
+                println!("fund matches the spec");
+            }
+            for difference in differences {
+                println!("{}", difference);
+            }
+            Ok(())
+        },
+        _ => Err(anyhow!("Unknown command {}", command)),
+    }
+}
+
+/// Value of a command line argument, otherwise of the environment variable `var`.
+fn config_value(matches: &ArgMatches, name: &str, var: &str) -> Result<String> {
+    match matches.value_of(name) {
+        Some(value) => Ok(value.to_string()),
+        None => env::var(var).map_err(|_| anyhow!("Missing --{} or {}", name.replace('_', "-"), var)),
+    }
+}
+
+fn pubkey_of(matches: &ArgMatches, name: &str) -> Result<Pubkey> {
+    Ok(Pubkey::from_str(matches.value_of(name).unwrap())?)
+}
+
+/// Comma separated amounts of an argument.
+fn amounts_of(matches: &ArgMatches, name: &str) -> Result<Vec<u64>> {
+    matches
+        .value_of(name)
+        .unwrap()
+        .split(',')
+        .map(|amount| Ok(u64::from_str(amount.trim())?))
+        .collect()
+}
+
+fn read_keypair(path: &str) -> Result<Keypair> {
+    read_keypair_file(path).map_err(|err| anyhow!("Read keypair {} error: {}", path, err))
+}
+
+/// Keypair of a signer argument, the payer keypair by default.
+fn signer_of(matches: &ArgMatches, name: &str, payer_keypair_file: &str) -> Result<Keypair> {
+    read_keypair(matches.value_of(name).unwrap_or(payer_keypair_file))
+}
+
+fn send(client: &mut Client, instructions: &[Instruction], signers: &[&Keypair]) -> Result<()> {
+    let mut transaction = Transaction::new_with_payer(instructions, Some(&client.payer_pubkey()));
+    let mut keypairs = vec![client.payer()];
+    keypairs.extend(signers);
+    transaction.sign(&keypairs, client.recent_blockhash());
+    client.process_transaction(&transaction)?;
+    Ok(())
+}
+
+fn token_decimals(client: &Client, mint: &Pubkey) -> Result<u8> {
+    Ok(Mint::unpack(&client.get_account(mint)?.data)?.decimals)
+}
+
+fn init(client: &mut Client, program_id: &Pubkey, admin: &Keypair, spec: &FundSpec) -> Result<()> {
+    let fund_account = client.create_account(program_id, spec.account_len(), None);
+    let (vault_signer, vault_signer_nonce) =
+        Pubkey::find_program_address(&[fund_account.pubkey().as_ref()], program_id);
+
+    let fund_token_mint = token::create_token(client, &vault_signer, spec.fund_token_decimals);
+    let initial_supply_fund_token_account = token::create_account(client, &vault_signer, &fund_token_mint.pubkey());
+
+    // Create the fund vaults and transfer the initial assets
+    let mut vaults = Vec::with_capacity(spec.assets.len());
+    let mut intermediate_vaults = Vec::with_capacity(spec.assets.len());
+    for asset in &spec.assets {
+        let vault = token::create_account(client, &vault_signer, &asset.mint).pubkey();
+        if asset.initial_amount > 0 {
+            let admin_token_account = client
+                .find_token_account(&admin.pubkey(), &asset.mint)?
+                .ok_or_else(|| anyhow!("Admin has no token account of the asset {}", asset.mint))?;
+            let decimals = token_decimals(client, &asset.mint)?;
+            token::transfer_to(
+                client,
+                admin,
+                &asset.mint,
+                &admin_token_account,
+                &vault,
+                asset.initial_amount,
+                decimals,
+            );
+        }
+        vaults.push(vault);
+        intermediate_vaults.push(
+            asset
+                .intermediate
+                .as_ref()
+                .map(|intermediate| token::create_account(client, &vault_signer, &intermediate.mint).pubkey()),
+        );
+    }
+    let basic_asset_vault = token::create_account(client, &vault_signer, &spec.basic_asset_mint).pubkey();
+
+    // Fees
+    let initializer_fee_vault = token::create_account(client, &admin.pubkey(), &fund_token_mint.pubkey()).pubkey();
+    let lqd_fee_vault = token::create_account(client, &POOL_FEE_OWNER_ID, &fund_token_mint.pubkey()).pubkey();
+
+    let fund_data = spec.initialize_fund_data(&intermediate_vaults)?;
+    let request = spec.initialize_pool_request(vault_signer_nonce, &fund_data)?;
+    let accounts = InitializeAccounts {
+        fund: fund_account.pubkey(),
+        fund_token_mint: fund_token_mint.pubkey(),
+        vaults,
+        vault_signer,
+        lqd_fee_vault,
+        initializer_fee_vault,
+        admin: admin.pubkey(),
+        initial_supply_fund_token_account: initial_supply_fund_token_account.pubkey(),
+        basic_asset_vault,
+    };
+    send(client, &[instruction::initialize(program_id, &accounts, request)?], &[])?;
+
+    println!("fund: {}", fund_account.pubkey());
+    show(client, &fund_account.pubkey())
+}
+
+/// Token accounts of the `owner`, creating the fund token and basic asset accounts if there are none.
+fn user_accounts(
+    client: &mut Client,
+    owner: &Keypair,
+    pool_state: &PoolState,
+    fund_state: &FundState,
+) -> Result<UserAccounts> {
+    let fund_token_mint = pool_state.pool_token_mint.pubkey();
+    let basic_asset_mint = fund_state.basic_asset.mint.pubkey();
+
+    let fund_token_account = match client.find_token_account(&owner.pubkey(), &fund_token_mint)? {
+        Some(fund_token_account) => fund_token_account,
+        None => token::create_account(client, &owner.pubkey(), &fund_token_mint).pubkey(),
+    };
+    let basic_asset_account = match client.find_token_account(&owner.pubkey(), &basic_asset_mint)? {
+        Some(basic_asset_account) => basic_asset_account,
+        None => token::create_account(client, &owner.pubkey(), &basic_asset_mint).pubkey(),
+    };
+    let asset_accounts = pool_state
+        .assets
+        .iter()
+        .map(|asset| {
+            client
+                .find_token_account(&owner.pubkey(), &asset.mint)?
+                .ok_or_else(|| anyhow!("Owner has no token account of the asset {}", asset.mint.pubkey()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(UserAccounts {
+        fund_token_account,
+        asset_accounts,
+        basic_asset_account,
+        authority: owner.pubkey(),
+    })
+}
+
+fn execute(
+    client: &mut Client,
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    owner: &Keypair,
+    referrer: Option<&Pubkey>,
+    action: PoolAction,
+) -> Result<()> {
+    let (pool_state, fund_state) = client.get_fund_state(fund)?;
+    let user = user_accounts(client, owner, &pool_state, &fund_state)?;
+    let fund_token_account = user.fund_token_account;
+
+    let fee_accounts = FeeAccounts::new(&pool_state, referrer);
+
+    if let PoolAction::Redeem(amount) = &action {
+        let redemption = client.quote_redemption(fund, &fund_token_account, &fee_accounts, *amount)?;
+        println!("fees: {:?}", redemption.fees);
+        for (i, asset_amount) in redemption.asset_amounts.iter().enumerate() {
+            println!("{} redeemed asset amount: {}", i, asset_amount);
+        }
+        println!("redeemed basic asset amount: {}", redemption.basic_asset_amount);
+    }
+
+    let instruction = instruction::execute(program_id, fund, &pool_state, &user, &fee_accounts, action)?;
+    send(client, &[instruction], &[owner])?;
+
+    let balance = client.get_token_account_balance(&fund_token_account)?;
+    println!("fund token balance: {:?}", balance.ui_amount);
+    show(client, fund)
+}
+
+fn zap_in(
+    client: &mut Client,
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    owner: &Keypair,
+    referrer: Option<&Pubkey>,
+    amount: u64,
+    min_tokens_out: Option<u64>,
+) -> Result<()> {
+    let planner = client.get_rebalance_planner(fund)?;
+    let pool_state = &planner.pool_state;
+    let fund_token_mint = pool_state.pool_token_mint.pubkey();
+    let basic_asset_mint = planner.fund_state.basic_asset.mint.pubkey();
+
+    let basic_asset_account = client
+        .find_token_account(&owner.pubkey(), &basic_asset_mint)?
+        .ok_or_else(|| anyhow!("Owner has no token account of the basic asset {}", basic_asset_mint))?;
+    let fund_token_account = match client.find_token_account(&owner.pubkey(), &fund_token_mint)? {
+        Some(fund_token_account) => fund_token_account,
+        None => token::create_account(client, &owner.pubkey(), &fund_token_mint).pubkey(),
+    };
+    let fee_accounts = FeeAccounts::new(pool_state, referrer);
+
+    // The quotes less the fees received by the user
+    let fund_token_supply = Mint::unpack(&client.get_account(&fund_token_mint)?.data)?.supply;
+    let user_tokens = |tokens: u64| -> Result<u64> {
+        Ok(tokens - basket::fees(pool_state, &fee_accounts, &fund_token_account, tokens)?.total_fee())
+    };
+    let expected = user_tokens(planner.quote_zap_in(amount, fund_token_supply, 0)?)?;
+    let min_tokens_out = match min_tokens_out {
+        Some(min_tokens_out) => min_tokens_out,
+        None => user_tokens(planner.quote_zap_in(amount, fund_token_supply, planner.fund_state.max_slippage_bps)?)?,
+    };
+    println!("expected fund tokens: {}, minimum: {}", expected, min_tokens_out);
+
+    let instruction = planner.zap_in_instruction(
+        program_id,
+        &basic_asset_account,
+        &fund_token_account,
+        &owner.pubkey(),
+        &fee_accounts,
+        amount,
+        min_tokens_out,
+    )?;
+    send(client, &[instruction], &[owner])?;
+
+    let balance = client.get_token_account_balance(&fund_token_account)?;
+    println!("fund token balance: {:?}", balance.ui_amount);
+    show(client, fund)
+}
+
+fn zap_out(
+    client: &mut Client,
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    owner: &Keypair,
+    referrer: Option<&Pubkey>,
+    amount: u64,
+    minimum_amount_out: Option<u64>,
+) -> Result<()> {
+    let planner = client.get_rebalance_planner(fund)?;
+    let pool_state = &planner.pool_state;
+    let fund_token_mint = pool_state.pool_token_mint.pubkey();
+    let basic_asset_mint = planner.fund_state.basic_asset.mint.pubkey();
+
+    let fund_token_account = client
+        .find_token_account(&owner.pubkey(), &fund_token_mint)?
+        .ok_or_else(|| anyhow!("Owner has no fund token account"))?;
+    let basic_asset_account = match client.find_token_account(&owner.pubkey(), &basic_asset_mint)? {
+        Some(basic_asset_account) => basic_asset_account,
+        None => token::create_account(client, &owner.pubkey(), &basic_asset_mint).pubkey(),
+    };
+    let fee_accounts = FeeAccounts::new(pool_state, referrer);
+
+    let fund_token_supply = Mint::unpack(&client.get_account(&fund_token_mint)?.data)?.supply;
+    let fees = basket::fees(pool_state, &fee_accounts, &fund_token_account, amount)?;
+    let redemption_size = amount - fees.total_fee();
+    let expected = planner.quote_zap_out(redemption_size, fund_token_supply, 0)?;
+    let minimum_amount_out = match minimum_amount_out {
+        Some(minimum_amount_out) => minimum_amount_out,
+        None => planner.quote_zap_out(redemption_size, fund_token_supply, planner.fund_state.max_slippage_bps)?,
+    };
+    println!("fees: {:?}", fees);
+    println!(
+        "expected basic asset amount: {}, minimum: {}",
+        expected, minimum_amount_out
+    );
+
+    let instruction = planner.zap_out_instruction(
+        program_id,
+        &fund_token_account,
+        &basic_asset_account,
+        &owner.pubkey(),
+        &fee_accounts,
+        amount,
+        minimum_amount_out,
+    )?;
+    send(client, &[instruction], &[owner])?;
+
+    let balance = client.get_token_account_balance(&basic_asset_account)?;
+    println!("basic asset balance: {:?}", balance.ui_amount);
+    show(client, fund)
+}
+
+fn swap(
+    client: &mut Client,
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    owner: &Keypair,
+    referrer: Option<&Pubkey>,
+    inputs: &[u64],
+    outputs: &[u64],
+) -> Result<()> {
+    let planner = client.get_rebalance_planner(fund)?;
+    let user = user_accounts(client, owner, &planner.pool_state, &planner.fund_state)?;
+    let fee_accounts = FeeAccounts::new(&planner.pool_state, referrer);
+
+    println!("basket: {:?}", planner.swap_basket(inputs, outputs)?);
+
+    let instruction = planner.swap_instruction(program_id, &user, &fee_accounts, inputs, outputs)?;
+    send(client, &[instruction], &[owner])?;
+    show(client, fund)
+}
+
+fn show(client: &Client, fund: &Pubkey) -> Result<()> {
+    let (pool_state, fund_state) = client.get_fund_state(fund)?;
+
+    println!("name: {}", pool_state.name);
+    println!(
+        "admin: {}",
+        pool_state
+            .admin_key
+            .as_ref()
+            .map_or_else(|| "none".to_string(), |admin| admin.pubkey().to_string())
+    );
+    println!("fee rate: {}", pool_state.fee_rate);
+    println!("paused: {}", fund_state.paused);
+    println!(
+        "last rebalance: slot {}, unix timestamp {}",
+        fund_state.last_rebalance_slot, fund_state.last_rebalance_timestamp
+    );
+
+    let fund_token_supply = client.get_token_supply(&pool_state.pool_token_mint)?;
+    println!(
+        "fund token {}: supply {:?}",
+        pool_state.pool_token_mint.pubkey(),
+        fund_token_supply.ui_amount
+    );
+
+    for (i, asset) in pool_state.assets.iter().enumerate() {
+        let balance = client.get_token_account_balance(&asset.vault_address)?;
+        println!(
+            "{} asset {}: weight {}, balance {:?}",
+            i,
+            asset.mint.pubkey(),
+            fund_state.asset_weights[i],
+            balance.ui_amount
+        );
+    }
+    let balance = client.get_token_account_balance(&fund_state.basic_asset.vault_address)?;
+    println!(
+        "basic asset {}: weight {}, balance {:?}",
+        fund_state.basic_asset.mint.pubkey(),
+        fund_state.basic_asset_weight,
+        balance.ui_amount
+    );
+
+    match client.get_rebalance_planner(fund) {
+        Ok(planner) => {
+            println!("nav: {}", planner.nav());
+            let drifts = planner.drift_bps();
+            for (i, trade) in planner.planned_trades()?.iter().enumerate() {
+                println!("{} drift: {} bps, planned trade: {:?}", i, drifts[i], trade);
+            }
+        },
+        Err(err) => println!("rebalance plan unavailable: {}", err),
+    }
+
+    Ok(())
+}
@@ -1,12 +1,46 @@
-use anyhow::Result;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
 use borsh::de::BorshDeserialize;
-use fund::state::FundState;
+use fund::state::{FundState, FundStateContainer, PlannedTrade};
 use serum_pool::schema::PoolState;
+use solana_client::rpc_request::TokenAccountsFilter;
 pub use solana_client_helpers::Client;
-use solana_program::pubkey::Pubkey;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use spl_token::state::{Account as TokenAccount, Mint};
+
+use crate::{
+    basket::{self, Redemption},
+    instruction::FeeAccounts,
+    planner::{self, Accounts, RebalancePlanner},
+};
+
+/// Maximum number of accounts of a `getMultipleAccounts` request.
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
 
 pub trait FundClient {
     fn get_fund_state(&self, fund_account: &Pubkey) -> Result<(PoolState, FundState)>;
+
+    /// Finds a token account of the `mint` owned by `owner`.
+    fn find_token_account(&self, owner: &Pubkey, mint: &Pubkey) -> Result<Option<Pubkey>>;
+
+    /// Fetches the accounts `addresses`, all of them must exist.
+    fn get_accounts(&self, addresses: &[Pubkey]) -> Result<Accounts>;
+
+    /// Fetches the fund accounts and plans a rebalance with the current balances and prices.
+    fn get_rebalance_planner(&self, fund_account: &Pubkey) -> Result<RebalancePlanner>;
+
+    /// Plans the trades of a rebalance with the current balances and prices, as the `SimulateRebalance` instruction.
+    fn simulate_rebalance(&self, fund_account: &Pubkey) -> Result<Vec<PlannedTrade>>;
+
+    /// Computes a redemption of `amount` fund tokens from the user `fund_token_account` with the current balances.
+    fn quote_redemption(
+        &self,
+        fund_account: &Pubkey,
+        fund_token_account: &Pubkey,
+        fee_accounts: &FeeAccounts,
+        amount: u64,
+    ) -> Result<Redemption>;
 }
 
 impl FundClient for Client {
@@ -15,10 +49,80 @@ impl FundClient for Client {
 
         let mut data = fund_account.data.as_slice();
         let pool_state: PoolState = BorshDeserialize::deserialize(&mut data)?;
-
-        data = pool_state.custom_state.as_slice();
-        let fund_state: FundState = BorshDeserialize::deserialize(&mut data)?;
+        let fund_state = pool_state.read_fund_state()?;
 
         Ok((pool_state, fund_state))
     }
+
+    fn find_token_account(&self, owner: &Pubkey, mint: &Pubkey) -> Result<Option<Pubkey>> {
+        let token_accounts = self.get_token_accounts_by_owner(owner, TokenAccountsFilter::Mint(*mint))?;
+        token_accounts
+            .first()
+            .map(|token_account| Ok(Pubkey::from_str(&token_account.pubkey)?))
+            .transpose()
+    }
+
+    fn get_accounts(&self, addresses: &[Pubkey]) -> Result<Accounts> {
+        let mut accounts = Accounts::with_capacity(addresses.len());
+        for chunk in addresses.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            for (address, account) in chunk.iter().zip(self.get_multiple_accounts(chunk)?) {
+                let account = account.ok_or_else(|| anyhow!("Account {} not found", address))?;
+                accounts.insert(*address, account);
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn get_rebalance_planner(&self, fund_account: &Pubkey) -> Result<RebalancePlanner> {
+        let (pool_state, fund_state) = self.get_fund_state(fund_account)?;
+
+        let mut accounts = self.get_accounts(&planner::fund_accounts(&pool_state, &fund_state))?;
+        let referenced_accounts = planner::referenced_accounts(&fund_state, &accounts)?;
+        accounts.extend(self.get_accounts(&referenced_accounts)?);
+
+        RebalancePlanner::new(*fund_account, pool_state, fund_state, &accounts)
+    }
+
+    fn simulate_rebalance(&self, fund_account: &Pubkey) -> Result<Vec<PlannedTrade>> {
+        self.get_rebalance_planner(fund_account)?.planned_trades()
+    }
+
+    fn quote_redemption(
+        &self,
+        fund_account: &Pubkey,
+        fund_token_account: &Pubkey,
+        fee_accounts: &FeeAccounts,
+        amount: u64,
+    ) -> Result<Redemption> {
+        let (pool_state, fund_state) = self.get_fund_state(fund_account)?;
+
+        let fund_token_mint = pool_state.pool_token_mint.pubkey();
+        let basic_asset_vault = fund_state.basic_asset.vault_address.pubkey();
+        let vaults: Vec<Pubkey> = pool_state
+            .assets
+            .iter()
+            .map(|asset| asset.vault_address.pubkey())
+            .collect();
+        let mut addresses = vaults.clone();
+        addresses.push(fund_token_mint);
+        addresses.push(basic_asset_vault);
+        let accounts = self.get_accounts(&addresses)?;
+
+        let vault_balances = vaults
+            .iter()
+            .map(|vault| Ok(TokenAccount::unpack(&accounts[vault].data)?.amount))
+            .collect::<Result<Vec<_>>>()?;
+        let fund_token_supply = Mint::unpack(&accounts[&fund_token_mint].data)?.supply;
+        let basic_asset_balance = TokenAccount::unpack(&accounts[&basic_asset_vault].data)?.amount;
+
+        basket::redemption(
+            &pool_state,
+            fee_accounts,
+            fund_token_account,
+            &vault_balances,
+            basic_asset_balance,
+            fund_token_supply,
+            amount,
+        )
+    }
 }
@@ -0,0 +1,226 @@
+use anyhow::Result;
+use borsh::ser::BorshSerialize;
+use fund::{
+    instruction::{FundInstructionInner, FundRequest, FundRequestTag},
+    state::FundStateContainer,
+};
+use serum_pool::schema::{InitializePoolRequest, PoolAction, PoolRequest, PoolRequestInner, PoolRequestTag, PoolState};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    sysvar,
+};
+
+/// Accounts of a new fund, see `InitializeFundData`.
+pub struct InitializeAccounts {
+    pub fund: Pubkey,
+    pub fund_token_mint: Pubkey,
+    pub vaults: Vec<Pubkey>,
+    pub vault_signer: Pubkey,
+    pub lqd_fee_vault: Pubkey,
+    pub initializer_fee_vault: Pubkey,
+    pub admin: Pubkey,
+    pub initial_supply_fund_token_account: Pubkey,
+    pub basic_asset_vault: Pubkey,
+}
+
+/// Fund token accounts receiving the fees of a creation or a redemption.
+pub struct FeeAccounts {
+    pub lqd_fee_vault: Pubkey,
+    pub initializer_fee_vault: Pubkey,
+    pub referrer_fee_account: Pubkey,
+}
+
+impl FeeAccounts {
+    /// Fee vaults of the fund, the initializer fee vault receives the referrer fee too without a `referrer` fund token
+    /// account.
+    pub fn new(pool_state: &PoolState, referrer: Option<&Pubkey>) -> Self {
+        let initializer_fee_vault = pool_state.initializer_fee_vault.pubkey();
+        Self {
+            lqd_fee_vault: pool_state.lqd_fee_vault.pubkey(),
+            initializer_fee_vault,
+            referrer_fee_account: referrer.copied().unwrap_or(initializer_fee_vault),
+        }
+    }
+}
+
+/// Token accounts of a user creating or redeeming fund tokens.
+pub struct UserAccounts {
+    pub fund_token_account: Pubkey,
+    /// Token account of each of the fund assets, in the `PoolState::assets` order.
+    pub asset_accounts: Vec<Pubkey>,
+    /// Token account of the basic asset, transferring the share of the fund vault of basic asset.
+    pub basic_asset_account: Pubkey,
+    pub authority: Pubkey,
+}
+
+pub fn initialize(
+    program_id: &Pubkey,
+    accounts: &InitializeAccounts,
+    request: InitializePoolRequest,
+) -> Result<Instruction> {
+    let mut account_metas = vec![
+        AccountMeta::new(accounts.fund, false),
+        AccountMeta::new(accounts.fund_token_mint, false),
+    ];
+    account_metas.extend(accounts.vaults.iter().map(|vault| AccountMeta::new(*vault, false)));
+    account_metas.extend(vec![
+        AccountMeta::new_readonly(accounts.vault_signer, false),
+        AccountMeta::new_readonly(accounts.lqd_fee_vault, false),
+        AccountMeta::new_readonly(accounts.initializer_fee_vault, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new(accounts.admin, false),
+        AccountMeta::new(accounts.initial_supply_fund_token_account, false),
+        AccountMeta::new_readonly(accounts.basic_asset_vault, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: account_metas,
+        data: PoolRequest {
+            tag: PoolRequestTag::default(),
+            inner: PoolRequestInner::Initialize(request),
+        }
+        .try_to_vec()?,
+    })
+}
+
+/// Creation or redemption of fund tokens by the `user`.
+pub fn execute(
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    pool_state: &PoolState,
+    user: &UserAccounts,
+    fee_accounts: &FeeAccounts,
+    action: PoolAction,
+) -> Result<Instruction> {
+    let mut accounts = vec![
+        AccountMeta::new(*fund, false),
+        AccountMeta::new(pool_state.pool_token_mint.pubkey(), false),
+    ];
+    accounts.extend(
+        pool_state
+            .assets
+            .iter()
+            .map(|asset| AccountMeta::new(asset.vault_address.pubkey(), false)),
+    );
+    accounts.push(AccountMeta::new_readonly(pool_state.vault_signer.pubkey(), false));
+    accounts.push(AccountMeta::new(user.fund_token_account, false));
+    accounts.extend(
+        user.asset_accounts
+            .iter()
+            .map(|asset_account| AccountMeta::new(*asset_account, false)),
+    );
+    accounts.extend(vec![
+        AccountMeta::new_readonly(user.authority, true),
+        AccountMeta::new(fee_accounts.lqd_fee_vault, false),
+        AccountMeta::new(fee_accounts.initializer_fee_vault, false),
+        AccountMeta::new(fee_accounts.referrer_fee_account, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ]);
+    if let PoolAction::Create(_) | PoolAction::Redeem(_) = action {
+        let fund_state = pool_state.read_fund_state()?;
+        accounts.extend(vec![
+            AccountMeta::new(fund_state.basic_asset.vault_address.pubkey(), false),
+            AccountMeta::new(user.basic_asset_account, false),
+        ]);
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data: PoolRequest {
+            tag: PoolRequestTag::default(),
+            inner: PoolRequestInner::Execute(action),
+        }
+        .try_to_vec()?,
+    })
+}
+
+pub fn create(
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    pool_state: &PoolState,
+    user: &UserAccounts,
+    fee_accounts: &FeeAccounts,
+    amount: u64,
+) -> Result<Instruction> {
+    execute(
+        program_id,
+        fund,
+        pool_state,
+        user,
+        fee_accounts,
+        PoolAction::Create(amount),
+    )
+}
+
+pub fn redeem(
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    pool_state: &PoolState,
+    user: &UserAccounts,
+    fee_accounts: &FeeAccounts,
+    amount: u64,
+) -> Result<Instruction> {
+    execute(
+        program_id,
+        fund,
+        pool_state,
+        user,
+        fee_accounts,
+        PoolAction::Redeem(amount),
+    )
+}
+
+/// Fund instruction signed by the fund `admin`, with the `accounts` following the admin account.
+pub fn admin_instruction(
+    program_id: &Pubkey,
+    fund: &Pubkey,
+    admin: &Pubkey,
+    accounts: Vec<AccountMeta>,
+    inner: FundInstructionInner,
+) -> Result<Instruction> {
+    let mut account_metas = vec![AccountMeta::new(*fund, false), AccountMeta::new_readonly(*admin, true)];
+    account_metas.extend(accounts);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts: account_metas,
+        data: FundRequest {
+            tag: FundRequestTag::default(),
+            inner,
+        }
+        .try_to_vec()?,
+    })
+}
+
+pub fn pause(program_id: &Pubkey, fund: &Pubkey, admin: &Pubkey) -> Result<Instruction> {
+    admin_instruction(program_id, fund, admin, vec![], FundInstructionInner::Pause)
+}
+
+pub fn unpause(program_id: &Pubkey, fund: &Pubkey, admin: &Pubkey, pool_state: &PoolState) -> Result<Instruction> {
+    let vaults = pool_state
+        .assets
+        .iter()
+        .map(|asset| AccountMeta::new_readonly(asset.vault_address.pubkey(), false))
+        .collect();
+    admin_instruction(program_id, fund, admin, vaults, FundInstructionInner::Unpause)
+}
+
+pub fn update_fee(program_id: &Pubkey, fund: &Pubkey, admin: &Pubkey, fee_rate: u32) -> Result<Instruction> {
+    admin_instruction(program_id, fund, admin, vec![], FundInstructionInner::UpdateFee {
+        fee_rate,
+    })
+}
+
+pub fn update_admin(program_id: &Pubkey, fund: &Pubkey, admin: &Pubkey, new_admin: &Pubkey) -> Result<Instruction> {
+    admin_instruction(
+        program_id,
+        fund,
+        admin,
+        vec![AccountMeta::new_readonly(*new_admin, true)],
+        FundInstructionInner::UpdateAdmin,
+    )
+}
@@ -1,4 +1,8 @@
 pub use solana_client_helpers::print;
 
+pub mod basket;
 pub mod client;
+pub mod instruction;
+pub mod planner;
+pub mod spec;
 pub mod token;
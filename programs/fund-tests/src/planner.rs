@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use borsh::ser::BorshSerialize;
+use fund::{
+    dex,
+    instruction::{swap_request, FundInstructionInner, FundRequest, FundRequestTag},
+    oracle::Price,
+    price::ExchangeRate,
+    state::{AssetMarket, AssetSwap, FundState, PlannedTrade, BPS_DENOMINATOR},
+    valuation::{
+        bps_of, fund_tokens_for_value, oracle_exchange_rate, redeemed_amounts, split_by_weights, swap_basket, Valuation,
+    },
+    venue::VenuePrograms,
+};
+use serum_dex::state::{gen_vault_signer_key, MarketState};
+use serum_pool::schema::{PoolAction, PoolState};
+use solana_program::{
+    account_info::AccountInfo,
+    clock::Clock,
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::{self, Sysvar},
+};
+use solana_sdk::account::Account;
+use spl_token::state::Account as TokenAccount;
+use spl_token_swap::state::SwapVersion;
+
+use crate::instruction::{execute, FeeAccounts, UserAccounts};
+
+/// Fetched accounts by their addresses.
+pub type Accounts = HashMap<Pubkey, Account>;
+
+/// Addresses of the fund accounts read by the planner: the fund vaults, the token-swap pools and the intermediate
+/// vaults or the Serum DEX markets of the assets, the oracle price accounts and the clock sysvar checking their age.
+pub fn fund_accounts(pool_state: &PoolState, fund_state: &FundState) -> Vec<Pubkey> {
+    let mut addresses: Vec<Pubkey> = pool_state
+        .assets
+        .iter()
+        .map(|asset| asset.vault_address.pubkey())
+        .collect();
+    addresses.push(fund_state.basic_asset.vault_address.pubkey());
+
+    for (asset_swap, asset_market) in fund_state.asset_swaps.iter().zip(fund_state.asset_markets.iter()) {
+        match asset_market {
+            Some(asset_market) => addresses.push(asset_market.market.pubkey()),
+            None => {
+                addresses.push(asset_swap.swap.pubkey());
+                if let Some(hop) = &asset_swap.intermediate {
+                    addresses.push(hop.swap.pubkey());
+                    addresses.push(hop.vault.pubkey());
+                }
+            },
+        }
+    }
+
+    addresses.extend(
+        fund_state
+            .asset_oracles
+            .iter()
+            .flatten()
+            .map(|asset_oracle| asset_oracle.price_account.pubkey()),
+    );
+    addresses.push(sysvar::clock::id());
+    addresses
+}
+
+/// Addresses of the accounts referenced by the fetched `fund_accounts`: the reserves of the token-swap pools and the
+/// order books of the Serum DEX markets.
+pub fn referenced_accounts(fund_state: &FundState, accounts: &Accounts) -> Result<Vec<Pubkey>> {
+    let mut addresses = Vec::new();
+    for (asset_swap, asset_market) in fund_state.asset_swaps.iter().zip(fund_state.asset_markets.iter()) {
+        match asset_market {
+            Some(asset_market) => {
+                let market = load_market(accounts, asset_market)?;
+                addresses.push(market.bids);
+                addresses.push(market.asks);
+            },
+            None => {
+                let mut swaps = vec![asset_swap.swap.pubkey()];
+                swaps.extend(asset_swap.intermediate.iter().map(|hop| hop.swap.pubkey()));
+                for swap in swaps {
+                    let swap_state = SwapVersion::unpack(&get_account(accounts, &swap)?.data)?;
+                    addresses.push(*swap_state.token_a_account());
+                    addresses.push(*swap_state.token_b_account());
+                }
+            },
+        }
+    }
+    Ok(addresses)
+}
+
+/// Rebalance of a fund planned from its fetched accounts, the same way as the `Rebalance` instruction values the fund.
+pub struct RebalancePlanner {
+    pub fund_account: Pubkey,
+    pub pool_state: PoolState,
+    pub fund_state: FundState,
+    pub balances: Vec<u64>,
+    pub basic_asset_balance: u64,
+    pub valuation: Valuation,
+    /// `Rebalance` accounts of the venue of each asset.
+    venue_accounts: Vec<Vec<AccountMeta>>,
+}
+
+impl RebalancePlanner {
+    /// Values the fund from the fetched `fund_accounts` and `referenced_accounts`.
+    pub fn new(
+        fund_account: Pubkey,
+        pool_state: PoolState,
+        fund_state: FundState,
+        accounts: &Accounts,
+    ) -> Result<Self> {
+        let mut clock_account = get_account(accounts, &sysvar::clock::id())?.clone();
+        let clock = Clock::from_account_info(&account_info(&sysvar::clock::id(), &mut clock_account))?;
+
+        let mut balances = Vec::with_capacity(pool_state.assets.len());
+        let mut exchange_rates = Vec::with_capacity(pool_state.assets.len());
+        let mut venue_accounts = Vec::with_capacity(pool_state.assets.len());
+        let mut held_value = 0;
+        for (i, asset) in pool_state.assets.iter().enumerate() {
+            balances.push(token_amount(accounts, &asset.vault_address)?);
+
+            let (venue_rate, venue_held_value, asset_venue_accounts) =
+                asset_venue(accounts, &fund_state, i, &asset.mint)?;
+            held_value += venue_held_value;
+            let exchange_rate = match &fund_state.asset_oracles[i] {
+                Some(asset_oracle) => {
+                    let price = Price::unpack(&get_account(accounts, &asset_oracle.price_account)?.data)?;
+                    oracle_exchange_rate(
+                        &price,
+                        asset_oracle,
+                        &venue_rate,
+                        fund_state.max_oracle_deviation_bps,
+                        clock.slot,
+                    )?
+                },
+                None => venue_rate,
+            };
+            exchange_rates.push(exchange_rate);
+            venue_accounts.push(asset_venue_accounts);
+        }
+        let basic_asset_balance = token_amount(accounts, &fund_state.basic_asset.vault_address)?;
+
+        // The tokens held by the venues are counted with the basic asset, as by the fund program
+        let valuation = Valuation::new(
+            &fund_state.asset_weights,
+            fund_state.basic_asset_weight,
+            &balances,
+            basic_asset_balance + held_value,
+            exchange_rates,
+        )?;
+
+        Ok(Self {
+            fund_account,
+            pool_state,
+            fund_state,
+            balances,
+            basic_asset_balance,
+            valuation,
+            venue_accounts,
+        })
+    }
+
+    /// Net asset value of the fund in the basic asset, including the basic asset vault.
+    pub fn nav(&self) -> u128 {
+        self.valuation.total_amount
+    }
+
+    /// Deviation of each asset value from its target in basis points of the fund value, positive above the target.
+    pub fn drift_bps(&self) -> Vec<i64> {
+        self.valuation
+            .current_amounts
+            .iter()
+            .zip(self.valuation.need_amounts.iter())
+            .map(|(&amount, &need_amount)| {
+                if self.valuation.total_amount == 0 {
+                    return 0;
+                }
+                let drift = amount as i128 - need_amount as i128;
+                (drift * BPS_DENOMINATOR as i128 / self.valuation.total_amount as i128) as i64
+            })
+            .collect()
+    }
+
+    /// Trades of the `Rebalance` instruction, as reported by the `SimulateRebalance` instruction.
+    pub fn planned_trades(&self) -> Result<Vec<PlannedTrade>> {
+        Ok(self.valuation.planned_trades(
+            self.fund_state.rebalance_threshold_bps,
+            self.fund_state.max_slippage_bps,
+        )?)
+    }
+
+    /// Accounts of the `Rebalance` instruction signed by the fund `admin`.
+    pub fn rebalance_accounts(&self, admin: &Pubkey) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new(self.fund_account, false),
+            AccountMeta::new_readonly(*admin, true),
+        ];
+        accounts.extend(self.trade_accounts());
+        accounts
+    }
+
+    /// `Rebalance` accounts after the admin account, also read by the `Crank` and `ZapIn` instructions.
+    fn trade_accounts(&self) -> Vec<AccountMeta> {
+        let mut accounts: Vec<_> = self
+            .pool_state
+            .assets
+            .iter()
+            .map(|asset| AccountMeta::new(asset.vault_address.pubkey(), false))
+            .collect();
+        accounts.push(AccountMeta::new_readonly(self.pool_state.vault_signer.pubkey(), false));
+        accounts.push(AccountMeta::new(
+            self.fund_state.basic_asset.vault_address.pubkey(),
+            false,
+        ));
+        accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+
+        // The spl-token-swap program of the routes, then the Serum DEX program of the markets if any
+        let spl_token_swap_program_id = self
+            .fund_state
+            .asset_swaps
+            .iter()
+            .zip(self.fund_state.asset_markets.iter())
+            .find(|(_, asset_market)| asset_market.is_none())
+            .map_or_else(spl_token_swap::id, |(asset_swap, _)| asset_swap.program_id.pubkey());
+        accounts.push(AccountMeta::new_readonly(spl_token_swap_program_id, false));
+        if let Some(asset_market) = self.fund_state.asset_markets.iter().flatten().next() {
+            accounts.push(AccountMeta::new_readonly(asset_market.program_id.pubkey(), false));
+            accounts.push(AccountMeta::new_readonly(sysvar::rent::id(), false));
+        }
+
+        accounts.extend(self.venue_accounts.iter().flatten().cloned());
+        accounts.extend(
+            self.fund_state
+                .asset_oracles
+                .iter()
+                .flatten()
+                .map(|asset_oracle| AccountMeta::new_readonly(asset_oracle.price_account.pubkey(), false)),
+        );
+        accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+        accounts
+    }
+
+    /// `Rebalance` instruction of the fund program `program_id` signed by the fund `admin`.
+    pub fn rebalance_instruction(&self, program_id: &Pubkey, admin: &Pubkey) -> Result<Instruction> {
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts: self.rebalance_accounts(admin),
+            data: FundRequest {
+                tag: FundRequestTag::default(),
+                inner: FundInstructionInner::Rebalance,
+            }
+            .try_to_vec()?,
+        })
+    }
+
+    /// Fund tokens, before the fees, minted by a `ZapIn` of `basic_asset_amount` at the current exchange rates, with
+    /// the bought asset amounts reduced by `slippage_bps`.
+    pub fn quote_zap_in(&self, basic_asset_amount: u64, fund_token_supply: u64, slippage_bps: u16) -> Result<u64> {
+        let amounts_in = split_by_weights(
+            basic_asset_amount,
+            &self.fund_state.asset_weights,
+            self.fund_state.basic_asset_weight,
+        )?;
+        let mut value = (basic_asset_amount - amounts_in.iter().sum::<u64>()) as u128;
+        for (amount_in, exchange_rate) in amounts_in.into_iter().zip(self.valuation.exchange_rates.iter()) {
+            let amount_out = exchange_rate.to_asset(amount_in)?;
+            value += exchange_rate.to_basic_asset(amount_out - bps_of(amount_out, slippage_bps))? as u128;
+        }
+        Ok(fund_tokens_for_value(fund_token_supply, self.nav(), value)?)
+    }
+
+    /// Basic asset tokens received for `redemption_size` fund tokens burned by a `ZapOut` at the current exchange
+    /// rates, with the sold asset amounts reduced by `slippage_bps`.
+    pub fn quote_zap_out(&self, redemption_size: u64, fund_token_supply: u64, slippage_bps: u16) -> Result<u64> {
+        let amounts_in = redeemed_amounts(&self.balances, redemption_size, fund_token_supply)?;
+        let mut amount_out = redeemed_amounts(&[self.basic_asset_balance], redemption_size, fund_token_supply)?[0];
+        for (amount_in, exchange_rate) in amounts_in.into_iter().zip(self.valuation.exchange_rates.iter()) {
+            let value = exchange_rate.to_basic_asset(amount_in)?;
+            amount_out += value - bps_of(value, slippage_bps);
+        }
+        Ok(amount_out)
+    }
+
+    /// `ZapIn` instruction of the fund program `program_id`, buying the fund assets with `basic_asset_amount` of the
+    /// `user_basic_asset_account`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn zap_in_instruction(
+        &self,
+        program_id: &Pubkey,
+        user_basic_asset_account: &Pubkey,
+        user_fund_token_account: &Pubkey,
+        user_authority: &Pubkey,
+        fee_accounts: &FeeAccounts,
+        basic_asset_amount: u64,
+        min_fund_tokens_out: u64,
+    ) -> Result<Instruction> {
+        let mut accounts = vec![
+            AccountMeta::new(self.fund_account, false),
+            AccountMeta::new(*user_basic_asset_account, false),
+            AccountMeta::new(*user_fund_token_account, false),
+            AccountMeta::new_readonly(*user_authority, true),
+            AccountMeta::new(self.pool_state.pool_token_mint.pubkey(), false),
+            AccountMeta::new(fee_accounts.lqd_fee_vault, false),
+            AccountMeta::new(fee_accounts.initializer_fee_vault, false),
+            AccountMeta::new(fee_accounts.referrer_fee_account, false),
+        ];
+        accounts.extend(self.trade_accounts());
+
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts,
+            data: FundRequest {
+                tag: FundRequestTag::default(),
+                inner: FundInstructionInner::ZapIn {
+                    basic_asset_amount,
+                    min_fund_tokens_out,
+                },
+            }
+            .try_to_vec()?,
+        })
+    }
+
+    /// Basket of a `PoolAction::Swap` of the `inputs` for the `outputs`, as checked by the fund program.
+    pub fn swap_basket(&self, inputs: &[u64], outputs: &[u64]) -> Result<Vec<i64>> {
+        Ok(swap_basket(&self.valuation, inputs, outputs, self.pool_state.fee_rate)?)
+    }
+
+    /// Additional accounts of a `PoolAction::Swap`, see `fund::instruction::swap_request`.
+    pub fn swap_accounts(&self) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.fund_state.basic_asset.vault_address.pubkey(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ];
+        accounts.extend(
+            self.fund_state
+                .asset_oracles
+                .iter()
+                .flatten()
+                .map(|asset_oracle| AccountMeta::new_readonly(asset_oracle.price_account.pubkey(), false)),
+        );
+        accounts
+    }
+
+    /// `PoolAction::Swap` instruction of the fund program `program_id`, depositing the `inputs` of the `user` asset
+    /// accounts for the `outputs`.
+    pub fn swap_instruction(
+        &self,
+        program_id: &Pubkey,
+        user: &UserAccounts,
+        fee_accounts: &FeeAccounts,
+        inputs: &[u64],
+        outputs: &[u64],
+    ) -> Result<Instruction> {
+        let mut instruction = execute(
+            program_id,
+            &self.fund_account,
+            &self.pool_state,
+            user,
+            fee_accounts,
+            PoolAction::Swap(swap_request(inputs, outputs)),
+        )?;
+        instruction.accounts.extend(self.swap_accounts());
+        Ok(instruction)
+    }
+
+    /// `ZapOut` instruction of the fund program `program_id`, redeeming `fund_token_amount` of the
+    /// `user_fund_token_account` for the basic asset.
+    #[allow(clippy::too_many_arguments)]
+    pub fn zap_out_instruction(
+        &self,
+        program_id: &Pubkey,
+        user_fund_token_account: &Pubkey,
+        user_basic_asset_account: &Pubkey,
+        user_authority: &Pubkey,
+        fee_accounts: &FeeAccounts,
+        fund_token_amount: u64,
+        minimum_amount_out: u64,
+    ) -> Result<Instruction> {
+        let mut accounts = vec![
+            AccountMeta::new(self.fund_account, false),
+            AccountMeta::new(*user_fund_token_account, false),
+            AccountMeta::new(*user_basic_asset_account, false),
+            AccountMeta::new_readonly(*user_authority, true),
+            AccountMeta::new(self.pool_state.pool_token_mint.pubkey(), false),
+            AccountMeta::new(fee_accounts.lqd_fee_vault, false),
+            AccountMeta::new(fee_accounts.initializer_fee_vault, false),
+            AccountMeta::new(fee_accounts.referrer_fee_account, false),
+        ];
+        accounts.extend(self.trade_accounts());
+
+        Ok(Instruction {
+            program_id: *program_id,
+            accounts,
+            data: FundRequest {
+                tag: FundRequestTag::default(),
+                inner: FundInstructionInner::ZapOut {
+                    fund_token_amount,
+                    minimum_amount_out,
+                },
+            }
+            .try_to_vec()?,
+        })
+    }
+}
+
+fn get_account<'a>(accounts: &'a Accounts, address: &Pubkey) -> Result<&'a Account> {
+    accounts
+        .get(address)
+        .ok_or_else(|| anyhow!("Account {} not fetched", address))
+}
+
+fn token_amount(accounts: &Accounts, token_account: &Pubkey) -> Result<u64> {
+    Ok(TokenAccount::unpack(&get_account(accounts, token_account)?.data)?.amount)
+}
+
+/// Spot rate, value held outside the asset vault and `Rebalance` accounts of the venue of the asset `index`. The rate
+/// and the value are read from the fetched accounts by the venue of the fund program.
+fn asset_venue(
+    accounts: &Accounts,
+    fund_state: &FundState,
+    index: usize,
+    asset_mint: &Pubkey,
+) -> Result<(ExchangeRate, u64, Vec<AccountMeta>)> {
+    let asset_market = &fund_state.asset_markets[index];
+    let asset_swap = &fund_state.asset_swaps[index];
+    let venue_accounts = match asset_market {
+        Some(asset_market) => market_accounts(accounts, asset_market)?,
+        None => route_accounts(accounts, asset_swap, asset_mint, &fund_state.basic_asset.mint)?,
+    };
+
+    // The venue programs then the venue accounts, as in `Rebalance`
+    let mut keys = vec![asset_swap.program_id.pubkey()];
+    if let Some(asset_market) = asset_market {
+        keys.push(asset_market.program_id.pubkey());
+        keys.push(sysvar::rent::id());
+    }
+    keys.extend(venue_accounts.iter().map(|account| account.pubkey));
+    let mut venue_account_data: Vec<Account> = keys
+        .iter()
+        .map(|key| accounts.get(key).cloned().unwrap_or_default())
+        .collect();
+    let account_infos: Vec<_> = keys
+        .iter()
+        .zip(venue_account_data.iter_mut())
+        .map(|(key, account)| account_info(key, account))
+        .collect();
+
+    let accounts_iter = &mut account_infos.iter();
+    let venue_programs = VenuePrograms::next(accounts_iter, asset_market.is_some())?;
+    let venue = venue_programs.next_venue(accounts_iter, fund_state, index, asset_mint)?;
+    Ok((venue.quote()?, venue.held_value()?, venue_accounts))
+}
+
+/// `Rebalance` accounts of an asset route through its token-swap pools.
+fn route_accounts(
+    accounts: &Accounts,
+    asset_swap: &AssetSwap,
+    asset_mint: &Pubkey,
+    basic_asset_mint: &Pubkey,
+) -> Result<Vec<AccountMeta>> {
+    let program_id = &asset_swap.program_id;
+    match &asset_swap.intermediate {
+        Some(hop) => {
+            let mut route_accounts = hop_accounts(accounts, &asset_swap.swap, program_id, asset_mint, &hop.mint)?;
+            route_accounts.push(AccountMeta::new(hop.vault.pubkey(), false));
+            route_accounts.extend(hop_accounts(
+                accounts,
+                &hop.swap,
+                program_id,
+                &hop.mint,
+                basic_asset_mint,
+            )?);
+            Ok(route_accounts)
+        },
+        None => hop_accounts(accounts, &asset_swap.swap, program_id, asset_mint, basic_asset_mint),
+    }
+}
+
+/// Accounts of a token-swap pool selling `source_mint` for `destination_mint`.
+fn hop_accounts(
+    accounts: &Accounts,
+    swap: &Pubkey,
+    spl_token_swap_program_id: &Pubkey,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+) -> Result<Vec<AccountMeta>> {
+    let swap_state = SwapVersion::unpack(&get_account(accounts, swap)?.data)?;
+    let reserve = |mint: &Pubkey| {
+        if swap_state.token_a_mint() == mint {
+            Ok(*swap_state.token_a_account())
+        } else if swap_state.token_b_mint() == mint {
+            Ok(*swap_state.token_b_account())
+        } else {
+            Err(anyhow!("Token-swap pool {} does not trade the mint {}", swap, mint))
+        }
+    };
+    let authority = Pubkey::create_program_address(&[swap.as_ref(), &[swap_state.nonce()]], spl_token_swap_program_id)?;
+
+    Ok(vec![
+        AccountMeta::new_readonly(*swap, false),
+        AccountMeta::new_readonly(authority, false),
+        AccountMeta::new(reserve(source_mint)?, false),
+        AccountMeta::new(reserve(destination_mint)?, false),
+        AccountMeta::new(*swap_state.pool_mint(), false),
+        AccountMeta::new(*swap_state.pool_fee_account(), false),
+    ])
+}
+
+/// Accounts of a Serum DEX market.
+struct MarketKeys {
+    request_queue: Pubkey,
+    event_queue: Pubkey,
+    bids: Pubkey,
+    asks: Pubkey,
+    coin_vault: Pubkey,
+    pc_vault: Pubkey,
+    vault_signer: Pubkey,
+}
+
+fn load_market(accounts: &Accounts, asset_market: &AssetMarket) -> Result<MarketKeys> {
+    let market = asset_market.market.pubkey();
+    let dex_program_id = asset_market.program_id.pubkey();
+    let mut market_account = get_account(accounts, &market)?.clone();
+    let market_info = account_info(&market, &mut market_account);
+
+    let market_state =
+        MarketState::load(&market_info, &dex_program_id).map_err(|err| anyhow!("Load market error: {:?}", err))?;
+    Ok(MarketKeys {
+        request_queue: dex::dex_pubkey(market_state.req_q),
+        event_queue: dex::dex_pubkey(market_state.event_q),
+        bids: dex::dex_pubkey(market_state.bids),
+        asks: dex::dex_pubkey(market_state.asks),
+        coin_vault: dex::dex_pubkey(market_state.coin_vault),
+        pc_vault: dex::dex_pubkey(market_state.pc_vault),
+        vault_signer: gen_vault_signer_key(market_state.vault_signer_nonce, &market, &dex_program_id)?,
+    })
+}
+
+/// `Rebalance` accounts of an asset Serum DEX market.
+fn market_accounts(accounts: &Accounts, asset_market: &AssetMarket) -> Result<Vec<AccountMeta>> {
+    let keys = load_market(accounts, asset_market)?;
+    Ok(vec![
+        AccountMeta::new(asset_market.market.pubkey(), false),
+        AccountMeta::new(asset_market.open_orders.pubkey(), false),
+        AccountMeta::new(keys.request_queue, false),
+        AccountMeta::new(keys.event_queue, false),
+        AccountMeta::new(keys.bids, false),
+        AccountMeta::new(keys.asks, false),
+        AccountMeta::new(keys.coin_vault, false),
+        AccountMeta::new(keys.pc_vault, false),
+        AccountMeta::new_readonly(keys.vault_signer, false),
+    ])
+}
+
+/// Account info of a fetched account, to read it with the fund program functions.
+fn account_info<'a>(key: &'a Pubkey, account: &'a mut Account) -> AccountInfo<'a> {
+    AccountInfo::new(
+        key,
+        false,
+        false,
+        &mut account.lamports,
+        &mut account.data,
+        &account.owner,
+        account.executable,
+        account.rent_epoch,
+    )
+}
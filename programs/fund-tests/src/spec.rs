@@ -0,0 +1,489 @@
+use std::{collections::HashSet, fmt, fs, path::Path};
+
+use anyhow::{bail, Result};
+use borsh::ser::BorshSerialize;
+use fund::{
+    instruction::InitializeFundData,
+    state::{AssetSwap, FundState, IntermediateHop, BPS_DENOMINATOR},
+};
+use serde::{Deserialize, Serialize};
+use serum_pool::schema::{InitializePoolRequest, PoolState, FEE_RATE_DENOMINATOR, MIN_FEE_RATE};
+use solana_program::pubkey::Pubkey;
+
+/// Version of the fund spec format, bumped on incompatible changes of the format.
+pub const FUND_SPEC_VERSION: u32 = 1;
+
+/// Parameters of a new fund, read from a JSON or TOML file by `fund-cli init`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FundSpec {
+    pub version: u32,
+    pub name: String,
+    /// Fee rate of creations and redemptions, in millionths.
+    pub fee_rate: u32,
+    pub rebalance_threshold_bps: u16,
+    pub max_slippage_bps: u16,
+    pub max_oracle_deviation_bps: u16,
+    pub fund_token_decimals: u8,
+    pub fund_token_initial_supply: u64,
+    #[serde(with = "base58")]
+    pub basic_asset_mint: Pubkey,
+    pub assets: Vec<AssetSpec>,
+    /// Assets count the fund account has room for, to add assets later with `AddAsset`, the count of `assets` if
+    /// omitted. The fund program cannot resize the account, a full fund is moved to a larger one by `MigrateFund`.
+    #[serde(default)]
+    pub max_assets_count: Option<usize>,
+}
+
+/// Asset of a new fund with its token-swap route to the basic asset.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct AssetSpec {
+    #[serde(with = "base58")]
+    pub mint: Pubkey,
+    pub weight: u32,
+    /// Amount transferred to the fund vault from the admin token account before the initialization.
+    pub initial_amount: u64,
+    #[serde(with = "base58", default = "spl_token_swap::id")]
+    pub swap_program_id: Pubkey,
+    #[serde(with = "base58")]
+    pub swap: Pubkey,
+    #[serde(default)]
+    pub intermediate: Option<IntermediateSpec>,
+}
+
+/// Intermediate token of a two-hop asset route, see `fund::state::AssetSwap`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct IntermediateSpec {
+    #[serde(with = "base58")]
+    pub mint: Pubkey,
+    /// Token-swap pool of the intermediate token and the basic asset.
+    #[serde(with = "base58")]
+    pub swap: Pubkey,
+}
+
+/// Parameter of a fund whose live value differs from its spec.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SpecDifference {
+    pub field: String,
+    pub spec: String,
+    pub live: String,
+}
+
+impl fmt::Display for SpecDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: spec {}, live {}", self.field, self.spec, self.live)
+    }
+}
+
+impl FundSpec {
+    /// Reads and validates a spec file, in TOML for the `.toml` extension, otherwise in JSON.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&contents),
+            _ => Self::from_json(&contents),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        Self::validated(serde_json::from_str(json)?)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        Self::validated(toml::from_str(toml)?)
+    }
+
+    fn validated(spec: Self) -> Result<Self> {
+        spec.validate()?;
+        Ok(spec)
+    }
+
+    /// Checks the spec the same way as the fund program checks the initialization, before any account is created.
+    pub fn validate(&self) -> Result<()> {
+        if self.version != FUND_SPEC_VERSION {
+            bail!(
+                "Unsupported fund spec version {}, expected {}",
+                self.version,
+                FUND_SPEC_VERSION
+            );
+        }
+        if self.name.is_empty() {
+            bail!("Fund name is empty");
+        }
+        if self.fee_rate < MIN_FEE_RATE || self.fee_rate >= FEE_RATE_DENOMINATOR {
+            bail!(
+                "Fee rate {} out of the range from {} to {}",
+                self.fee_rate,
+                MIN_FEE_RATE,
+                FEE_RATE_DENOMINATOR - 1
+            );
+        }
+        check_bps(self.rebalance_threshold_bps, stringify!(rebalance_threshold_bps))?;
+        check_bps(self.max_slippage_bps, stringify!(max_slippage_bps))?;
+        check_bps(self.max_oracle_deviation_bps, stringify!(max_oracle_deviation_bps))?;
+        if self.fund_token_initial_supply == 0 {
+            bail!("Fund token initial supply is zero");
+        }
+
+        if self.assets.is_empty() || self.assets.len() > u8::MAX as usize {
+            bail!(
+                "Assets count {} out of the range from 1 to {}",
+                self.assets.len(),
+                u8::MAX
+            );
+        }
+        if let Some(max_assets_count) = self.max_assets_count {
+            if max_assets_count < self.assets.len() || max_assets_count > u8::MAX as usize {
+                bail!(
+                    "Max assets count {} out of the range from {} to {}",
+                    max_assets_count,
+                    self.assets.len(),
+                    u8::MAX
+                );
+            }
+        }
+        if self.assets.iter().all(|asset| asset.weight == 0) {
+            bail!("Asset weights total is zero");
+        }
+        let mut mints = HashSet::new();
+        for asset in &self.assets {
+            if asset.mint == self.basic_asset_mint {
+                bail!("Asset {} is the basic asset", asset.mint);
+            }
+            if !mints.insert(asset.mint) {
+                bail!("Asset {} is listed twice", asset.mint);
+            }
+            if let Some(intermediate) = &asset.intermediate {
+                if intermediate.mint == asset.mint || intermediate.mint == self.basic_asset_mint {
+                    bail!("Intermediate token of the asset {} is not a third token", asset.mint);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Length of the fund account data for `max_assets_count` assets, see `fund::state::calc_len`.
+    pub fn account_len(&self) -> usize {
+        let assets_count = self.max_assets_count.unwrap_or(self.assets.len());
+        fund::state::calc_len(self.name.as_str(), assets_count)
+    }
+
+    pub fn asset_weights(&self) -> Vec<u32> {
+        self.assets.iter().map(|asset| asset.weight).collect()
+    }
+
+    /// Fund data of the initialization, with the fund vault of the intermediate token of each asset with a two-hop
+    /// route.
+    pub fn initialize_fund_data(&self, intermediate_vaults: &[Option<Pubkey>]) -> Result<InitializeFundData> {
+        if intermediate_vaults.len() != self.assets.len() {
+            bail!(
+                "Intermediate vaults count {} does not match the assets count {}",
+                intermediate_vaults.len(),
+                self.assets.len()
+            );
+        }
+        let asset_swaps = self
+            .assets
+            .iter()
+            .zip(intermediate_vaults.iter())
+            .map(|(asset, intermediate_vault)| {
+                let intermediate = match (&asset.intermediate, intermediate_vault) {
+                    (Some(intermediate), Some(vault)) => Some(IntermediateHop {
+                        mint: intermediate.mint.into(),
+                        swap: intermediate.swap.into(),
+                        vault: (*vault).into(),
+                    }),
+                    (None, None) => None,
+                    _ => bail!(
+                        "Intermediate vault does not match the route of the asset {}",
+                        asset.mint
+                    ),
+                };
+                Ok(AssetSwap {
+                    program_id: asset.swap_program_id.into(),
+                    swap: asset.swap.into(),
+                    intermediate,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(InitializeFundData {
+            rebalance_threshold_bps: self.rebalance_threshold_bps,
+            max_slippage_bps: self.max_slippage_bps,
+            max_oracle_deviation_bps: self.max_oracle_deviation_bps,
+            asset_weights: self.asset_weights(),
+            asset_swaps,
+            fund_token_initial_supply: self.fund_token_initial_supply,
+        })
+    }
+
+    pub fn initialize_pool_request(
+        &self,
+        vault_signer_nonce: u8,
+        fund_data: &InitializeFundData,
+    ) -> Result<InitializePoolRequest> {
+        Ok(InitializePoolRequest {
+            vault_signer_nonce,
+            assets_length: self.assets.len() as u8,
+            pool_name: self.name.clone(),
+            fee_rate: self.fee_rate,
+            custom_data: fund_data.try_to_vec()?,
+        })
+    }
+
+    /// Differences of the live state of a fund from the spec, the initial amounts and supply are not compared.
+    pub fn diff(&self, pool_state: &PoolState, fund_state: &FundState) -> Vec<SpecDifference> {
+        let mut differences = Vec::new();
+        let mut compare = |field: String, spec: String, live: String| {
+            if spec != live {
+                differences.push(SpecDifference { field, spec, live });
+            }
+        };
+
+        compare("name".to_string(), self.name.clone(), pool_state.name.clone());
+        compare(
+            "fee_rate".to_string(),
+            self.fee_rate.to_string(),
+            pool_state.fee_rate.to_string(),
+        );
+        compare(
+            "rebalance_threshold_bps".to_string(),
+            self.rebalance_threshold_bps.to_string(),
+            fund_state.rebalance_threshold_bps.to_string(),
+        );
+        compare(
+            "max_slippage_bps".to_string(),
+            self.max_slippage_bps.to_string(),
+            fund_state.max_slippage_bps.to_string(),
+        );
+        compare(
+            "max_oracle_deviation_bps".to_string(),
+            self.max_oracle_deviation_bps.to_string(),
+            fund_state.max_oracle_deviation_bps.to_string(),
+        );
+        compare(
+            "basic_asset_mint".to_string(),
+            self.basic_asset_mint.to_string(),
+            fund_state.basic_asset.mint.pubkey().to_string(),
+        );
+        compare(
+            "assets".to_string(),
+            self.assets.len().to_string(),
+            pool_state.assets.len().to_string(),
+        );
+
+        for (i, (asset, live_asset)) in self.assets.iter().zip(pool_state.assets.iter()).enumerate() {
+            let asset_swap = &fund_state.asset_swaps[i];
+            compare(
+                format!("assets[{}].mint", i),
+                asset.mint.to_string(),
+                live_asset.mint.pubkey().to_string(),
+            );
+            compare(
+                format!("assets[{}].weight", i),
+                asset.weight.to_string(),
+                fund_state.asset_weights[i].to_string(),
+            );
+            compare(
+                format!("assets[{}].swap_program_id", i),
+                asset.swap_program_id.to_string(),
+                asset_swap.program_id.pubkey().to_string(),
+            );
+            compare(
+                format!("assets[{}].swap", i),
+                asset.swap.to_string(),
+                asset_swap.swap.pubkey().to_string(),
+            );
+            compare(
+                format!("assets[{}].intermediate", i),
+                asset.intermediate.as_ref().map_or_else(
+                    || "none".to_string(),
+                    |intermediate| format!("{} via {}", intermediate.mint, intermediate.swap),
+                ),
+                asset_swap.intermediate.as_ref().map_or_else(
+                    || "none".to_string(),
+                    |hop| format!("{} via {}", hop.mint.pubkey(), hop.swap.pubkey()),
+                ),
+            );
+        }
+        differences
+    }
+}
+
+fn check_bps(value: u16, name: &str) -> Result<()> {
+    if value > BPS_DENOMINATOR {
+        bail!("{} must not exceed {} bps", name, BPS_DENOMINATOR);
+    }
+    Ok(())
+}
+
+/// Public keys as base58 strings.
+mod base58 {
+    use std::str::FromStr;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use solana_program::pubkey::Pubkey;
+
+    pub fn serialize<S: Serializer>(pubkey: &Pubkey, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(pubkey)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Pubkey, D::Error> {
+        let base58 = String::deserialize(deserializer)?;
+        Pubkey::from_str(&base58).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serum_pool::schema::AssetInfo;
+
+    use super::*;
+
+    fn spec() -> FundSpec {
+        FundSpec {
+            version: FUND_SPEC_VERSION,
+            name: "Test DTF".to_string(),
+            fee_rate: 1000,
+            rebalance_threshold_bps: 100,
+            max_slippage_bps: 100,
+            max_oracle_deviation_bps: 500,
+            fund_token_decimals: 6,
+            fund_token_initial_supply: 1000,
+            basic_asset_mint: Pubkey::new_unique(),
+            assets: vec![
+                AssetSpec {
+                    mint: Pubkey::new_unique(),
+                    weight: 600,
+                    initial_amount: 600,
+                    swap_program_id: spl_token_swap::id(),
+                    swap: Pubkey::new_unique(),
+                    intermediate: None,
+                },
+                AssetSpec {
+                    mint: Pubkey::new_unique(),
+                    weight: 400,
+                    initial_amount: 400,
+                    swap_program_id: spl_token_swap::id(),
+                    swap: Pubkey::new_unique(),
+                    intermediate: Some(IntermediateSpec {
+                        mint: Pubkey::new_unique(),
+                        swap: Pubkey::new_unique(),
+                    }),
+                },
+            ],
+            max_assets_count: None,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let spec = spec();
+        assert_eq!(
+            FundSpec::from_json(&serde_json::to_string(&spec).unwrap()).unwrap(),
+            spec
+        );
+        assert_eq!(FundSpec::from_toml(&toml::to_string(&spec).unwrap()).unwrap(), spec);
+
+        let mut future_spec = spec;
+        future_spec.version = FUND_SPEC_VERSION + 1;
+        assert!(FundSpec::from_json(&serde_json::to_string(&future_spec).unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(spec().validate().is_ok());
+
+        let mut zero_weights = spec();
+        zero_weights.assets.iter_mut().for_each(|asset| asset.weight = 0);
+        assert!(zero_weights.validate().is_err());
+
+        let mut duplicate_asset = spec();
+        duplicate_asset.assets[1].mint = duplicate_asset.assets[0].mint;
+        assert!(duplicate_asset.validate().is_err());
+
+        let mut basic_asset = spec();
+        basic_asset.assets[0].mint = basic_asset.basic_asset_mint;
+        assert!(basic_asset.validate().is_err());
+
+        let mut high_fee = spec();
+        high_fee.fee_rate = FEE_RATE_DENOMINATOR;
+        assert!(high_fee.validate().is_err());
+
+        let mut small_account = spec();
+        small_account.max_assets_count = Some(1);
+        assert!(small_account.validate().is_err());
+    }
+
+    #[test]
+    fn test_account_len() {
+        let mut spec = spec();
+        let len = spec.account_len();
+        spec.max_assets_count = Some(3);
+        assert!(spec.validate().is_ok());
+        assert!(spec.account_len() > len);
+    }
+
+    #[test]
+    fn test_initialize_fund_data() {
+        let spec = spec();
+        assert!(spec.initialize_fund_data(&[None, None]).is_err());
+
+        let intermediate_vault = Pubkey::new_unique();
+        let fund_data = spec.initialize_fund_data(&[None, Some(intermediate_vault)]).unwrap();
+        assert_eq!(fund_data.asset_weights, vec![600, 400]);
+        assert_eq!(
+            fund_data.asset_swaps[1].intermediate.as_ref().unwrap().vault.pubkey(),
+            intermediate_vault
+        );
+
+        let request = spec.initialize_pool_request(1, &fund_data).unwrap();
+        assert_eq!(request.assets_length, 2);
+        assert_eq!(request.pool_name, spec.name);
+    }
+
+    #[test]
+    fn test_diff() {
+        let spec = spec();
+        let intermediate_vault = Pubkey::new_unique();
+        let fund_data = spec.initialize_fund_data(&[None, Some(intermediate_vault)]).unwrap();
+        let pool_state = PoolState {
+            tag: Default::default(),
+            pool_token_mint: Default::default(),
+            assets: spec
+                .assets
+                .iter()
+                .map(|asset| AssetInfo {
+                    mint: asset.mint.into(),
+                    vault_address: Pubkey::new_unique().into(),
+                })
+                .collect(),
+            vault_signer: Default::default(),
+            vault_signer_nonce: 1,
+            account_params: vec![],
+            name: spec.name.clone(),
+            lqd_fee_vault: Default::default(),
+            initializer_fee_vault: Default::default(),
+            fee_rate: spec.fee_rate,
+            admin_key: None,
+            custom_state: vec![],
+        };
+        let mut fund_state = FundState {
+            rebalance_threshold_bps: spec.rebalance_threshold_bps,
+            max_slippage_bps: spec.max_slippage_bps,
+            max_oracle_deviation_bps: spec.max_oracle_deviation_bps,
+            asset_weights: fund_data.asset_weights,
+            asset_swaps: fund_data.asset_swaps,
+            ..Default::default()
+        };
+        fund_state.basic_asset.mint = spec.basic_asset_mint.into();
+        assert_eq!(spec.diff(&pool_state, &fund_state), vec![]);
+
+        fund_state.asset_weights[0] = 500;
+        assert_eq!(spec.diff(&pool_state, &fund_state), vec![SpecDifference {
+            field: "assets[0].weight".to_string(),
+            spec: "600".to_string(),
+            live: "500".to_string(),
+        }]);
+    }
+}
@@ -11,6 +11,7 @@ use spl_token_swap::curve::{base::SwapCurve, fees::Fees};
 
 use super::client::Client;
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_swap(
     client: &mut Client,
     swap_program_id: &Pubkey,
@@ -74,7 +75,7 @@ pub fn create_swap(
         &vec![client.payer(), token_swap, &fee, &pool_token_initial],
         client.recent_blockhash(),
     );
-    client.process_transaction(&transaction);
+    client.process_transaction(&transaction).unwrap();
 
     (fee, pool_token_initial)
 }
@@ -1,29 +1,69 @@
-use borsh::ser::BorshSerialize;
+use borsh::{de::BorshDeserialize, ser::BorshSerialize};
 use fund::{
+    dex::OPEN_ORDERS_LEN,
+    error::FundError,
+    instruction::{FundInstructionInner, FundRequest, InitializeFundData},
+    oracle::{Price, PRICE_STATUS_TRADING},
+    price::ExchangeRate,
     processor::Fund,
-    state::{self as fund_state},
+    state::{
+        self as fund_state, AssetMarket, AssetOracle, AssetSwap, CrankConfig, FundStateContainer, IntermediateHop,
+        KeeperReward, PendingTrade, PlannedTrade,
+    },
+};
+use fund_tests::{
+    basket,
+    instruction::{self as fund_instruction, FeeAccounts, UserAccounts},
 };
+use serum_dex::matching::Side;
 use serum_pool::{
     pool_entrypoint,
     schema::{
-        fee_owner::ID as POOL_FEE_OWNER_ID, InitializePoolRequest, PoolRequest, PoolRequestInner, PoolRequestTag,
+        fee_owner::ID as POOL_FEE_OWNER_ID, InitializePoolRequest, PoolAction, PoolRequest, PoolRequestInner,
+        PoolRequestTag, PoolState,
     },
 };
 use solana_program::{
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_option::COption,
+    program_pack::Pack,
     pubkey::Pubkey,
     sysvar,
 };
 use solana_program_test::{processor, ProgramTest};
 use solana_sdk::{
+    account::Account,
     signature::{Keypair, Signer},
     transaction::Transaction,
 };
+use spl_token::{
+    instruction::AuthorityType,
+    state::{Account as TokenAccount, Mint},
+};
+use spl_token_swap::error::SwapError;
 
-use self::helpers::{client::TestClient, token};
+use self::helpers::{
+    client::{instruction_error, TestClient},
+    dex::{self, TestMarket},
+    fund::{swap_accounts, TestFund, BASIC_ASSET_DECIMALS},
+    oracle, retbuf,
+    swap::TestSwap,
+    token,
+};
 
 mod helpers;
 
+fn program_test() -> (ProgramTest, Pubkey) {
+    let program_id = Keypair::new().pubkey();
+    let mut program_test = ProgramTest::new("fund", program_id, processor!(pool_entrypoint::<Fund>));
+    program_test.add_program(
+        "spl_token_swap",
+        spl_token_swap::id(),
+        processor!(spl_token_swap::processor::Processor::process),
+    );
+    (program_test, program_id)
+}
+
 #[tokio::test]
 async fn test_init_fund() {
     let program_key = Keypair::new();
@@ -38,6 +78,7 @@ async fn test_init_fund() {
     // Create assets
     let x_token_mint = token::create_token(&mut client, &initializer_account.pubkey(), 2).await;
     let y_token_mint = token::create_token(&mut client, &initializer_account.pubkey(), 0).await;
+    let basic_asset_mint = token::create_token(&mut client, &initializer_account.pubkey(), BASIC_ASSET_DECIMALS).await;
 
     // Mint to user
     let initializer_x_token_account =
@@ -79,6 +120,8 @@ async fn test_init_fund() {
         token::create_account(&mut client, &fund_vault_authority, &x_token_mint.pubkey()).await;
     let fund_y_token_vault_account =
         token::create_account(&mut client, &fund_vault_authority, &y_token_mint.pubkey()).await;
+    let fund_basic_asset_vault_account =
+        token::create_account(&mut client, &fund_vault_authority, &basic_asset_mint.pubkey()).await;
 
     // Transfer initial assets to fund
     token::transfer_to(
@@ -113,7 +156,23 @@ async fn test_init_fund() {
         assets_length: 2,
         pool_name: fund_name.to_string(),
         fee_rate: 1000,
-        custom_data: vec![],
+        custom_data: InitializeFundData {
+            rebalance_threshold_bps: 100,
+            max_slippage_bps: 100,
+            max_oracle_deviation_bps: 500,
+            asset_weights: vec![1, 1],
+            asset_swaps: vec![
+                AssetSwap {
+                    program_id: spl_token_swap::id().into(),
+                    swap: Pubkey::new_unique().into(),
+                    intermediate: None,
+                };
+                2
+            ],
+            fund_token_initial_supply: 100,
+        }
+        .try_to_vec()
+        .unwrap(),
     };
 
     let mut transaction = Transaction::new_with_payer(
@@ -128,8 +187,10 @@ async fn test_init_fund() {
                 AccountMeta::new_readonly(lqd_fee_account.pubkey(), false),
                 AccountMeta::new_readonly(initializer_fee_account.pubkey(), false),
                 AccountMeta::new_readonly(sysvar::rent::id(), false),
-                AccountMeta::new(initializer_account.pubkey(), false),
+                // The fund admin must be rent exempt, the payer
+                AccountMeta::new(client.payer_pubkey(), false),
                 AccountMeta::new(fund_token_account.pubkey(), false),
+                AccountMeta::new_readonly(fund_basic_asset_vault_account.pubkey(), false),
                 AccountMeta::new_readonly(spl_token::id(), false),
             ],
             data: PoolRequest {
@@ -143,4 +204,2839 @@ async fn test_init_fund() {
     );
     transaction.sign(&[client.payer()], client.recent_blockhash());
     client.process_transaction(transaction).await.unwrap();
+
+    let account = client
+        .client
+        .get_account(fund_account.pubkey())
+        .await
+        .unwrap()
+        .expect("Fund account should exist");
+    let pool_state = PoolState::deserialize(&mut account.data.as_slice()).unwrap();
+    let fund_state = pool_state.read_fund_state().unwrap();
+    assert_eq!(pool_state.assets.len(), 2);
+    assert_eq!(fund_state.asset_weights, vec![1, 1]);
+    assert_eq!(fund_state.asset_oracles, vec![None, None]);
+    assert_eq!(
+        fund_state.basic_asset.vault_address.as_ref(),
+        &fund_basic_asset_vault_account.pubkey()
+    );
+}
+
+#[tokio::test]
+async fn test_update_asset_oracle() {
+    let (mut program_test, program_id) = program_test();
+    let price_account = oracle::add_price_account(&mut program_test, 2_500_000, -6);
+    // A price account of another program
+    let other_price_account = Pubkey::new_unique();
+    let mut account = Account::new(u32::MAX as u64, Price::LEN, &Pubkey::new_unique());
+    Price {
+        expo: -6,
+        price: 2_500_000,
+        status: PRICE_STATUS_TRADING,
+        ..Default::default()
+    }
+    .pack(&mut account.data)
+    .unwrap();
+    program_test.add_account(other_price_account, account);
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(9, 1_000_000_000), (6, 1_000_000)]).await;
+
+    fund.update_asset_oracle(&mut client, 0, &price_account, 25, 200)
+        .await
+        .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.asset_oracles, vec![
+        Some(AssetOracle {
+            program_id: oracle::id().into(),
+            price_account: price_account.into(),
+            asset_decimals: 9,
+            basic_asset_decimals: BASIC_ASSET_DECIMALS,
+            max_price_age: 25,
+            max_confidence_bps: 200,
+        }),
+        None
+    ]);
+
+    // The mint must be the asset one
+    let wrong_mint_accounts = vec![
+        AccountMeta::new_readonly(fund.asset_vaults[1].pubkey(), false),
+        AccountMeta::new_readonly(fund.asset_mints[0].pubkey(), false),
+        AccountMeta::new_readonly(fund.basic_asset_mint.pubkey(), false),
+        AccountMeta::new_readonly(price_account, false),
+        AccountMeta::new_readonly(oracle::id(), false),
+    ];
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateAssetOracle {
+                max_price_age: 25,
+                max_confidence_bps: 200,
+            },
+            wrong_mint_accounts
+        )
+        .await
+        .is_err());
+
+    // The price account must be owned by the oracle program
+    assert!(fund
+        .update_asset_oracle(&mut client, 1, &other_price_account, 25, 200)
+        .await
+        .is_err());
+
+    fund.process_admin_instruction(&mut client, FundInstructionInner::RemoveAssetOracle, vec![
+        AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+    ])
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.asset_oracles, vec![None, None]);
+}
+
+#[tokio::test]
+async fn test_update_asset_swap_route() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 30)]).await;
+
+    let intermediate_mint = token::create_token(&mut client, &fund.mint_authority.pubkey(), 9).await;
+    let intermediate_vault =
+        token::create_account(&mut client, &fund.vault_authority, &intermediate_mint.pubkey()).await;
+    let swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+        (&intermediate_mint.pubkey(), 9, 1_000_000),
+    )
+    .await;
+    let intermediate_swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&intermediate_mint.pubkey(), 9, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+    )
+    .await;
+    let direct_swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+    )
+    .await;
+    let two_hop_accounts = |first_hop: &TestSwap, second_hop: &TestSwap, vault: Pubkey| {
+        vec![
+            AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+            AccountMeta::new_readonly(first_hop.swap.pubkey(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+            AccountMeta::new_readonly(second_hop.swap.pubkey(), false),
+            AccountMeta::new_readonly(vault, false),
+        ]
+    };
+
+    // The first hop must trade the asset against the intermediate token
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateAssetSwap {
+                with_intermediate: true
+            },
+            two_hop_accounts(&intermediate_swap, &intermediate_swap, intermediate_vault.pubkey()),
+        )
+        .await
+        .is_err());
+
+    // The second hop must trade the intermediate token against the basic asset
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateAssetSwap {
+                with_intermediate: true
+            },
+            two_hop_accounts(&swap, &direct_swap, intermediate_vault.pubkey()),
+        )
+        .await
+        .is_err());
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateAssetSwap {
+            with_intermediate: true,
+        },
+        two_hop_accounts(&swap, &intermediate_swap, intermediate_vault.pubkey()),
+    )
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.asset_swaps[0], AssetSwap {
+        program_id: spl_token_swap::id().into(),
+        swap: swap.swap.pubkey().into(),
+        intermediate: Some(IntermediateHop {
+            swap: intermediate_swap.swap.pubkey().into(),
+            vault: intermediate_vault.pubkey().into(),
+            mint: intermediate_mint.pubkey().into(),
+        }),
+    });
+
+    // The intermediate token cannot be the basic asset
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateAssetSwap {
+                with_intermediate: true
+            },
+            two_hop_accounts(&swap, &intermediate_swap, fund.basic_asset_vault.pubkey()),
+        )
+        .await
+        .is_err());
+
+    // A direct route must trade the asset against the basic asset
+    let direct_accounts = |swap: &TestSwap| {
+        vec![
+            AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+            AccountMeta::new_readonly(swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+        ]
+    };
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateAssetSwap {
+                with_intermediate: false
+            },
+            direct_accounts(&swap),
+        )
+        .await
+        .is_err());
+
+    // A direct route drops the intermediate hop
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateAssetSwap {
+            with_intermediate: false,
+        },
+        direct_accounts(&direct_swap),
+    )
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.asset_swaps[0].swap.as_ref(), &direct_swap.swap.pubkey());
+    assert_eq!(fund_state.asset_swaps[0].intermediate, None);
+}
+
+#[tokio::test]
+async fn test_add_asset() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    // The fund account has room for a second asset only
+    let fund = TestFund::create_with_capacity(&mut client, program_id, &[(2, 70)], 2).await;
+
+    let asset_mint = token::create_token(&mut client, &fund.mint_authority.pubkey(), 0).await;
+    let swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&asset_mint.pubkey(), 0, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+    )
+    .await;
+    let add_asset_accounts = |asset_vault: Pubkey, swap: &TestSwap| {
+        vec![
+            AccountMeta::new_readonly(asset_vault, false),
+            AccountMeta::new_readonly(swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+        ]
+    };
+
+    // A vault the previous owner can still close
+    let payer = client.payer_pubkey();
+    let closable_vault = token::create_account(&mut client, &payer, &asset_mint.pubkey()).await;
+    for (authority_type, new_authority) in [
+        (AuthorityType::CloseAccount, payer),
+        (AuthorityType::AccountOwner, fund.vault_authority),
+    ] {
+        let mut transaction = Transaction::new_with_payer(
+            &[spl_token::instruction::set_authority(
+                &spl_token::id(),
+                &closable_vault.pubkey(),
+                Some(&new_authority),
+                authority_type,
+                &payer,
+                &[],
+            )
+            .unwrap()],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer()], client.recent_blockhash());
+        client.process_transaction(transaction).await.unwrap();
+    }
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::AddAsset {
+                weight: 1,
+                with_intermediate: false,
+            },
+            add_asset_accounts(closable_vault.pubkey(), &swap),
+        )
+        .await
+        .is_err());
+
+    // A vault not owned by the fund
+    let other_vault = token::create_account(&mut client, &payer, &asset_mint.pubkey()).await;
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::AddAsset {
+                weight: 1,
+                with_intermediate: false,
+            },
+            add_asset_accounts(other_vault.pubkey(), &swap),
+        )
+        .await
+        .is_err());
+
+    // A pool not trading the asset
+    let asset_vault = token::create_account(&mut client, &fund.vault_authority, &asset_mint.pubkey()).await;
+    let other_swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+    )
+    .await;
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::AddAsset {
+                weight: 1,
+                with_intermediate: false,
+            },
+            add_asset_accounts(asset_vault.pubkey(), &other_swap),
+        )
+        .await
+        .is_err());
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::AddAsset {
+            weight: 3,
+            with_intermediate: false,
+        },
+        add_asset_accounts(asset_vault.pubkey(), &swap),
+    )
+    .await
+    .unwrap();
+
+    let (pool_state, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(pool_state.assets.len(), 2);
+    assert_eq!(pool_state.assets[1].vault_address.as_ref(), &asset_vault.pubkey());
+    assert_eq!(fund_state.asset_weights, vec![1, 3]);
+    assert_eq!(fund_state.asset_swaps[1].swap.as_ref(), &swap.swap.pubkey());
+    assert_eq!(fund_state.asset_oracles, vec![None, None]);
+
+    // The fund account has no room for a third asset
+    let third_mint = token::create_token(&mut client, &fund.mint_authority.pubkey(), 0).await;
+    let third_vault = token::create_account(&mut client, &fund.vault_authority, &third_mint.pubkey()).await;
+    let third_swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&third_mint.pubkey(), 0, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+    )
+    .await;
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::AddAsset {
+                weight: 1,
+                with_intermediate: false,
+            },
+            add_asset_accounts(third_vault.pubkey(), &third_swap),
+        )
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_migrate_fund() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    // The fund account has no room for another asset
+    let mut fund = TestFund::create(&mut client, program_id, &[(0, 1_000)]).await;
+    let user = fund.create_user(&mut client, 1_000).await;
+    fund.execute(&mut client, &user, PoolAction::Create(100), None)
+        .await
+        .unwrap();
+
+    let asset_mint = token::create_token(&mut client, &fund.mint_authority.pubkey(), 0).await;
+    let swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&asset_mint.pubkey(), 0, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+    )
+    .await;
+    let add_asset = FundInstructionInner::AddAsset {
+        weight: 1,
+        with_intermediate: false,
+    };
+    let add_asset_accounts = |asset_vault: Pubkey| {
+        vec![
+            AccountMeta::new_readonly(asset_vault, false),
+            AccountMeta::new_readonly(swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+        ]
+    };
+    let asset_vault = token::create_account(&mut client, &fund.vault_authority, &asset_mint.pubkey()).await;
+    assert_eq!(
+        instruction_error(
+            &fund
+                .process_admin_instruction(&mut client, add_asset.clone(), add_asset_accounts(asset_vault.pubkey()))
+                .await
+        ),
+        Some(InstructionError::AccountDataTooSmall)
+    );
+
+    // The new account must have room for another asset
+    assert_eq!(
+        instruction_error(&fund.migrate(&mut client, 1, &[]).await),
+        Some(InstructionError::AccountDataTooSmall)
+    );
+
+    let old_fund_account = fund.fund_account.pubkey();
+    let old_vault_authority = fund.vault_authority;
+    fund.migrate(&mut client, 2, &[]).await.unwrap();
+    assert!(client.client.get_account(old_fund_account).await.unwrap().is_none());
+    let (pool_state, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(pool_state.vault_signer.as_ref(), &fund.vault_authority);
+    assert_ne!(fund.vault_authority, old_vault_authority);
+    assert_eq!(fund_state.asset_weights, vec![1]);
+    let fund_token_mint = Mint::unpack(&client.get_account(&fund.fund_token_mint.pubkey()).await.data).unwrap();
+    assert_eq!(fund_token_mint.mint_authority, COption::Some(fund.vault_authority));
+    for token_account in &[
+        fund.asset_vaults[0].pubkey(),
+        fund.basic_asset_vault.pubkey(),
+        fund.fund_token_account.pubkey(),
+    ] {
+        let token_account = TokenAccount::unpack(&client.get_account(token_account).await.data).unwrap();
+        assert_eq!(token_account.owner, fund.vault_authority);
+    }
+
+    // The new vault signer redeems
+    let fund_tokens = token::get_balance(&mut client, &user.fund_token_account.pubkey()).await;
+    fund.execute(&mut client, &user, PoolAction::Redeem(fund_tokens), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.fund_token_account.pubkey()).await,
+        0
+    );
+
+    let asset_vault = token::create_account(&mut client, &fund.vault_authority, &asset_mint.pubkey()).await;
+    fund.process_admin_instruction(&mut client, add_asset, add_asset_accounts(asset_vault.pubkey()))
+        .await
+        .unwrap();
+    let (pool_state, _) = fund.get_state(&mut client).await;
+    assert_eq!(pool_state.assets.len(), 2);
+}
+
+#[tokio::test]
+async fn test_remove_asset() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 30)]).await;
+    let swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+    )
+    .await;
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateAssetSwap {
+            with_intermediate: false,
+        },
+        vec![
+            AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+            AccountMeta::new_readonly(swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+        ],
+    )
+    .await
+    .unwrap();
+
+    let mut remove_asset_accounts = vec![
+        AccountMeta::new(fund.asset_vaults[0].pubkey(), false),
+        AccountMeta::new_readonly(fund.vault_authority, false),
+        AccountMeta::new(fund.basic_asset_vault.pubkey(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_token_swap::id(), false),
+    ];
+    remove_asset_accounts.extend(swap_accounts(&swap));
+
+    // The 70 tokens of asset 0 sell for 349 of the basic asset, below the minimum of 350
+    let result = fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::RemoveAsset {
+                minimum_amount_out: 350,
+            },
+            remove_asset_accounts.clone(),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(SwapError::ExceededSlippage as u32))
+    );
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::RemoveAsset {
+            minimum_amount_out: 349,
+        },
+        remove_asset_accounts,
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await, 0);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        349
+    );
+    let (pool_state, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(pool_state.assets.len(), 1);
+    assert_eq!(
+        pool_state.assets[0].vault_address.as_ref(),
+        &fund.asset_vaults[1].pubkey()
+    );
+    assert_eq!(fund_state.asset_weights, vec![1]);
+}
+
+#[tokio::test]
+async fn test_remove_asset_on_market() {
+    let (mut program_test, program_id) = program_test();
+    let dex_program_id = dex::add_dex_program(&mut program_test);
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(0, 100), (0, 30)]).await;
+    let market = TestMarket::create(
+        &mut client,
+        &dex_program_id,
+        &fund.asset_mints[0].pubkey(),
+        &fund.basic_asset_mint.pubkey(),
+    )
+    .await;
+    let open_orders = client.create_account(&dex_program_id, OPEN_ORDERS_LEN).await;
+    fund.process_admin_instruction(&mut client, FundInstructionInner::UpdateAssetMarket, vec![
+        AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+        AccountMeta::new_readonly(market.market.pubkey(), false),
+        AccountMeta::new_readonly(open_orders.pubkey(), false),
+        AccountMeta::new_readonly(dex_program_id, false),
+    ])
+    .await
+    .unwrap();
+
+    // A maker bids 100 of the basic asset for the asset
+    let maker = fund.create_user(&mut client, 1_000_000).await;
+    let maker_open_orders = client.create_account(&dex_program_id, OPEN_ORDERS_LEN).await;
+    market
+        .place_order(
+            &mut client,
+            &dex_program_id,
+            &maker.owner,
+            &maker_open_orders.pubkey(),
+            &maker.basic_asset_account.pubkey(),
+            Side::Bid,
+            100,
+            100,
+        )
+        .await
+        .unwrap();
+
+    // The asset is sold on its market rather than on its token-swap route
+    let mut accounts = vec![
+        AccountMeta::new(fund.asset_vaults[0].pubkey(), false),
+        AccountMeta::new_readonly(fund.vault_authority, false),
+        AccountMeta::new(fund.basic_asset_vault.pubkey(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_token_swap::id(), false),
+        AccountMeta::new_readonly(dex_program_id, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    accounts.extend(market.venue_accounts(&open_orders.pubkey()));
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::RemoveAsset {
+            minimum_amount_out: 9_900,
+        },
+        accounts,
+    )
+    .await
+    .unwrap();
+
+    // 100 tokens are sold at the bid price for 10_000 less the taker fee of 22
+    assert_eq!(token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await, 0);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        9_978
+    );
+    let (pool_state, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(pool_state.assets.len(), 1);
+    assert_eq!(fund_state.asset_markets, vec![None]);
+}
+
+#[tokio::test]
+async fn test_simulate_rebalance() {
+    let (mut program_test, program_id) = program_test();
+    let (retbuf_program_id, retbuf_account) = retbuf::add_retbuf(&mut program_test, 128);
+    let mut client: TestClient = program_test.start().await.into();
+
+    // Asset 0 is worth 3000 of the basic asset and asset 1 is worth 4000, both target 3500
+    let fund = TestFund::create(&mut client, program_id, &[(2, 600), (0, 4000)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+
+    fund.approve_swaps(&mut client, &swaps).await;
+
+    // Does not require the admin signature nor a writable pool account
+    let mut accounts = fund.rebalance_accounts(&swaps);
+    accounts.extend(vec![
+        AccountMeta::new(retbuf_account, false),
+        AccountMeta::new_readonly(retbuf_program_id, false),
+    ]);
+    let mut instruction = fund.keeper_instruction(FundInstructionInner::SimulateRebalance, accounts);
+    instruction.accounts[0].is_writable = false;
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&client.payer_pubkey()));
+    transaction.sign(&[client.payer()], client.recent_blockhash());
+    client.process_transaction(transaction).await.unwrap();
+
+    let retbuf = client.client.get_account(retbuf_account).await.unwrap().unwrap();
+    let trades = Vec::<PlannedTrade>::deserialize(&mut retbuf.data.as_slice()).unwrap();
+    assert_eq!(trades, vec![
+        PlannedTrade {
+            current_value: 3000,
+            target_value: 3500,
+            direction: PendingTrade::Buy,
+            amount_in: 500,
+            minimum_amount_out: 99,
+        },
+        PlannedTrade {
+            current_value: 4000,
+            target_value: 3500,
+            direction: PendingTrade::Sell,
+            amount_in: 500,
+            minimum_amount_out: 495,
+        },
+    ]);
+
+    // Nothing is traded
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        600
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await,
+        4000
+    );
+}
+
+#[tokio::test]
+async fn test_start_rebalance_swap_reserves() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70)]).await;
+    token::mint_to(
+        &mut client,
+        &fund.mint_authority,
+        &fund.basic_asset_mint.pubkey(),
+        &fund.basic_asset_vault.pubkey(),
+        7000,
+        BASIC_ASSET_DECIMALS,
+    )
+    .await;
+    let swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[0].pubkey(), 2, 1000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5000),
+    )
+    .await;
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateAssetSwap {
+            with_intermediate: false,
+        },
+        vec![
+            AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+            AccountMeta::new_readonly(swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+        ],
+    )
+    .await
+    .unwrap();
+
+    let start_rebalance_accounts = |swap_asset: Pubkey, swap_basic_asset: Pubkey| {
+        vec![
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+            AccountMeta::new_readonly(fund.basic_asset_vault.pubkey(), false),
+            AccountMeta::new_readonly(swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(swap_asset, false),
+            AccountMeta::new_readonly(swap_basic_asset, false),
+        ]
+    };
+
+    // An account of the asset outside of the pool reserves
+    let other_asset_account = token::create_account(&mut client, &swap.authority, &fund.asset_mints[0].pubkey()).await;
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::StartRebalance,
+            start_rebalance_accounts(other_asset_account.pubkey(), swap.token_b.pubkey()),
+        )
+        .await
+        .is_err());
+
+    // The reserves in the wrong order
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::StartRebalance,
+            start_rebalance_accounts(swap.token_b.pubkey(), swap.token_a.pubkey()),
+        )
+        .await
+        .is_err());
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::StartRebalance,
+        start_rebalance_accounts(swap.token_a.pubkey(), swap.token_b.pubkey()),
+    )
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert!(fund_state.rebalance_plan.is_some());
+}
+
+#[tokio::test]
+async fn test_rebalance_from_empty_vault() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    // The fund holds none of asset 1, both assets are valued at the pool rates
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 0)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+
+    fund.approve_swaps(&mut client, &swaps).await;
+
+    // Asset 0 is sold for about 175 of the basic asset, the buy of asset 1 spends what the sell yielded
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::Rebalance,
+        fund.rebalance_accounts(&swaps),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        35
+    );
+    assert!(token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await >= 173);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_rebalance_two_hop_route() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    // Asset 1 trades at 2 intermediate tokens, the intermediate token at 0.5 of the basic asset
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 0)]).await;
+    let intermediate_mint = token::create_token(&mut client, &fund.mint_authority.pubkey(), 0).await;
+    let intermediate_vault =
+        token::create_account(&mut client, &fund.vault_authority, &intermediate_mint.pubkey()).await;
+    let swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+    )
+    .await;
+    let first_hop_swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+        (&intermediate_mint.pubkey(), 0, 2_000_000),
+    )
+    .await;
+    let second_hop_swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&intermediate_mint.pubkey(), 0, 1_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 500_000),
+    )
+    .await;
+
+    fund.approve_swaps(&mut client, std::slice::from_ref(&swap)).await;
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateAssetSwap {
+            with_intermediate: true,
+        },
+        vec![
+            AccountMeta::new_readonly(fund.asset_vaults[1].pubkey(), false),
+            AccountMeta::new_readonly(first_hop_swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+            AccountMeta::new_readonly(second_hop_swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(intermediate_vault.pubkey(), false),
+        ],
+    )
+    .await
+    .unwrap();
+
+    // 100 intermediate tokens left in the fund account are worth 50 of the basic asset
+    token::mint_to(
+        &mut client,
+        &fund.mint_authority,
+        &intermediate_mint.pubkey(),
+        &intermediate_vault.pubkey(),
+        100,
+        0,
+    )
+    .await;
+
+    let mut accounts = vec![
+        AccountMeta::new(fund.asset_vaults[0].pubkey(), false),
+        AccountMeta::new(fund.asset_vaults[1].pubkey(), false),
+        AccountMeta::new_readonly(fund.vault_authority, false),
+        AccountMeta::new(fund.basic_asset_vault.pubkey(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_token_swap::id(), false),
+    ];
+    accounts.extend(swap_accounts(&swap));
+    accounts.extend(swap_accounts(&first_hop_swap));
+    accounts.push(AccountMeta::new(intermediate_vault.pubkey(), false));
+    accounts.extend(swap_accounts(&second_hop_swap));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+
+    // The fund valued at 400 sells 30 tokens of asset 0, and buys asset 1 with the proceeds through both hops, the
+    // intermediate tokens in the fund account are left untouched
+    fund.process_admin_instruction(&mut client, FundInstructionInner::Rebalance, accounts)
+        .await
+        .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        40
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await,
+        148
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        0
+    );
+    assert_eq!(token::get_balance(&mut client, &intermediate_vault.pubkey()).await, 100);
+}
+
+#[tokio::test]
+async fn test_rebalance_oracle_price() {
+    let (mut program_test, program_id) = program_test();
+    // Asset 0 trades at 500 basic asset tokens per whole token on its pool, 0.000500 in whole basic asset tokens
+    let oracle_price = |price: i64, conf: u64| Price {
+        expo: -6,
+        price,
+        conf,
+        status: PRICE_STATUS_TRADING,
+        pub_slot: 0,
+    };
+    let price_account = oracle::add_price(&mut program_test, oracle_price(490, 0));
+    let deviating_price_account = oracle::add_price(&mut program_test, oracle_price(400, 0));
+    let uncertain_price_account = oracle::add_price(&mut program_test, oracle_price(490, 10));
+    let stale_price_account = oracle::add_price(&mut program_test, oracle_price(490, 0));
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 0)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+
+    // The pool price is 20% off the oracle one
+    fund.update_asset_oracle(&mut client, 0, &deviating_price_account, 100, 100)
+        .await
+        .unwrap();
+    let result = fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::Rebalance,
+            fund.rebalance_accounts_with_prices(&swaps, &[deviating_price_account]),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::OraclePriceDeviation as u32))
+    );
+
+    // The confidence interval is about 2% of the price
+    fund.update_asset_oracle(&mut client, 0, &uncertain_price_account, 100, 100)
+        .await
+        .unwrap();
+    let result = fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::Rebalance,
+            fund.rebalance_accounts_with_prices(&swaps, &[uncertain_price_account]),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::OraclePriceUncertain as u32))
+    );
+
+    // The price was published at slot 0
+    fund.update_asset_oracle(&mut client, 0, &stale_price_account, 0, 100)
+        .await
+        .unwrap();
+    let result = fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::Rebalance,
+            fund.rebalance_accounts_with_prices(&swaps, &[stale_price_account]),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::OraclePriceStale as u32))
+    );
+
+    // Asset 0 is valued at the oracle price, 343 of the basic asset, the buy of asset 1 spends its target of 171 and
+    // leaves the rest of the sell in the basic asset vault
+    fund.update_asset_oracle(&mut client, 0, &price_account, u64::MAX, 100)
+        .await
+        .unwrap();
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::Rebalance,
+        fund.rebalance_accounts_with_prices(&swaps, &[price_account]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        35
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await,
+        170
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        3
+    );
+}
+
+#[tokio::test]
+async fn test_rebalance_nav_guard() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    // Selling half of asset 0 into a thin pool loses about 2% of the fund value
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 0)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateSlippage {
+            max_slippage_bps: 1_000,
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateMaxNavLoss { max_nav_loss_bps: 100 },
+        vec![],
+    )
+    .await
+    .unwrap();
+    let result = fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::Rebalance,
+            fund.rebalance_accounts(&swaps),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::RebalanceNavLoss as u32))
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        70
+    );
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateMaxNavLoss { max_nav_loss_bps: 500 },
+        vec![],
+    )
+    .await
+    .unwrap();
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateWeights {
+            asset_weights: vec![1, 1],
+        },
+        fund.rebalance_accounts(&swaps),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        35
+    );
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.max_nav_loss_bps, 500);
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateMaxNavLoss {
+                max_nav_loss_bps: 10_001
+            },
+            vec![],
+        )
+        .await
+        .is_err());
+
+    // Asset 1 is planned to be bought at the start rate, but its pool price falls by three quarters before the buy
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateWeights {
+            asset_weights: vec![1, 3],
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::StartRebalance,
+        fund.start_rebalance_accounts(&swaps),
+    )
+    .await
+    .unwrap();
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::RebalanceAssets { indices: vec![0] },
+        fund.rebalance_assets_accounts(&swaps, &[0]),
+    )
+    .await
+    .unwrap();
+
+    let user = fund.create_user(&mut client, 1_000_000).await;
+    swaps[1]
+        .swap(
+            &mut client,
+            &user.owner,
+            &user.asset_accounts[1].pubkey(),
+            &user.basic_asset_account.pubkey(),
+            true,
+            1_000_000,
+        )
+        .await;
+
+    // The buy gets about four times the planned amount and overshoots the target
+    let result = fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::RebalanceAssets { indices: vec![1] },
+            fund.rebalance_assets_accounts(&swaps, &[1]),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::RebalanceDrift as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_start_rebalance_and_rebalance_assets() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 0)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::StartRebalance,
+        fund.start_rebalance_accounts(&swaps),
+    )
+    .await
+    .unwrap();
+
+    // The planned trades wait while the fund is paused
+    fund.process_admin_instruction(&mut client, FundInstructionInner::Pause, vec![])
+        .await
+        .unwrap();
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::RebalanceAssets { indices: vec![0] },
+            fund.rebalance_assets_accounts(&swaps, &[0]),
+        )
+        .await
+        .is_err());
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        70
+    );
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::Unpause,
+        fund.asset_vaults
+            .iter()
+            .map(|asset_vault| AccountMeta::new_readonly(asset_vault.pubkey(), false))
+            .collect(),
+    )
+    .await
+    .unwrap();
+
+    // Asset 0 is sold for about 175 of the basic asset, then asset 1 is bought with it
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::RebalanceAssets { indices: vec![0, 1] },
+        fund.rebalance_assets_accounts(&swaps, &[0, 1]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        35
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await,
+        173
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        0
+    );
+
+    // The plan is done
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert!(fund_state.rebalance_plan.is_none());
+}
+
+#[tokio::test]
+async fn test_rebalance_assets_in_slices() {
+    let (mut program_test, program_id) = program_test();
+    let price_accounts = vec![
+        oracle::add_price_account(&mut program_test, 500, -6),
+        oracle::add_price_account(&mut program_test, 1, -6),
+    ];
+    let mut client: TestClient = program_test.start().await.into();
+
+    // Half of asset 0, worth 175 of the basic asset, is sold for asset 1
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 0)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+    // The keepers need the oracle prices
+    for (i, price_account) in price_accounts.iter().enumerate() {
+        fund.update_asset_oracle(&mut client, i, price_account, u64::MAX, 100)
+            .await
+            .unwrap();
+    }
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateSlippage {
+            max_slippage_bps: 1_000,
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateMaxSliceValue { max_slice_value: 100 },
+        vec![],
+    )
+    .await
+    .unwrap();
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateCrank {
+            crank: CrankConfig {
+                enabled: true,
+                min_interval: 24 * 60 * 60,
+                drift_threshold_bps: 500,
+                reward: KeeperReward::None,
+            },
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::StartRebalance,
+        fund.start_rebalance_accounts_with_prices(&swaps, &price_accounts),
+    )
+    .await
+    .unwrap();
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.max_slice_value, 100);
+    let plan = fund_state.rebalance_plan.unwrap();
+    assert_eq!(plan.pending_trades, vec![PendingTrade::Sell, PendingTrade::Buy]);
+    assert_eq!(plan.exchange_rates, vec![
+        ExchangeRate {
+            asset: 100,
+            basic_asset: 500,
+        },
+        ExchangeRate {
+            asset: 1,
+            basic_asset: 1,
+        },
+    ]);
+
+    // An asset is traded at most once per instruction, in the assets order
+    for indices in &[[0, 0], [1, 0]] {
+        let result = fund
+            .process_admin_instruction(
+                &mut client,
+                FundInstructionInner::RebalanceAssets {
+                    indices: indices.iter().map(|&i| i as u8).collect(),
+                },
+                fund.rebalance_assets_accounts(&swaps, indices),
+            )
+            .await;
+        assert_eq!(
+            instruction_error(&result),
+            Some(InstructionError::InvalidInstructionData)
+        );
+    }
+
+    // The first slice sells 20 of asset 0, worth 100
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::RebalanceAssets { indices: vec![0] },
+        fund.rebalance_assets_accounts(&swaps, &[0]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        50
+    );
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.rebalance_plan.unwrap().pending_trades, vec![
+        PendingTrade::Sell,
+        PendingTrade::Buy
+    ]);
+
+    let crank_rebalance_assets = |indices: &[usize]| {
+        let mut accounts = vec![AccountMeta::new_readonly(sysvar::clock::id(), false)];
+        accounts.extend(fund.rebalance_assets_accounts(&swaps, indices));
+        fund.keeper_instruction(
+            FundInstructionInner::CrankRebalanceAssets {
+                indices: indices.iter().map(|&i| i as u8).collect(),
+            },
+            accounts,
+        )
+    };
+
+    // One keeper slice per slot, the instructions of a transaction share the slot
+    let mut transaction = Transaction::new_with_payer(
+        &[crank_rebalance_assets(&[0]), crank_rebalance_assets(&[1])],
+        Some(&client.payer_pubkey()),
+    );
+    transaction.sign(&[client.payer()], client.recent_blockhash());
+    assert!(client.process_transaction(transaction).await.is_err());
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        50
+    );
+
+    // A keeper sells the rest
+    let mut transaction = Transaction::new_with_payer(&[crank_rebalance_assets(&[0])], Some(&client.payer_pubkey()));
+    transaction.sign(&[client.payer()], client.recent_blockhash());
+    client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        35
+    );
+
+    // The buy of asset 1 is sliced as well
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::RebalanceAssets { indices: vec![1] },
+        fund.rebalance_assets_accounts(&swaps, &[1]),
+    )
+    .await
+    .unwrap();
+    assert!(token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await >= 99);
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.rebalance_plan.unwrap().pending_trades, vec![
+        PendingTrade::None,
+        PendingTrade::Buy
+    ]);
+}
+
+#[tokio::test]
+async fn test_update_crank() {
+    let (mut program_test, program_id) = program_test();
+    let price_accounts = [
+        oracle::add_price_account(&mut program_test, 500, -6),
+        oracle::add_price_account(&mut program_test, 1, -6),
+    ];
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 30)]).await;
+
+    // Disabled by default
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert!(!fund_state.crank.enabled);
+
+    let crank = CrankConfig {
+        enabled: true,
+        min_interval: 24 * 60 * 60,
+        drift_threshold_bps: 500,
+        reward: KeeperReward::FundTokens { amount: 1 },
+    };
+
+    // The keeper trades at the oracle prices, asset 1 has none
+    fund.update_asset_oracle(&mut client, 0, &price_accounts[0], 25, 100)
+        .await
+        .unwrap();
+    let result = fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateCrank { crank: crank.clone() },
+            vec![],
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::MissingAssetOracle as u32))
+    );
+
+    fund.update_asset_oracle(&mut client, 1, &price_accounts[1], 25, 100)
+        .await
+        .unwrap();
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateCrank {
+            crank: CrankConfig {
+                drift_threshold_bps: 400,
+                ..crank.clone()
+            },
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.crank.drift_threshold_bps, 400);
+    assert_eq!(fund_state.last_rebalance_timestamp, 0);
+
+    // The interval condition must not always hold
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateCrank {
+                crank: CrankConfig {
+                    min_interval: 0,
+                    ..crank
+                },
+            },
+            vec![],
+        )
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_crank() {
+    let (mut program_test, program_id) = program_test();
+    // The oracle prices of the assets match their pool prices
+    let price_accounts = vec![
+        oracle::add_price_account(&mut program_test, 500, -6),
+        oracle::add_price_account(&mut program_test, 1, -6),
+    ];
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 0)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+    for (i, price_account) in price_accounts.iter().enumerate() {
+        fund.update_asset_oracle(&mut client, i, price_account, u64::MAX, 100)
+            .await
+            .unwrap();
+    }
+
+    // A fund token account of a keeper for each crank, the transactions of a test must differ
+    let mut keeper_accounts = Vec::new();
+    for _ in 0..6 {
+        let keeper_account =
+            token::create_account(&mut client, &Pubkey::new_unique(), &fund.fund_token_mint.pubkey()).await;
+        keeper_accounts.push(keeper_account.pubkey());
+    }
+    let crank_accounts = |keeper_account: &Pubkey| {
+        let mut accounts = vec![
+            AccountMeta::new(*keeper_account, false),
+            AccountMeta::new(fund.fund_token_mint.pubkey(), false),
+        ];
+        accounts.extend(fund.rebalance_accounts_with_prices(&swaps, &price_accounts));
+        accounts
+    };
+    let update_crank = |drift_threshold_bps: u16| FundInstructionInner::UpdateCrank {
+        crank: CrankConfig {
+            enabled: true,
+            min_interval: 60 * 60,
+            drift_threshold_bps,
+            reward: KeeperReward::FundTokens { amount: 5 },
+        },
+    };
+
+    // The crank is disabled
+    assert!(fund
+        .process_keeper_instruction(
+            &mut client,
+            FundInstructionInner::Crank,
+            crank_accounts(&keeper_accounts[0])
+        )
+        .await
+        .is_err());
+
+    // Asset 0 is 50% of the fund value above its target, below the drift threshold, but the fund was never
+    // rebalanced: the keeper rebalances it and is paid the reward
+    fund.process_admin_instruction(&mut client, update_crank(10_000), vec![])
+        .await
+        .unwrap();
+    fund.process_keeper_instruction(
+        &mut client,
+        FundInstructionInner::Crank,
+        crank_accounts(&keeper_accounts[1]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        35
+    );
+    assert_eq!(token::get_balance(&mut client, &keeper_accounts[1]).await, 5);
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert!(fund_state.last_rebalance_timestamp > 0);
+
+    // The new weights drift the fund again within the min interval, below the drift threshold
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateWeights {
+            asset_weights: vec![3, 1],
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+    assert!(fund
+        .process_keeper_instruction(
+            &mut client,
+            FundInstructionInner::Crank,
+            crank_accounts(&keeper_accounts[2])
+        )
+        .await
+        .is_err());
+    fund.process_admin_instruction(&mut client, update_crank(500), vec![])
+        .await
+        .unwrap();
+
+    // No asset is beyond the rebalance threshold, the keeper is not paid for nothing
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateRebalanceThreshold {
+            rebalance_threshold_bps: 10_000,
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+    assert!(fund
+        .process_keeper_instruction(
+            &mut client,
+            FundInstructionInner::Crank,
+            crank_accounts(&keeper_accounts[3])
+        )
+        .await
+        .is_err());
+    assert_eq!(token::get_balance(&mut client, &keeper_accounts[3]).await, 0);
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateRebalanceThreshold {
+            rebalance_threshold_bps: 100,
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    // The drift exceeds the threshold within the min interval, the keeper rebalances the fund again
+    fund.process_keeper_instruction(
+        &mut client,
+        FundInstructionInner::Crank,
+        crank_accounts(&keeper_accounts[4]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(token::get_balance(&mut client, &keeper_accounts[4]).await, 5);
+    assert!(token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await > 35);
+
+    // An asset without oracle
+    fund.process_admin_instruction(&mut client, FundInstructionInner::RemoveAssetOracle, vec![
+        AccountMeta::new_readonly(fund.asset_vaults[1].pubkey(), false),
+    ])
+    .await
+    .unwrap();
+    let mut accounts = crank_accounts(&keeper_accounts[5]);
+    accounts.remove(accounts.len() - 2);
+    let result = fund
+        .process_keeper_instruction(&mut client, FundInstructionInner::Crank, accounts)
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::MissingAssetOracle as u32))
+    );
+}
+
+#[tokio::test]
+async fn test_crank_basic_asset_reward() {
+    let (mut program_test, program_id) = program_test();
+    let price_accounts = vec![
+        oracle::add_price_account(&mut program_test, 500, -6),
+        oracle::add_price_account(&mut program_test, 1, -6),
+    ];
+    let mut client: TestClient = program_test.start().await.into();
+
+    // The fund vault of basic asset holds its target third of the fund value
+    let fund = TestFund::create(&mut client, program_id, &[(2, 700_000), (0, 0)]).await;
+    token::mint_to(
+        &mut client,
+        &fund.mint_authority,
+        &fund.basic_asset_mint.pubkey(),
+        &fund.basic_asset_vault.pubkey(),
+        1_750_000,
+        BASIC_ASSET_DECIMALS,
+    )
+    .await;
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateBasicAssetWeight { basic_asset_weight: 1 },
+        vec![],
+    )
+    .await
+    .unwrap();
+    // Deep pools, the trades lose little of the fund value
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+    for (i, price_account) in price_accounts.iter().enumerate() {
+        fund.update_asset_oracle(&mut client, i, price_account, u64::MAX, 100)
+            .await
+            .unwrap();
+    }
+
+    // A basic asset account of a keeper for each crank, the transactions of a test must differ
+    let mut keeper_accounts = Vec::new();
+    for _ in 0..2 {
+        let keeper_account =
+            token::create_account(&mut client, &Pubkey::new_unique(), &fund.basic_asset_mint.pubkey()).await;
+        keeper_accounts.push(keeper_account.pubkey());
+    }
+    let crank_accounts = |keeper_account: &Pubkey| {
+        let mut accounts = vec![
+            AccountMeta::new(*keeper_account, false),
+            AccountMeta::new(fund.fund_token_mint.pubkey(), false),
+        ];
+        accounts.extend(fund.rebalance_accounts_with_prices(&swaps, &price_accounts));
+        accounts
+    };
+    let update_crank = |amount: u64| FundInstructionInner::UpdateCrank {
+        crank: CrankConfig {
+            enabled: true,
+            min_interval: 60 * 60,
+            drift_threshold_bps: 500,
+            reward: KeeperReward::BasicAsset { amount },
+        },
+    };
+
+    // A reward of a fifth of the fund value exceeds the max NAV loss of the rebalance
+    fund.process_admin_instruction(&mut client, update_crank(1_000_000), vec![])
+        .await
+        .unwrap();
+    let result = fund
+        .process_keeper_instruction(
+            &mut client,
+            FundInstructionInner::Crank,
+            crank_accounts(&keeper_accounts[0]),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::RebalanceNavLoss as u32))
+    );
+    assert_eq!(token::get_balance(&mut client, &keeper_accounts[0]).await, 0);
+
+    fund.process_admin_instruction(&mut client, update_crank(10_000), vec![])
+        .await
+        .unwrap();
+    fund.process_keeper_instruction(
+        &mut client,
+        FundInstructionInner::Crank,
+        crank_accounts(&keeper_accounts[1]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(token::get_balance(&mut client, &keeper_accounts[1]).await, 10_000);
+}
+
+#[tokio::test]
+async fn test_update_rebalance_cooldown() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 30)]).await;
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateRebalanceCooldown {
+            rebalance_cooldown: 60 * 60,
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.rebalance_cooldown, 60 * 60);
+    assert_eq!(fund_state.last_rebalance_slot, 0);
+
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::UpdateRebalanceCooldown { rebalance_cooldown: -1 },
+            vec![],
+        )
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_rebalance_cooldown() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 0)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateRebalanceCooldown {
+            rebalance_cooldown: 60 * 60,
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let rebalance_instruction = || {
+        let mut accounts = vec![
+            AccountMeta::new(fund.fund_account.pubkey(), false),
+            AccountMeta::new_readonly(client.payer_pubkey(), true),
+        ];
+        accounts.extend(fund.rebalance_accounts(&swaps));
+        Instruction {
+            program_id,
+            accounts,
+            data: FundRequest {
+                tag: Default::default(),
+                inner: FundInstructionInner::Rebalance,
+            }
+            .try_to_vec()
+            .unwrap(),
+        }
+    };
+
+    // The second rebalance is within the cooldown of the first one
+    let mut transaction = Transaction::new_with_payer(
+        &[rebalance_instruction(), rebalance_instruction()],
+        Some(&client.payer_pubkey()),
+    );
+    transaction.sign(&[client.payer()], client.recent_blockhash());
+    let result = client.process_transaction(transaction).await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::RebalanceCooldown as u32))
+    );
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.last_rebalance_slot, 0);
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::Rebalance,
+        fund.rebalance_accounts(&swaps),
+    )
+    .await
+    .unwrap();
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert!(fund_state.last_rebalance_slot > 0);
+}
+
+#[tokio::test]
+async fn test_rebalance_assets_without_plan() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 30)]).await;
+
+    assert!(fund
+        .process_admin_instruction(
+            &mut client,
+            FundInstructionInner::RebalanceAssets { indices: vec![0] },
+            vec![],
+        )
+        .await
+        .is_err());
+
+    fund.process_admin_instruction(&mut client, FundInstructionInner::CancelRebalance, vec![])
+        .await
+        .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.rebalance_plan, None);
+}
+
+#[tokio::test]
+async fn test_redeem() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(0, 70), (0, 33)]).await;
+    let user = fund.create_user(&mut client, 1000).await;
+
+    fund.execute(&mut client, &user, PoolAction::Create(1000), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.fund_token_account.pubkey()).await,
+        999
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[0].pubkey()).await,
+        300
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[1].pubkey()).await,
+        670
+    );
+
+    let (pool_state, _) = fund.get_state(&mut client).await;
+    let redemption = basket::redemption(
+        &pool_state,
+        &FeeAccounts::new(&pool_state, None),
+        &user.fund_token_account.pubkey(),
+        &[770, 363],
+        0,
+        token::get_supply(&mut client, &fund.fund_token_mint.pubkey()).await,
+        999,
+    )
+    .unwrap();
+    assert_eq!(redemption.fees.total_fee(), 1);
+    assert_eq!(redemption.burned, 998);
+    assert_eq!(redemption.asset_amounts, vec![698, 329]);
+
+    fund.execute(&mut client, &user, PoolAction::Redeem(999), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.fund_token_account.pubkey()).await,
+        0
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[0].pubkey()).await,
+        998
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[1].pubkey()).await,
+        999
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        72
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await,
+        34
+    );
+    assert_eq!(token::get_balance(&mut client, &fund.lqd_fee_account.pubkey()).await, 2);
+    assert_eq!(
+        token::get_supply(&mut client, &fund.fund_token_mint.pubkey()).await,
+        102
+    );
+
+    // Nothing left to redeem
+    assert!(fund
+        .execute(&mut client, &user, PoolAction::Redeem(1), None)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_redemption_fees() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(0, 70), (0, 33)]).await;
+    let user = fund.create_user(&mut client, 100_000).await;
+    let referrer_account =
+        token::create_account(&mut client, &Keypair::new().pubkey(), &fund.fund_token_mint.pubkey()).await;
+
+    fund.execute(
+        &mut client,
+        &user,
+        PoolAction::Create(100_000),
+        Some(&referrer_account.pubkey()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.fund_token_account.pubkey()).await,
+        99_900
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.lqd_fee_account.pubkey()).await,
+        40
+    );
+    assert_eq!(token::get_balance(&mut client, &referrer_account.pubkey()).await, 20);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.initializer_fee_account.pubkey()).await,
+        40
+    );
+
+    let (pool_state, _) = fund.get_state(&mut client).await;
+    let redemption = basket::redemption(
+        &pool_state,
+        &FeeAccounts::new(&pool_state, Some(&referrer_account.pubkey())),
+        &user.fund_token_account.pubkey(),
+        &[70_070, 33_033],
+        0,
+        token::get_supply(&mut client, &fund.fund_token_mint.pubkey()).await,
+        99_900,
+    )
+    .unwrap();
+    assert_eq!(
+        (
+            redemption.fees.lqd_fee,
+            redemption.fees.referrer_fee,
+            redemption.fees.initializer_fee
+        ),
+        (40, 20, 40)
+    );
+    assert_eq!(redemption.burned, 99_800);
+    assert_eq!(redemption.asset_amounts, vec![69_860, 32_934]);
+
+    fund.execute(
+        &mut client,
+        &user,
+        PoolAction::Redeem(99_900),
+        Some(&referrer_account.pubkey()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[0].pubkey()).await,
+        99_860
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[1].pubkey()).await,
+        99_934
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.lqd_fee_account.pubkey()).await,
+        80
+    );
+    assert_eq!(token::get_balance(&mut client, &referrer_account.pubkey()).await, 40);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.initializer_fee_account.pubkey()).await,
+        80
+    );
+    assert_eq!(
+        token::get_supply(&mut client, &fund.fund_token_mint.pubkey()).await,
+        300
+    );
+}
+
+#[tokio::test]
+async fn test_zap_in() {
+    let (mut program_test, program_id) = program_test();
+    // The oracle prices of the assets match their pool prices
+    let price_accounts = vec![
+        oracle::add_price_account(&mut program_test, 500, -6),
+        oracle::add_price_account(&mut program_test, 1, -6),
+    ];
+    let mut client: TestClient = program_test.start().await.into();
+
+    // Both assets are worth 350 of the basic asset, 7 per fund token
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 350)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+    let user = fund.create_user(&mut client, 10_000).await;
+
+    // The fund tokens are priced at the oracle prices, asset 1 has none
+    fund.update_asset_oracle(&mut client, 0, &price_accounts[0], u64::MAX, 100)
+        .await
+        .unwrap();
+    let result = fund
+        .zap_in(&mut client, &user, &swaps, &price_accounts[..1], 7000, 0)
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::MissingAssetOracle as u32))
+    );
+    fund.update_asset_oracle(&mut client, 1, &price_accounts[1], u64::MAX, 100)
+        .await
+        .unwrap();
+
+    // 7000 of the basic asset buy about 1000 fund tokens
+    let result = fund
+        .zap_in(&mut client, &user, &swaps, &price_accounts, 7000, 1001)
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::MinimumFundTokensOut as u32))
+    );
+
+    fund.process_admin_instruction(&mut client, FundInstructionInner::Pause, vec![])
+        .await
+        .unwrap();
+    assert!(fund
+        .zap_in(&mut client, &user, &swaps, &price_accounts, 7000, 900)
+        .await
+        .is_err());
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::Unpause,
+        fund.asset_vaults
+            .iter()
+            .map(|asset_vault| AccountMeta::new_readonly(asset_vault.pubkey(), false))
+            .collect(),
+    )
+    .await
+    .unwrap();
+
+    fund.zap_in(&mut client, &user, &swaps, &price_accounts, 7000, 950)
+        .await
+        .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.basic_asset_account.pubkey()).await,
+        3000
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.fund_token_account.pubkey()).await,
+        996
+    );
+    assert_eq!(token::get_balance(&mut client, &fund.lqd_fee_account.pubkey()).await, 1);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        769
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await,
+        3837
+    );
+}
+
+#[tokio::test]
+async fn test_zap_out() {
+    let (mut program_test, program_id) = program_test();
+    // The oracle prices of the assets match their pool prices
+    let price_accounts = vec![
+        oracle::add_price_account(&mut program_test, 500, -6),
+        oracle::add_price_account(&mut program_test, 1, -6),
+    ];
+    let mut client: TestClient = program_test.start().await.into();
+
+    // Both assets are worth 3500 of the basic asset, 70 per fund token
+    let fund = TestFund::create(&mut client, program_id, &[(2, 700), (0, 3500)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 100_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 500_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 100_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 100_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+    let user = fund.create_user(&mut client, 1_000_000).await;
+    let referrer_account =
+        token::create_account(&mut client, &Keypair::new().pubkey(), &fund.fund_token_mint.pubkey()).await;
+    fund.execute(&mut client, &user, PoolAction::Create(10_000), None)
+        .await
+        .unwrap();
+    let fund_tokens = token::get_balance(&mut client, &user.fund_token_account.pubkey()).await;
+    // The initializer fee account receives the referrer fee of the creation
+    let lqd_fees = token::get_balance(&mut client, &fund.lqd_fee_account.pubkey()).await;
+    let initializer_fees = token::get_balance(&mut client, &fund.initializer_fee_account.pubkey()).await;
+    assert_eq!((fund_tokens, lqd_fees, initializer_fees), (9990, 4, 6));
+
+    // The sells are bounded at the oracle prices, asset 1 has none
+    fund.update_asset_oracle(&mut client, 0, &price_accounts[0], u64::MAX, 100)
+        .await
+        .unwrap();
+    let result = fund
+        .zap_out(
+            &mut client,
+            &user,
+            &swaps,
+            &price_accounts[..1],
+            fund_tokens,
+            0,
+            Some(&referrer_account.pubkey()),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::MissingAssetOracle as u32))
+    );
+    fund.update_asset_oracle(&mut client, 1, &price_accounts[1], u64::MAX, 100)
+        .await
+        .unwrap();
+
+    // The redeemed shares are worth about 698_600 of the basic asset
+    let result = fund
+        .zap_out(
+            &mut client,
+            &user,
+            &swaps,
+            &price_accounts,
+            fund_tokens,
+            10_000_000,
+            Some(&referrer_account.pubkey()),
+        )
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::MinimumAmountOut as u32))
+    );
+
+    let basic_asset_balance = token::get_balance(&mut client, &user.basic_asset_account.pubkey()).await;
+    fund.zap_out(
+        &mut client,
+        &user,
+        &swaps,
+        &price_accounts,
+        fund_tokens,
+        690_000,
+        Some(&referrer_account.pubkey()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.basic_asset_account.pubkey()).await - basic_asset_balance,
+        697_140
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.fund_token_account.pubkey()).await,
+        0
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        840
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await,
+        4200
+    );
+
+    // The fees of the 9990 fund tokens are split between the fee accounts, the rest is burned
+    assert_eq!(
+        token::get_balance(&mut client, &fund.lqd_fee_account.pubkey()).await,
+        lqd_fees + 4
+    );
+    assert_eq!(token::get_balance(&mut client, &referrer_account.pubkey()).await, 2);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.initializer_fee_account.pubkey()).await,
+        initializer_fees + 4
+    );
+    assert_eq!(
+        token::get_supply(&mut client, &fund.fund_token_mint.pubkey()).await,
+        120
+    );
+}
+
+#[tokio::test]
+async fn test_swap() {
+    let (mut program_test, program_id) = program_test();
+    let price_accounts = vec![
+        oracle::add_price_account(&mut program_test, 500, -6),
+        oracle::add_price_account(&mut program_test, 1, -6),
+    ];
+    let mut client: TestClient = program_test.start().await.into();
+
+    // Asset 0 is worth 3000 of the basic asset and asset 1 is worth 4000, both target 3500
+    let fund = TestFund::create(&mut client, program_id, &[(2, 600), (0, 4000)]).await;
+    let user = fund.create_user(&mut client, 10_000).await;
+
+    // The swaps are priced at the oracle prices only, asset 1 has none
+    fund.update_asset_oracle(&mut client, 0, &price_accounts[0], u64::MAX, 100)
+        .await
+        .unwrap();
+    let result = fund
+        .swap(&mut client, &user, &price_accounts[..1], &[100, 0], &[0, 498])
+        .await;
+    assert_eq!(
+        instruction_error(&result),
+        Some(InstructionError::Custom(FundError::MissingAssetOracle as u32))
+    );
+    fund.update_asset_oracle(&mut client, 1, &price_accounts[1], u64::MAX, 100)
+        .await
+        .unwrap();
+
+    // The deposit of asset 0 can't exceed its target value, nor the withdrawal of asset 1 fall below it
+    let result = fund
+        .swap(&mut client, &user, &price_accounts, &[101, 0], &[0, 400])
+        .await;
+    assert_eq!(instruction_error(&result), Some(InstructionError::InvalidArgument));
+    let result = fund
+        .swap(&mut client, &user, &price_accounts, &[100, 0], &[0, 500])
+        .await;
+    assert_eq!(instruction_error(&result), Some(InstructionError::InvalidArgument));
+
+    // 500 of the deposited value less the fee of 1 don't pay for a withdrawn value of 500
+    let result = fund
+        .swap(&mut client, &user, &price_accounts, &[100, 0], &[0, 499])
+        .await;
+    assert_eq!(instruction_error(&result), Some(InstructionError::InvalidArgument));
+
+    fund.swap(&mut client, &user, &price_accounts, &[100, 0], &[0, 498])
+        .await
+        .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[0].pubkey()).await,
+        9900
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[1].pubkey()).await,
+        10_498
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        700
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await,
+        3502
+    );
+}
+
+#[tokio::test]
+async fn test_basic_asset_share() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    // 10 asset tokens and 0.5 basic asset token per fund token
+    let fund = TestFund::create(&mut client, program_id, &[(0, 1_000)]).await;
+    token::mint_to(
+        &mut client,
+        &fund.mint_authority,
+        &fund.basic_asset_mint.pubkey(),
+        &fund.basic_asset_vault.pubkey(),
+        50,
+        BASIC_ASSET_DECIMALS,
+    )
+    .await;
+    let user = fund.create_user(&mut client, 2_000).await;
+
+    // The created share of the basic asset vault is rounded up
+    fund.execute(&mut client, &user, PoolAction::Create(99), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[0].pubkey()).await,
+        1_010
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.basic_asset_account.pubkey()).await,
+        1_950
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        100
+    );
+
+    let (pool_state, _) = fund.get_state(&mut client).await;
+    let fund_tokens = token::get_balance(&mut client, &user.fund_token_account.pubkey()).await;
+    let redemption = basket::redemption(
+        &pool_state,
+        &FeeAccounts::new(&pool_state, None),
+        &user.fund_token_account.pubkey(),
+        &[1_990],
+        100,
+        token::get_supply(&mut client, &fund.fund_token_mint.pubkey()).await,
+        fund_tokens,
+    )
+    .unwrap();
+
+    fund.execute(&mut client, &user, PoolAction::Redeem(fund_tokens), None)
+        .await
+        .unwrap();
+    assert_eq!(
+        token::get_balance(&mut client, &user.asset_accounts[0].pubkey()).await,
+        1_010 + redemption.asset_amounts[0]
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &user.basic_asset_account.pubkey()).await,
+        1_950 + redemption.basic_asset_amount
+    );
+    assert!(redemption.basic_asset_amount > 0);
+
+    // The basic asset vault accounts must follow a creation
+    let (pool_state, _) = fund.get_state(&mut client).await;
+    let user_accounts = UserAccounts {
+        fund_token_account: user.fund_token_account.pubkey(),
+        asset_accounts: vec![user.asset_accounts[0].pubkey()],
+        basic_asset_account: user.basic_asset_account.pubkey(),
+        authority: user.owner.pubkey(),
+    };
+    let mut instruction = fund_instruction::execute(
+        &program_id,
+        &fund.fund_account.pubkey(),
+        &pool_state,
+        &user_accounts,
+        &FeeAccounts::new(&pool_state, None),
+        PoolAction::Create(10),
+    )
+    .unwrap();
+    instruction.accounts.truncate(instruction.accounts.len() - 2);
+    let mut transaction = Transaction::new_with_payer(&[instruction.clone()], Some(&client.payer_pubkey()));
+    transaction.sign(&[client.payer(), &user.owner], client.recent_blockhash());
+    assert!(client.process_transaction(transaction).await.is_err());
+
+    // The user basic asset account is required while the fund holds basic asset
+    instruction
+        .accounts
+        .push(AccountMeta::new(fund.basic_asset_vault.pubkey(), false));
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&client.payer_pubkey()));
+    transaction.sign(&[client.payer(), &user.owner], client.recent_blockhash());
+    assert_eq!(
+        instruction_error(&client.process_transaction(transaction).await),
+        Some(InstructionError::NotEnoughAccountKeys)
+    );
+}
+
+#[tokio::test]
+async fn test_create_and_redeem_without_basic_asset_account() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    // The fund holds no basic asset
+    let fund = TestFund::create(&mut client, program_id, &[(0, 1_000)]).await;
+    let user = fund.create_user(&mut client, 2_000).await;
+    let user_accounts = UserAccounts {
+        fund_token_account: user.fund_token_account.pubkey(),
+        asset_accounts: vec![user.asset_accounts[0].pubkey()],
+        basic_asset_account: user.basic_asset_account.pubkey(),
+        authority: user.owner.pubkey(),
+    };
+
+    for &create in &[true, false] {
+        let (pool_state, _) = fund.get_state(&mut client).await;
+        let action = if create {
+            PoolAction::Create(100)
+        } else {
+            PoolAction::Redeem(token::get_balance(&mut client, &user.fund_token_account.pubkey()).await)
+        };
+        let mut instruction = fund_instruction::execute(
+            &program_id,
+            &fund.fund_account.pubkey(),
+            &pool_state,
+            &user_accounts,
+            &FeeAccounts::new(&pool_state, None),
+            action,
+        )
+        .unwrap();
+        instruction.accounts.pop();
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&client.payer_pubkey()));
+        transaction.sign(&[client.payer(), &user.owner], client.recent_blockhash());
+        client.process_transaction(transaction).await.unwrap();
+    }
+    assert_eq!(
+        token::get_balance(&mut client, &user.fund_token_account.pubkey()).await,
+        0
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_update_weights() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 30)]).await;
+    let swaps = vec![
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[0].pubkey(), 2, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+        )
+        .await,
+        TestSwap::create(
+            &mut client,
+            &fund.mint_authority,
+            (&fund.asset_mints[1].pubkey(), 0, 1_000_000),
+            (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 1_000_000),
+        )
+        .await,
+    ];
+    fund.approve_swaps(&mut client, &swaps).await;
+
+    // One weight per asset, not all of them zero
+    for asset_weights in [vec![1], vec![1, 1, 1], vec![0, 0]] {
+        assert!(fund
+            .process_admin_instruction(
+                &mut client,
+                FundInstructionInner::UpdateWeights { asset_weights },
+                vec![]
+            )
+            .await
+            .is_err());
+    }
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateWeights {
+            asset_weights: vec![2, 1],
+        },
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.asset_weights, vec![2, 1]);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        70
+    );
+
+    // The rebalance accounts trade the fund to the new weights, the whole of asset 0 is sold for about 349 of the
+    // basic asset spent on asset 1
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateWeights {
+            asset_weights: vec![0, 1],
+        },
+        fund.rebalance_accounts(&swaps),
+    )
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.asset_weights, vec![0, 1]);
+    assert_eq!(token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await, 0);
+    assert!(token::get_balance(&mut client, &fund.asset_vaults[1].pubkey()).await >= 370);
+}
+
+#[tokio::test]
+async fn test_update_basic_asset_weight() {
+    let (program_test, program_id) = program_test();
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(2, 70), (0, 30)]).await;
+
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateBasicAssetWeight { basic_asset_weight: 1 },
+        vec![],
+    )
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.basic_asset_weight, 1);
+}
+
+#[tokio::test]
+async fn test_update_asset_market() {
+    let (mut program_test, program_id) = program_test();
+    let dex_program_id = dex::add_dex_program(&mut program_test);
+    let mut client: TestClient = program_test.start().await.into();
+
+    let fund = TestFund::create(&mut client, program_id, &[(6, 1_000_000), (6, 1_000_000)]).await;
+    let market = TestMarket::create(
+        &mut client,
+        &dex_program_id,
+        &fund.asset_mints[0].pubkey(),
+        &fund.basic_asset_mint.pubkey(),
+    )
+    .await;
+    let open_orders = client.create_account(&dex_program_id, OPEN_ORDERS_LEN).await;
+
+    // The open orders account must be of the DEX program and not of another owner
+    let other_open_orders = client.create_account(&dex_program_id, OPEN_ORDERS_LEN).await;
+    let user = fund.create_user(&mut client, 1_000_000).await;
+    market
+        .place_order(
+            &mut client,
+            &dex_program_id,
+            &user.owner,
+            &other_open_orders.pubkey(),
+            &user.basic_asset_account.pubkey(),
+            Side::Bid,
+            1,
+            1,
+        )
+        .await
+        .unwrap();
+    let invalid_open_orders = vec![
+        other_open_orders.pubkey(),
+        client
+            .create_account(&dex_program_id, OPEN_ORDERS_LEN - 1)
+            .await
+            .pubkey(),
+        client.create_account(&program_id, OPEN_ORDERS_LEN).await.pubkey(),
+    ];
+    for invalid_open_orders in invalid_open_orders {
+        let result = fund
+            .process_admin_instruction(&mut client, FundInstructionInner::UpdateAssetMarket, vec![
+                AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+                AccountMeta::new_readonly(market.market.pubkey(), false),
+                AccountMeta::new_readonly(invalid_open_orders, false),
+                AccountMeta::new_readonly(dex_program_id, false),
+            ])
+            .await;
+        assert_eq!(instruction_error(&result), Some(InstructionError::InvalidAccountData));
+    }
+
+    fund.process_admin_instruction(&mut client, FundInstructionInner::UpdateAssetMarket, vec![
+        AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+        AccountMeta::new_readonly(market.market.pubkey(), false),
+        AccountMeta::new_readonly(open_orders.pubkey(), false),
+        AccountMeta::new_readonly(dex_program_id, false),
+    ])
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.asset_markets, vec![
+        Some(AssetMarket {
+            program_id: dex_program_id.into(),
+            market: market.market.pubkey().into(),
+            open_orders: open_orders.pubkey().into(),
+        }),
+        None
+    ]);
+
+    // The market must trade the asset
+    assert!(fund
+        .process_admin_instruction(&mut client, FundInstructionInner::UpdateAssetMarket, vec![
+            AccountMeta::new_readonly(fund.asset_vaults[1].pubkey(), false),
+            AccountMeta::new_readonly(market.market.pubkey(), false),
+            AccountMeta::new_readonly(open_orders.pubkey(), false),
+            AccountMeta::new_readonly(dex_program_id, false),
+        ])
+        .await
+        .is_err());
+
+    fund.process_admin_instruction(&mut client, FundInstructionInner::RemoveAssetMarket, vec![
+        AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+    ])
+    .await
+    .unwrap();
+
+    let (_, fund_state) = fund.get_state(&mut client).await;
+    assert_eq!(fund_state.asset_markets, vec![None, None]);
+}
+
+#[tokio::test]
+async fn test_rebalance_on_market() {
+    let (mut program_test, program_id) = program_test();
+    let dex_program_id = dex::add_dex_program(&mut program_test);
+    let mut client: TestClient = program_test.start().await.into();
+
+    // The fund holds 100 tokens of its only asset and 10_100 of the basic asset to buy more
+    let fund = TestFund::create(&mut client, program_id, &[(0, 100)]).await;
+    token::mint_to(
+        &mut client,
+        &fund.mint_authority,
+        &fund.basic_asset_mint.pubkey(),
+        &fund.basic_asset_vault.pubkey(),
+        10_100,
+        BASIC_ASSET_DECIMALS,
+    )
+    .await;
+    let market = TestMarket::create(
+        &mut client,
+        &dex_program_id,
+        &fund.asset_mints[0].pubkey(),
+        &fund.basic_asset_mint.pubkey(),
+    )
+    .await;
+    let open_orders = client.create_account(&dex_program_id, OPEN_ORDERS_LEN).await;
+    fund.process_admin_instruction(&mut client, FundInstructionInner::UpdateAssetMarket, vec![
+        AccountMeta::new_readonly(fund.asset_vaults[0].pubkey(), false),
+        AccountMeta::new_readonly(market.market.pubkey(), false),
+        AccountMeta::new_readonly(open_orders.pubkey(), false),
+        AccountMeta::new_readonly(dex_program_id, false),
+    ])
+    .await
+    .unwrap();
+
+    // A maker quotes the asset at 100 and 101 of the basic asset
+    let maker = fund.create_user(&mut client, 1_000_000).await;
+    let maker_open_orders = client.create_account(&dex_program_id, OPEN_ORDERS_LEN).await;
+    for (side, limit_price, payer) in [
+        (Side::Bid, 100, &maker.basic_asset_account),
+        (Side::Ask, 101, &maker.asset_accounts[0]),
+    ] {
+        market
+            .place_order(
+                &mut client,
+                &dex_program_id,
+                &maker.owner,
+                &maker_open_orders.pubkey(),
+                &payer.pubkey(),
+                side,
+                limit_price,
+                100,
+            )
+            .await
+            .unwrap();
+    }
+
+    let mut accounts = vec![
+        AccountMeta::new(fund.asset_vaults[0].pubkey(), false),
+        AccountMeta::new_readonly(fund.vault_authority, false),
+        AccountMeta::new(fund.basic_asset_vault.pubkey(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(spl_token_swap::id(), false),
+        AccountMeta::new_readonly(dex_program_id, false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+    ];
+    accounts.extend(market.venue_accounts(&open_orders.pubkey()));
+    accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+    fund.process_admin_instruction(&mut client, FundInstructionInner::Rebalance, accounts)
+        .await
+        .unwrap();
+
+    // 99 tokens are bought at the ask price for 9_999 and the taker fee of 22
+    assert_eq!(
+        token::get_balance(&mut client, &fund.asset_vaults[0].pubkey()).await,
+        199
+    );
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        79
+    );
+}
+
+#[tokio::test]
+async fn test_rebalance_planner() {
+    let (mut program_test, program_id) = program_test();
+    let dex_program_id = dex::add_dex_program(&mut program_test);
+    let mut client: TestClient = program_test.start().await.into();
+
+    // Asset 0 trades on a pool, asset 1 through an intermediate token, asset 2 on a market
+    let fund = TestFund::create(&mut client, program_id, &[(2, 7000), (0, 0), (0, 0)]).await;
+    let swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[0].pubkey(), 2, 10_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 50_000_000),
+    )
+    .await;
+    fund.approve_swaps(&mut client, std::slice::from_ref(&swap)).await;
+
+    let intermediate_mint = token::create_token(&mut client, &fund.mint_authority.pubkey(), 0).await;
+    let intermediate_vault =
+        token::create_account(&mut client, &fund.vault_authority, &intermediate_mint.pubkey()).await;
+    let first_hop_swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&fund.asset_mints[1].pubkey(), 0, 10_000_000),
+        (&intermediate_mint.pubkey(), 0, 20_000_000),
+    )
+    .await;
+    let second_hop_swap = TestSwap::create(
+        &mut client,
+        &fund.mint_authority,
+        (&intermediate_mint.pubkey(), 0, 10_000_000),
+        (&fund.basic_asset_mint.pubkey(), BASIC_ASSET_DECIMALS, 5_000_000),
+    )
+    .await;
+    fund.process_admin_instruction(
+        &mut client,
+        FundInstructionInner::UpdateAssetSwap {
+            with_intermediate: true,
+        },
+        vec![
+            AccountMeta::new_readonly(fund.asset_vaults[1].pubkey(), false),
+            AccountMeta::new_readonly(first_hop_swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+            AccountMeta::new_readonly(second_hop_swap.swap.pubkey(), false),
+            AccountMeta::new_readonly(intermediate_vault.pubkey(), false),
+        ],
+    )
+    .await
+    .unwrap();
+
+    let market = TestMarket::create(
+        &mut client,
+        &dex_program_id,
+        &fund.asset_mints[2].pubkey(),
+        &fund.basic_asset_mint.pubkey(),
+    )
+    .await;
+    let open_orders = client.create_account(&dex_program_id, OPEN_ORDERS_LEN).await;
+    fund.process_admin_instruction(&mut client, FundInstructionInner::UpdateAssetMarket, vec![
+        AccountMeta::new_readonly(fund.asset_vaults[2].pubkey(), false),
+        AccountMeta::new_readonly(market.market.pubkey(), false),
+        AccountMeta::new_readonly(open_orders.pubkey(), false),
+        AccountMeta::new_readonly(dex_program_id, false),
+    ])
+    .await
+    .unwrap();
+
+    // A maker quotes asset 2 at 99 and 101 of the basic asset, a mid price of 100
+    let maker = fund.create_user(&mut client, 1_000_000).await;
+    let maker_open_orders = client.create_account(&dex_program_id, OPEN_ORDERS_LEN).await;
+    for (side, limit_price, payer) in [
+        (Side::Bid, 99, &maker.basic_asset_account),
+        (Side::Ask, 101, &maker.asset_accounts[2]),
+    ] {
+        market
+            .place_order(
+                &mut client,
+                &dex_program_id,
+                &maker.owner,
+                &maker_open_orders.pubkey(),
+                &payer.pubkey(),
+                side,
+                limit_price,
+                200,
+            )
+            .await
+            .unwrap();
+    }
+
+    // The fund of 35_000 sells asset 0 for the two others
+    let planner = fund.rebalance_planner(&mut client).await;
+    assert_eq!(planner.nav(), 35_000);
+    assert_eq!(
+        planner
+            .planned_trades()
+            .unwrap()
+            .iter()
+            .map(|trade| trade.direction)
+            .collect::<Vec<_>>(),
+        vec![PendingTrade::Sell, PendingTrade::Buy, PendingTrade::Buy]
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[planner
+            .rebalance_instruction(&program_id, &client.payer_pubkey())
+            .unwrap()],
+        Some(&client.payer_pubkey()),
+    );
+    transaction.sign(&[client.payer()], client.recent_blockhash());
+    client.process_transaction(transaction).await.unwrap();
+
+    // 115 tokens of asset 2 are bought at the ask price with the taker fee
+    let mut balances = Vec::new();
+    for asset_vault in &fund.asset_vaults {
+        balances.push(token::get_balance(&mut client, &asset_vault.pubkey()).await);
+    }
+    assert_eq!(balances, vec![2334, 11_624, 115]);
+    assert_eq!(
+        token::get_balance(&mut client, &fund.basic_asset_vault.pubkey()).await,
+        12
+    );
 }
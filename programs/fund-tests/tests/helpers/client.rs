@@ -1,9 +1,10 @@
-use solana_program::{hash::Hash, pubkey::Pubkey, system_instruction};
+use solana_program::{hash::Hash, instruction::InstructionError, pubkey::Pubkey, system_instruction};
 use solana_program_test::BanksClient;
 use solana_sdk::{
+    account::Account,
     signature::{Keypair, Signer},
-    transaction::Transaction,
-    transport,
+    transaction::{Transaction, TransactionError},
+    transport::{self, TransportError},
 };
 
 pub struct TestClient {
@@ -34,6 +35,14 @@ impl TestClient {
         self.client.process_transaction(transaction).await
     }
 
+    pub async fn get_account(&mut self, address: &Pubkey) -> Account {
+        self.client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .unwrap_or_else(|| panic!("Account {} should exist", address))
+    }
+
     pub async fn create_account(&mut self, owner: &Pubkey, account_data_len: usize) -> Keypair {
         let account = Keypair::new();
 
@@ -62,3 +71,11 @@ impl From<(BanksClient, Keypair, Hash)> for TestClient {
         }
     }
 }
+
+/// Instruction error of a failed transaction, `None` for a success or another error.
+pub fn instruction_error(result: &transport::Result<()>) -> Option<InstructionError> {
+    match result {
+        Err(TransportError::TransactionError(TransactionError::InstructionError(_, err))) => Some(err.clone()),
+        _ => None,
+    }
+}
@@ -0,0 +1,180 @@
+use std::{mem::size_of, num::NonZeroU64};
+
+use serum_dex::{
+    instruction::SelfTradeBehavior,
+    matching::{OrderType, Side},
+    state::{gen_vault_signer_key, MarketState, State},
+};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, instruction::AccountMeta, program_error::ProgramError,
+    pubkey::Pubkey, sysvar,
+};
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+    transport,
+};
+
+use super::{client::TestClient, token};
+
+/// Padding of the DEX accounts data.
+const ACCOUNT_PADDING_LEN: usize = 12;
+const REQUEST_QUEUE_LEN: usize = 640;
+const EVENT_QUEUE_LEN: usize = 1 << 16;
+const ORDER_BOOK_LEN: usize = 1 << 16;
+
+/// Adds the DEX program, processing its instructions natively, returns its program ID.
+pub fn add_dex_program(program_test: &mut ProgramTest) -> Pubkey {
+    let dex_program_id = Keypair::new().pubkey();
+    program_test.add_program("serum_dex", dex_program_id, processor!(process_instruction));
+    dex_program_id
+}
+
+fn process_instruction(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> ProgramResult {
+    State::process(program_id, accounts, input).map_err(ProgramError::from)
+}
+
+/// Serum DEX market of the tests, with lot sizes of one token unit.
+pub struct TestMarket {
+    pub market: Keypair,
+    pub request_queue: Keypair,
+    pub event_queue: Keypair,
+    pub bids: Keypair,
+    pub asks: Keypair,
+    pub coin_vault: Keypair,
+    pub pc_vault: Keypair,
+    pub vault_signer: Pubkey,
+}
+
+impl TestMarket {
+    pub async fn create(
+        client: &mut TestClient,
+        dex_program_id: &Pubkey,
+        coin_mint: &Pubkey,
+        pc_mint: &Pubkey,
+    ) -> Self {
+        let market_len = size_of::<MarketState>() + ACCOUNT_PADDING_LEN;
+        let market = client.create_account(dex_program_id, market_len).await;
+        let request_queue = client
+            .create_account(dex_program_id, REQUEST_QUEUE_LEN + ACCOUNT_PADDING_LEN)
+            .await;
+        let event_queue = client
+            .create_account(dex_program_id, EVENT_QUEUE_LEN + ACCOUNT_PADDING_LEN)
+            .await;
+        let bids = client
+            .create_account(dex_program_id, ORDER_BOOK_LEN + ACCOUNT_PADDING_LEN)
+            .await;
+        let asks = client
+            .create_account(dex_program_id, ORDER_BOOK_LEN + ACCOUNT_PADDING_LEN)
+            .await;
+
+        let (vault_signer_nonce, vault_signer) = (0..)
+            .find_map(|nonce| {
+                gen_vault_signer_key(nonce, &market.pubkey(), dex_program_id)
+                    .ok()
+                    .map(|vault_signer| (nonce, vault_signer))
+            })
+            .unwrap();
+        let coin_vault = token::create_account(client, &vault_signer, coin_mint).await;
+        let pc_vault = token::create_account(client, &vault_signer, pc_mint).await;
+
+        let mut transaction = Transaction::new_with_payer(
+            &[serum_dex::instruction::initialize_market(
+                &market.pubkey(),
+                dex_program_id,
+                coin_mint,
+                pc_mint,
+                &coin_vault.pubkey(),
+                &pc_vault.pubkey(),
+                None,
+                None,
+                &bids.pubkey(),
+                &asks.pubkey(),
+                &request_queue.pubkey(),
+                &event_queue.pubkey(),
+                1,
+                1,
+                vault_signer_nonce,
+                1,
+            )
+            .unwrap()],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer()], client.recent_blockhash());
+        client.process_transaction(transaction).await.unwrap();
+
+        Self {
+            market,
+            request_queue,
+            event_queue,
+            bids,
+            asks,
+            coin_vault,
+            pc_vault,
+            vault_signer,
+        }
+    }
+
+    /// Places a limit order of the `owner` paid from its `payer` token account, resting on the order book.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn place_order(
+        &self,
+        client: &mut TestClient,
+        dex_program_id: &Pubkey,
+        owner: &Keypair,
+        open_orders: &Pubkey,
+        payer: &Pubkey,
+        side: Side,
+        limit_price: u64,
+        max_coin_qty: u64,
+    ) -> transport::Result<()> {
+        // Bids lock the quote amount of the order with the base taker fee of the DEX of 22 bps
+        let max_native_pc_qty = (limit_price * max_coin_qty * 10_022).div_ceil(10_000);
+        let mut transaction = Transaction::new_with_payer(
+            &[serum_dex::instruction::new_order(
+                &self.market.pubkey(),
+                open_orders,
+                &self.request_queue.pubkey(),
+                &self.event_queue.pubkey(),
+                &self.bids.pubkey(),
+                &self.asks.pubkey(),
+                payer,
+                &owner.pubkey(),
+                &self.coin_vault.pubkey(),
+                &self.pc_vault.pubkey(),
+                &spl_token::id(),
+                &sysvar::rent::id(),
+                None,
+                dex_program_id,
+                side,
+                NonZeroU64::new(limit_price).unwrap(),
+                NonZeroU64::new(max_coin_qty).unwrap(),
+                OrderType::Limit,
+                0,
+                SelfTradeBehavior::DecrementTake,
+                u16::MAX,
+                NonZeroU64::new(max_native_pc_qty).unwrap(),
+            )
+            .unwrap()],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer(), owner], client.recent_blockhash());
+        client.process_transaction(transaction).await
+    }
+
+    /// Venue accounts of the market for the fund `open_orders` account, as in `Rebalance`.
+    pub fn venue_accounts(&self, open_orders: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.market.pubkey(), false),
+            AccountMeta::new(*open_orders, false),
+            AccountMeta::new(self.request_queue.pubkey(), false),
+            AccountMeta::new(self.event_queue.pubkey(), false),
+            AccountMeta::new(self.bids.pubkey(), false),
+            AccountMeta::new(self.asks.pubkey(), false),
+            AccountMeta::new(self.coin_vault.pubkey(), false),
+            AccountMeta::new(self.pc_vault.pubkey(), false),
+            AccountMeta::new_readonly(self.vault_signer, false),
+        ]
+    }
+}
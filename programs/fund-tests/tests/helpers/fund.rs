@@ -0,0 +1,676 @@
+use borsh::{de::BorshDeserialize, ser::BorshSerialize};
+use fund::{
+    instruction::{swap_request, FundInstructionInner, FundRequest, InitializeFundData},
+    state::{self as fund_state, AssetSwap, FundState, FundStateContainer},
+};
+use fund_tests::{
+    instruction::{self as fund_instruction, FeeAccounts, UserAccounts},
+    planner::{self, Accounts, RebalancePlanner},
+};
+use serum_pool::schema::{
+    fee_owner::ID as POOL_FEE_OWNER_ID, InitializePoolRequest, PoolAction, PoolRequest, PoolRequestInner,
+    PoolRequestTag, PoolState,
+};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    sysvar,
+};
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+    transport,
+};
+
+use super::{client::TestClient, oracle, swap::TestSwap, token};
+
+/// Decimals of the basic asset of the test funds.
+pub const BASIC_ASSET_DECIMALS: u8 = 6;
+
+/// Test fund with its assets, the client payer is the fund admin.
+pub struct TestFund {
+    pub program_id: Pubkey,
+    pub fund_account: Keypair,
+    pub vault_authority: Pubkey,
+    pub fund_token_mint: Keypair,
+    pub fund_token_account: Keypair,
+    pub lqd_fee_account: Keypair,
+    pub initializer_fee_account: Keypair,
+    /// Mint authority of the assets.
+    pub mint_authority: Keypair,
+    pub asset_mints: Vec<Keypair>,
+    pub asset_decimals: Vec<u8>,
+    pub asset_vaults: Vec<Keypair>,
+    pub basic_asset_mint: Keypair,
+    pub basic_asset_vault: Keypair,
+}
+
+/// User of a test fund with its token accounts.
+pub struct TestUser {
+    pub owner: Keypair,
+    pub fund_token_account: Keypair,
+    pub asset_accounts: Vec<Keypair>,
+    pub basic_asset_account: Keypair,
+}
+
+impl TestFund {
+    /// Creates a fund holding the given `(decimals, amount)` assets, with equal asset weights.
+    pub async fn create(client: &mut TestClient, program_id: Pubkey, assets: &[(u8, u64)]) -> Self {
+        Self::create_with_capacity(client, program_id, assets, assets.len()).await
+    }
+
+    /// Creates a fund as `create` with room for `max_assets_count` assets in the fund account.
+    pub async fn create_with_capacity(
+        client: &mut TestClient,
+        program_id: Pubkey,
+        assets: &[(u8, u64)],
+        max_assets_count: usize,
+    ) -> Self {
+        let fund_name = "Test fund";
+        let mint_authority = Keypair::new();
+
+        // Create fund accounts
+        let fund_account_data_len = fund_state::calc_len(fund_name, max_assets_count);
+        let fund_account = client.create_account(&program_id, fund_account_data_len).await;
+        let (vault_authority, nonce) = Pubkey::find_program_address(&[fund_account.pubkey().as_ref()], &program_id);
+
+        // Create fund token
+        let fund_token_mint = token::create_token(client, &vault_authority, 0).await;
+        let fund_token_account = token::create_account(client, &vault_authority, &fund_token_mint.pubkey()).await;
+
+        // Create assets and transfer them to the fund
+        let mut asset_mints = Vec::with_capacity(assets.len());
+        let mut asset_vaults = Vec::with_capacity(assets.len());
+        for &(decimals, amount) in assets {
+            let asset_mint = token::create_token(client, &mint_authority.pubkey(), decimals).await;
+            let asset_vault = token::create_account(client, &vault_authority, &asset_mint.pubkey()).await;
+            if amount > 0 {
+                token::mint_to(
+                    client,
+                    &mint_authority,
+                    &asset_mint.pubkey(),
+                    &asset_vault.pubkey(),
+                    amount,
+                    decimals,
+                )
+                .await;
+            }
+            asset_mints.push(asset_mint);
+            asset_vaults.push(asset_vault);
+        }
+
+        let basic_asset_mint = token::create_token(client, &mint_authority.pubkey(), BASIC_ASSET_DECIMALS).await;
+        let basic_asset_vault = token::create_account(client, &vault_authority, &basic_asset_mint.pubkey()).await;
+
+        // Fees
+        let admin = client.payer_pubkey();
+        let initializer_fee_account = token::create_account(client, &admin, &fund_token_mint.pubkey()).await;
+        let lqd_fee_account = token::create_account(client, &POOL_FEE_OWNER_ID, &fund_token_mint.pubkey()).await;
+
+        // Create fund
+        let initialize_fund_request = InitializePoolRequest {
+            vault_signer_nonce: nonce,
+            assets_length: assets.len() as u8,
+            pool_name: fund_name.to_string(),
+            fee_rate: 1000,
+            custom_data: InitializeFundData {
+                rebalance_threshold_bps: 100,
+                max_slippage_bps: 100,
+                max_oracle_deviation_bps: 500,
+                asset_weights: vec![1; assets.len()],
+                asset_swaps: vec![
+                    AssetSwap {
+                        program_id: spl_token_swap::id().into(),
+                        swap: Pubkey::new_unique().into(),
+                        intermediate: None,
+                    };
+                    assets.len()
+                ],
+                fund_token_initial_supply: 100,
+            }
+            .try_to_vec()
+            .unwrap(),
+        };
+
+        let mut accounts = vec![
+            AccountMeta::new(fund_account.pubkey(), false),
+            AccountMeta::new(fund_token_mint.pubkey(), false),
+        ];
+        accounts.extend(
+            asset_vaults
+                .iter()
+                .map(|asset_vault| AccountMeta::new(asset_vault.pubkey(), false)),
+        );
+        accounts.extend(vec![
+            AccountMeta::new_readonly(vault_authority, false),
+            AccountMeta::new_readonly(lqd_fee_account.pubkey(), false),
+            AccountMeta::new_readonly(initializer_fee_account.pubkey(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new(admin, false),
+            AccountMeta::new(fund_token_account.pubkey(), false),
+            AccountMeta::new_readonly(basic_asset_vault.pubkey(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ]);
+
+        let mut transaction = Transaction::new_with_payer(
+            &[Instruction {
+                program_id,
+                accounts,
+                data: PoolRequest {
+                    tag: PoolRequestTag::default(),
+                    inner: PoolRequestInner::Initialize(initialize_fund_request),
+                }
+                .try_to_vec()
+                .unwrap(),
+            }],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer()], client.recent_blockhash());
+        client.process_transaction(transaction).await.unwrap();
+
+        Self {
+            program_id,
+            fund_account,
+            vault_authority,
+            fund_token_mint,
+            fund_token_account,
+            lqd_fee_account,
+            initializer_fee_account,
+            mint_authority,
+            asset_mints,
+            asset_decimals: assets.iter().map(|&(decimals, _)| decimals).collect(),
+            asset_vaults,
+            basic_asset_mint,
+            basic_asset_vault,
+        }
+    }
+
+    /// Creates a user holding `amount` tokens of each fund asset and of the basic asset.
+    pub async fn create_user(&self, client: &mut TestClient, amount: u64) -> TestUser {
+        let owner = Keypair::new();
+        let fund_token_account = token::create_account(client, &owner.pubkey(), &self.fund_token_mint.pubkey()).await;
+
+        let mut asset_accounts = Vec::with_capacity(self.asset_mints.len());
+        for (asset_mint, &decimals) in self.asset_mints.iter().zip(self.asset_decimals.iter()) {
+            let asset_account = token::create_account(client, &owner.pubkey(), &asset_mint.pubkey()).await;
+            token::mint_to(
+                client,
+                &self.mint_authority,
+                &asset_mint.pubkey(),
+                &asset_account.pubkey(),
+                amount,
+                decimals,
+            )
+            .await;
+            asset_accounts.push(asset_account);
+        }
+
+        let basic_asset_account = token::create_account(client, &owner.pubkey(), &self.basic_asset_mint.pubkey()).await;
+        token::mint_to(
+            client,
+            &self.mint_authority,
+            &self.basic_asset_mint.pubkey(),
+            &basic_asset_account.pubkey(),
+            amount,
+            BASIC_ASSET_DECIMALS,
+        )
+        .await;
+
+        TestUser {
+            owner,
+            fund_token_account,
+            asset_accounts,
+            basic_asset_account,
+        }
+    }
+
+    /// Creates or redeems fund tokens of the `user`, the initializer fee account receives the referrer fee without a
+    /// `referrer` fund token account.
+    pub async fn execute(
+        &self,
+        client: &mut TestClient,
+        user: &TestUser,
+        action: PoolAction,
+        referrer: Option<&Pubkey>,
+    ) -> transport::Result<()> {
+        self.execute_with_accounts(client, user, action, referrer, vec![]).await
+    }
+
+    /// Swaps the `inputs` of the `user` assets for the `outputs`, valued at the `price_accounts` of all the assets.
+    pub async fn swap(
+        &self,
+        client: &mut TestClient,
+        user: &TestUser,
+        price_accounts: &[Pubkey],
+        inputs: &[u64],
+        outputs: &[u64],
+    ) -> transport::Result<()> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.basic_asset_vault.pubkey(), false),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+        ];
+        accounts.extend(
+            price_accounts
+                .iter()
+                .map(|price_account| AccountMeta::new_readonly(*price_account, false)),
+        );
+        self.execute_with_accounts(
+            client,
+            user,
+            PoolAction::Swap(swap_request(inputs, outputs)),
+            None,
+            accounts,
+        )
+        .await
+    }
+
+    /// Executes the `action` as `execute` with the `additional_accounts` of the request.
+    async fn execute_with_accounts(
+        &self,
+        client: &mut TestClient,
+        user: &TestUser,
+        action: PoolAction,
+        referrer: Option<&Pubkey>,
+        additional_accounts: Vec<AccountMeta>,
+    ) -> transport::Result<()> {
+        let (pool_state, _) = self.get_state(client).await;
+        let user_accounts = UserAccounts {
+            fund_token_account: user.fund_token_account.pubkey(),
+            asset_accounts: user.asset_accounts.iter().map(Keypair::pubkey).collect(),
+            basic_asset_account: user.basic_asset_account.pubkey(),
+            authority: user.owner.pubkey(),
+        };
+        let fee_accounts = FeeAccounts::new(&pool_state, referrer);
+        let mut instruction = fund_instruction::execute(
+            &self.program_id,
+            &self.fund_account.pubkey(),
+            &pool_state,
+            &user_accounts,
+            &fee_accounts,
+            action,
+        )
+        .unwrap();
+        instruction.accounts.extend(additional_accounts);
+
+        let mut transaction = Transaction::new_with_payer(&[instruction], Some(&client.payer_pubkey()));
+        transaction.sign(&[client.payer(), &user.owner], client.recent_blockhash());
+        client.process_transaction(transaction).await
+    }
+
+    /// Buys the fund assets on their token-swap pools of `swaps` with `basic_asset_amount` of the `user` basic asset,
+    /// valued at the `price_accounts` of the assets with an oracle.
+    pub async fn zap_in(
+        &self,
+        client: &mut TestClient,
+        user: &TestUser,
+        swaps: &[TestSwap],
+        price_accounts: &[Pubkey],
+        basic_asset_amount: u64,
+        min_fund_tokens_out: u64,
+    ) -> transport::Result<()> {
+        let (pool_state, _) = self.get_state(client).await;
+        let fee_accounts = FeeAccounts::new(&pool_state, None);
+        let mut accounts = vec![
+            AccountMeta::new(self.fund_account.pubkey(), false),
+            AccountMeta::new(user.basic_asset_account.pubkey(), false),
+            AccountMeta::new(user.fund_token_account.pubkey(), false),
+            AccountMeta::new_readonly(user.owner.pubkey(), true),
+            AccountMeta::new(self.fund_token_mint.pubkey(), false),
+            AccountMeta::new(fee_accounts.lqd_fee_vault, false),
+            AccountMeta::new(fee_accounts.initializer_fee_vault, false),
+            AccountMeta::new(fee_accounts.referrer_fee_account, false),
+        ];
+        accounts.extend(self.rebalance_accounts_with_prices(swaps, price_accounts));
+
+        let mut transaction = Transaction::new_with_payer(
+            &[Instruction {
+                program_id: self.program_id,
+                accounts,
+                data: FundRequest {
+                    tag: Default::default(),
+                    inner: FundInstructionInner::ZapIn {
+                        basic_asset_amount,
+                        min_fund_tokens_out,
+                    },
+                }
+                .try_to_vec()
+                .unwrap(),
+            }],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer(), &user.owner], client.recent_blockhash());
+        client.process_transaction(transaction).await
+    }
+
+    /// Sells the redeemed share of the fund assets on their token-swap pools of `swaps` for `fund_token_amount` of the
+    /// `user` fund tokens, the initializer fee account receives the referrer fee without a `referrer` account.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn zap_out(
+        &self,
+        client: &mut TestClient,
+        user: &TestUser,
+        swaps: &[TestSwap],
+        price_accounts: &[Pubkey],
+        fund_token_amount: u64,
+        minimum_amount_out: u64,
+        referrer: Option<&Pubkey>,
+    ) -> transport::Result<()> {
+        let (pool_state, _) = self.get_state(client).await;
+        let fee_accounts = FeeAccounts::new(&pool_state, referrer);
+        let mut accounts = vec![
+            AccountMeta::new(self.fund_account.pubkey(), false),
+            AccountMeta::new(user.fund_token_account.pubkey(), false),
+            AccountMeta::new(user.basic_asset_account.pubkey(), false),
+            AccountMeta::new_readonly(user.owner.pubkey(), true),
+            AccountMeta::new(self.fund_token_mint.pubkey(), false),
+            AccountMeta::new(fee_accounts.lqd_fee_vault, false),
+            AccountMeta::new(fee_accounts.initializer_fee_vault, false),
+            AccountMeta::new(fee_accounts.referrer_fee_account, false),
+        ];
+        accounts.extend(self.rebalance_accounts_with_prices(swaps, price_accounts));
+
+        let mut transaction = Transaction::new_with_payer(
+            &[Instruction {
+                program_id: self.program_id,
+                accounts,
+                data: FundRequest {
+                    tag: Default::default(),
+                    inner: FundInstructionInner::ZapOut {
+                        fund_token_amount,
+                        minimum_amount_out,
+                    },
+                }
+                .try_to_vec()
+                .unwrap(),
+            }],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer(), &user.owner], client.recent_blockhash());
+        client.process_transaction(transaction).await
+    }
+
+    /// Approves the token-swap pool of each asset, in the `asset_vaults` order.
+    pub async fn approve_swaps(&self, client: &mut TestClient, swaps: &[TestSwap]) {
+        for (asset_vault, swap) in self.asset_vaults.iter().zip(swaps.iter()) {
+            self.process_admin_instruction(
+                client,
+                FundInstructionInner::UpdateAssetSwap {
+                    with_intermediate: false,
+                },
+                vec![
+                    AccountMeta::new_readonly(asset_vault.pubkey(), false),
+                    AccountMeta::new_readonly(swap.swap.pubkey(), false),
+                    AccountMeta::new_readonly(spl_token_swap::id(), false),
+                ],
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    /// Sets the oracle of the asset `index` to the `price_account` of the test oracle program.
+    pub async fn update_asset_oracle(
+        &self,
+        client: &mut TestClient,
+        index: usize,
+        price_account: &Pubkey,
+        max_price_age: u64,
+        max_confidence_bps: u16,
+    ) -> transport::Result<()> {
+        self.process_admin_instruction(
+            client,
+            FundInstructionInner::UpdateAssetOracle {
+                max_price_age,
+                max_confidence_bps,
+            },
+            vec![
+                AccountMeta::new_readonly(self.asset_vaults[index].pubkey(), false),
+                AccountMeta::new_readonly(self.asset_mints[index].pubkey(), false),
+                AccountMeta::new_readonly(self.basic_asset_mint.pubkey(), false),
+                AccountMeta::new_readonly(*price_account, false),
+                AccountMeta::new_readonly(oracle::id(), false),
+            ],
+        )
+        .await
+    }
+
+    /// `Rebalance` accounts after the admin account, trading each asset on its token-swap pool of `swaps`.
+    pub fn rebalance_accounts(&self, swaps: &[TestSwap]) -> Vec<AccountMeta> {
+        self.rebalance_accounts_with_prices(swaps, &[])
+    }
+
+    /// `Rebalance` accounts as `rebalance_accounts`, with the `price_accounts` of the assets with an oracle.
+    pub fn rebalance_accounts_with_prices(&self, swaps: &[TestSwap], price_accounts: &[Pubkey]) -> Vec<AccountMeta> {
+        let mut accounts: Vec<_> = self
+            .asset_vaults
+            .iter()
+            .map(|asset_vault| AccountMeta::new(asset_vault.pubkey(), false))
+            .collect();
+        accounts.extend(vec![
+            AccountMeta::new_readonly(self.vault_authority, false),
+            AccountMeta::new(self.basic_asset_vault.pubkey(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+        ]);
+        accounts.extend(swaps.iter().flat_map(swap_accounts));
+        accounts.extend(
+            price_accounts
+                .iter()
+                .map(|price_account| AccountMeta::new_readonly(*price_account, false)),
+        );
+        accounts.push(AccountMeta::new_readonly(sysvar::clock::id(), false));
+        accounts
+    }
+
+    /// `StartRebalance` accounts after the admin account, quoting each asset on its token-swap pool of `swaps`.
+    pub fn start_rebalance_accounts(&self, swaps: &[TestSwap]) -> Vec<AccountMeta> {
+        self.start_rebalance_accounts_with_prices(swaps, &[])
+    }
+
+    /// `StartRebalance` accounts as `start_rebalance_accounts`, with the `price_accounts` of the first assets, each
+    /// after the venue accounts of its asset.
+    pub fn start_rebalance_accounts_with_prices(
+        &self,
+        swaps: &[TestSwap],
+        price_accounts: &[Pubkey],
+    ) -> Vec<AccountMeta> {
+        let mut accounts = vec![AccountMeta::new_readonly(sysvar::clock::id(), false)];
+        accounts.extend(
+            self.asset_vaults
+                .iter()
+                .map(|asset_vault| AccountMeta::new_readonly(asset_vault.pubkey(), false)),
+        );
+        accounts.push(AccountMeta::new_readonly(self.basic_asset_vault.pubkey(), false));
+        for (i, swap) in swaps.iter().enumerate() {
+            accounts.extend(vec![
+                AccountMeta::new_readonly(swap.swap.pubkey(), false),
+                AccountMeta::new_readonly(swap.token_a.pubkey(), false),
+                AccountMeta::new_readonly(swap.token_b.pubkey(), false),
+            ]);
+            if let Some(price_account) = price_accounts.get(i) {
+                accounts.push(AccountMeta::new_readonly(*price_account, false));
+            }
+        }
+        accounts
+    }
+
+    /// `RebalanceAssets` accounts after the admin account for the assets `indices`, trading each asset on its
+    /// token-swap pool of `swaps`.
+    pub fn rebalance_assets_accounts(&self, swaps: &[TestSwap], indices: &[usize]) -> Vec<AccountMeta> {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.vault_authority, false),
+            AccountMeta::new(self.basic_asset_vault.pubkey(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(spl_token_swap::id(), false),
+        ];
+        for &i in indices {
+            accounts.push(AccountMeta::new(self.asset_vaults[i].pubkey(), false));
+            accounts.extend(swap_accounts(&swaps[i]));
+        }
+        accounts
+    }
+
+    /// Moves the fund to a new account with room for `max_assets_count` assets by `MigrateFund`, handing over the
+    /// vaults, the `intermediate_vaults` of the two-hop routes and the initial supply fund token account.
+    pub async fn migrate(
+        &mut self,
+        client: &mut TestClient,
+        max_assets_count: usize,
+        intermediate_vaults: &[Pubkey],
+    ) -> transport::Result<()> {
+        let (pool_state, _) = self.get_state(client).await;
+        let new_fund_account = client
+            .create_account(
+                &self.program_id,
+                fund_state::calc_len(pool_state.name.as_str(), max_assets_count),
+            )
+            .await;
+        let (vault_authority, nonce) =
+            Pubkey::find_program_address(&[new_fund_account.pubkey().as_ref()], &self.program_id);
+
+        let mut accounts = vec![
+            AccountMeta::new(self.fund_account.pubkey(), false),
+            AccountMeta::new(client.payer_pubkey(), true),
+            AccountMeta::new(new_fund_account.pubkey(), false),
+            AccountMeta::new_readonly(self.vault_authority, false),
+            AccountMeta::new(self.fund_token_mint.pubkey(), false),
+        ];
+        accounts.extend(
+            pool_state
+                .assets
+                .iter()
+                .map(|asset| AccountMeta::new(asset.vault_address.pubkey(), false)),
+        );
+        accounts.push(AccountMeta::new(self.basic_asset_vault.pubkey(), false));
+        accounts.extend(intermediate_vaults.iter().map(|vault| AccountMeta::new(*vault, false)));
+        accounts.extend(vec![
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(self.fund_token_account.pubkey(), false),
+        ]);
+
+        let mut transaction = Transaction::new_with_payer(
+            &[Instruction {
+                program_id: self.program_id,
+                accounts,
+                data: FundRequest {
+                    tag: Default::default(),
+                    inner: FundInstructionInner::MigrateFund {
+                        vault_signer_nonce: nonce,
+                    },
+                }
+                .try_to_vec()
+                .unwrap(),
+            }],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer()], client.recent_blockhash());
+        client.process_transaction(transaction).await?;
+
+        self.fund_account = new_fund_account;
+        self.vault_authority = vault_authority;
+        Ok(())
+    }
+
+    /// Sends a fund admin instruction, `accounts` follow the pool and the admin accounts.
+    pub async fn process_admin_instruction(
+        &self,
+        client: &mut TestClient,
+        instruction: FundInstructionInner,
+        accounts: Vec<AccountMeta>,
+    ) -> transport::Result<()> {
+        let mut instruction_accounts = vec![
+            AccountMeta::new(self.fund_account.pubkey(), false),
+            AccountMeta::new_readonly(client.payer_pubkey(), true),
+        ];
+        instruction_accounts.extend(accounts);
+
+        let mut transaction = Transaction::new_with_payer(
+            &[Instruction {
+                program_id: self.program_id,
+                accounts: instruction_accounts,
+                data: FundRequest {
+                    tag: Default::default(),
+                    inner: instruction,
+                }
+                .try_to_vec()
+                .unwrap(),
+            }],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer()], client.recent_blockhash());
+        client.process_transaction(transaction).await
+    }
+
+    /// Fund instruction without the admin signature, `accounts` follow the pool account.
+    pub fn keeper_instruction(&self, instruction: FundInstructionInner, accounts: Vec<AccountMeta>) -> Instruction {
+        let mut instruction_accounts = vec![AccountMeta::new(self.fund_account.pubkey(), false)];
+        instruction_accounts.extend(accounts);
+
+        Instruction {
+            program_id: self.program_id,
+            accounts: instruction_accounts,
+            data: FundRequest {
+                tag: Default::default(),
+                inner: instruction,
+            }
+            .try_to_vec()
+            .unwrap(),
+        }
+    }
+
+    /// Sends a fund instruction without the admin signature, `accounts` follow the pool account.
+    pub async fn process_keeper_instruction(
+        &self,
+        client: &mut TestClient,
+        instruction: FundInstructionInner,
+        accounts: Vec<AccountMeta>,
+    ) -> transport::Result<()> {
+        let mut transaction = Transaction::new_with_payer(
+            &[self.keeper_instruction(instruction, accounts)],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer()], client.recent_blockhash());
+        client.process_transaction(transaction).await
+    }
+
+    /// Plans a rebalance from the fund accounts fetched as by `FundClient::get_rebalance_planner`.
+    pub async fn rebalance_planner(&self, client: &mut TestClient) -> RebalancePlanner {
+        let (pool_state, fund_state) = self.get_state(client).await;
+
+        let mut accounts = Accounts::new();
+        for address in planner::fund_accounts(&pool_state, &fund_state) {
+            accounts.insert(address, client.get_account(&address).await);
+        }
+        for address in planner::referenced_accounts(&fund_state, &accounts).unwrap() {
+            accounts.insert(address, client.get_account(&address).await);
+        }
+        RebalancePlanner::new(self.fund_account.pubkey(), pool_state, fund_state, &accounts).unwrap()
+    }
+
+    pub async fn get_state(&self, client: &mut TestClient) -> (PoolState, FundState) {
+        let account = client
+            .client
+            .get_account(self.fund_account.pubkey())
+            .await
+            .unwrap()
+            .expect("Fund account should exist");
+
+        let mut data = account.data.as_slice();
+        let pool_state: PoolState = BorshDeserialize::deserialize(&mut data).unwrap();
+        let fund_state = pool_state.read_fund_state().unwrap();
+        (pool_state, fund_state)
+    }
+}
+
+/// Venue accounts of an asset traded on the token-swap pool `swap`, as in `Rebalance`.
+pub fn swap_accounts(swap: &TestSwap) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(swap.swap.pubkey(), false),
+        AccountMeta::new_readonly(swap.authority, false),
+        AccountMeta::new(swap.token_a.pubkey(), false),
+        AccountMeta::new(swap.token_b.pubkey(), false),
+        AccountMeta::new(swap.pool_token_mint.pubkey(), false),
+        AccountMeta::new(swap.fee.pubkey(), false),
+    ]
+}
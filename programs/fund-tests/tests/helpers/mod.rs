@@ -1,2 +1,10 @@
+// Every test file uses its own part of the helpers
+#![allow(dead_code)]
+
 pub mod client;
+pub mod dex;
+pub mod fund;
+pub mod oracle;
+pub mod retbuf;
+pub mod swap;
 pub mod token;
@@ -0,0 +1,27 @@
+use fund::oracle::{Price, PRICE_STATUS_TRADING};
+use solana_program::pubkey::Pubkey;
+use solana_program_test::ProgramTest;
+use solana_sdk::account::Account;
+
+// Program owning the price accounts of the tests, the Pyth program ID
+solana_program::declare_id!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+
+/// Adds a Pyth-style price account, a stand-in for the oracle program accounts in the tests.
+pub fn add_price_account(program_test: &mut ProgramTest, price: i64, expo: i32) -> Pubkey {
+    add_price(program_test, Price {
+        expo,
+        price,
+        conf: 0,
+        status: PRICE_STATUS_TRADING,
+        pub_slot: 0,
+    })
+}
+
+/// Adds a price account of the aggregate `price`, owned by the oracle program `id()`.
+pub fn add_price(program_test: &mut ProgramTest, price: Price) -> Pubkey {
+    let price_account = Pubkey::new_unique();
+    let mut account = Account::new(u32::MAX as u64, Price::LEN, &id());
+    price.pack(&mut account.data).unwrap();
+    program_test.add_account(price_account, account);
+    price_account
+}
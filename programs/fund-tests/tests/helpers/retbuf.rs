@@ -0,0 +1,40 @@
+use std::convert::TryInto;
+
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey,
+};
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+
+/// Adds a stand-in for the retbuf program and a retbuf account of `len` bytes owned by it, returns the program ID and
+/// the account.
+pub fn add_retbuf(program_test: &mut ProgramTest, len: usize) -> (Pubkey, Pubkey) {
+    let program_id = Keypair::new().pubkey();
+    program_test.add_program("retbuf", program_id, processor!(process_instruction));
+
+    let retbuf_account = Pubkey::new_unique();
+    program_test.add_account(retbuf_account, Account::new(u32::MAX as u64, len, &program_id));
+    (program_id, retbuf_account)
+}
+
+/// Writes the instruction data after its first 8 bytes to the retbuf account, at the little-endian offset of these
+/// bytes, as the retbuf program.
+fn process_instruction(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let retbuf_account = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if data.len() < 8 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let (offset, bytes) = data.split_at(8);
+    let offset = u64::from_le_bytes(offset.try_into().unwrap()) as usize;
+
+    let mut retbuf_data = retbuf_account.try_borrow_mut_data()?;
+    let end = offset + bytes.len();
+    if end > retbuf_data.len() {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    retbuf_data[offset..end].copy_from_slice(bytes);
+    Ok(())
+}
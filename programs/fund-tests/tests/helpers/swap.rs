@@ -0,0 +1,120 @@
+use solana_program::pubkey::Pubkey;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use spl_token_swap::{
+    curve::{base::SwapCurve, fees::Fees},
+    instruction::Swap,
+    state::SwapVersion,
+};
+
+use super::{client::TestClient, token};
+
+/// Token-swap pool of the tests with a constant product curve and no fees, the program is added to the test by
+/// `spl_token_swap::processor::Processor::process`.
+pub struct TestSwap {
+    pub swap: Keypair,
+    pub authority: Pubkey,
+    pub token_a: Keypair,
+    pub token_b: Keypair,
+    pub pool_token_mint: Keypair,
+    pub fee: Keypair,
+}
+
+impl TestSwap {
+    /// Creates a pool of the given `(mint, decimals, amount)` reserves, minted by `mint_authority`.
+    pub async fn create(
+        client: &mut TestClient,
+        mint_authority: &Keypair,
+        (mint_a, decimals_a, amount_a): (&Pubkey, u8, u64),
+        (mint_b, decimals_b, amount_b): (&Pubkey, u8, u64),
+    ) -> Self {
+        let swap = client
+            .create_account(&spl_token_swap::id(), SwapVersion::LATEST_LEN)
+            .await;
+        let (authority, nonce) = Pubkey::find_program_address(&[swap.pubkey().as_ref()], &spl_token_swap::id());
+
+        let token_a = token::create_account(client, &authority, mint_a).await;
+        token::mint_to(client, mint_authority, mint_a, &token_a.pubkey(), amount_a, decimals_a).await;
+        let token_b = token::create_account(client, &authority, mint_b).await;
+        token::mint_to(client, mint_authority, mint_b, &token_b.pubkey(), amount_b, decimals_b).await;
+
+        let pool_token_mint = token::create_token(client, &authority, 2).await;
+        let payer = client.payer_pubkey();
+        let fee = token::create_account(client, &payer, &pool_token_mint.pubkey()).await;
+        let destination = token::create_account(client, &payer, &pool_token_mint.pubkey()).await;
+
+        let mut transaction = Transaction::new_with_payer(
+            &[spl_token_swap::instruction::initialize(
+                &spl_token_swap::id(),
+                &spl_token::id(),
+                &swap.pubkey(),
+                &authority,
+                &token_a.pubkey(),
+                &token_b.pubkey(),
+                &pool_token_mint.pubkey(),
+                &fee.pubkey(),
+                &destination.pubkey(),
+                nonce,
+                Fees::default(),
+                SwapCurve::default(),
+            )
+            .unwrap()],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer(), &swap], client.recent_blockhash());
+        client.process_transaction(transaction).await.unwrap();
+
+        Self {
+            swap,
+            authority,
+            token_a,
+            token_b,
+            pool_token_mint,
+            fee,
+        }
+    }
+
+    /// Swaps `amount_in` tokens of the `source` account of the `owner` for the tokens of the `destination` account,
+    /// from token A to token B if `a_to_b`, otherwise from token B to token A.
+    pub async fn swap(
+        &self,
+        client: &mut TestClient,
+        owner: &Keypair,
+        source: &Pubkey,
+        destination: &Pubkey,
+        a_to_b: bool,
+        amount_in: u64,
+    ) {
+        let (swap_source, swap_destination) = if a_to_b {
+            (self.token_a.pubkey(), self.token_b.pubkey())
+        } else {
+            (self.token_b.pubkey(), self.token_a.pubkey())
+        };
+        let mut transaction = Transaction::new_with_payer(
+            &[spl_token_swap::instruction::swap(
+                &spl_token_swap::id(),
+                &spl_token::id(),
+                &self.swap.pubkey(),
+                &self.authority,
+                &owner.pubkey(),
+                source,
+                &swap_source,
+                &swap_destination,
+                destination,
+                &self.pool_token_mint.pubkey(),
+                &self.fee.pubkey(),
+                None,
+                Swap {
+                    amount_in,
+                    minimum_amount_out: 0,
+                },
+            )
+            .unwrap()],
+            Some(&client.payer_pubkey()),
+        );
+        transaction.sign(&[client.payer(), owner], client.recent_blockhash());
+        client.process_transaction(transaction).await.unwrap();
+    }
+}
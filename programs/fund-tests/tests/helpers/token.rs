@@ -72,7 +72,7 @@ pub async fn mint_to(
         .unwrap()],
         Some(&client.payer_pubkey()),
     );
-    transaction.sign(&[client.payer(), &owner], client.recent_blockhash());
+    transaction.sign(&[client.payer(), owner], client.recent_blockhash());
     client.process_transaction(transaction).await.unwrap();
 }
 
@@ -99,6 +99,26 @@ pub async fn transfer_to(
         .unwrap()],
         Some(&client.payer_pubkey()),
     );
-    transaction.sign(&[client.payer(), &owner], client.recent_blockhash());
+    transaction.sign(&[client.payer(), owner], client.recent_blockhash());
     client.process_transaction(transaction).await.unwrap();
 }
+
+pub async fn get_balance(client: &mut TestClient, account: &Pubkey) -> u64 {
+    let account = client
+        .client
+        .get_account(*account)
+        .await
+        .unwrap()
+        .expect("Token account should exist");
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+pub async fn get_supply(client: &mut TestClient, token_mint: &Pubkey) -> u64 {
+    let account = client
+        .client
+        .get_account(*token_mint)
+        .await
+        .unwrap()
+        .expect("Token mint should exist");
+    Mint::unpack(&account.data).unwrap().supply
+}
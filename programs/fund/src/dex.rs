@@ -0,0 +1,483 @@
+use std::{mem::size_of, num::NonZeroU64};
+
+use serum_dex::{
+    critbit::SlabView,
+    error::DexError,
+    instruction::SelfTradeBehavior,
+    matching::{OrderType, Side},
+    state::{MarketState, OpenOrders, ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING},
+};
+use serum_pool::context::check_account_address;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    error::FundError,
+    price::ExchangeRate,
+    processor::parse_token_account,
+    state::{AssetMarket, FundState},
+    venue::{check_amount_out, SwapContext, SwapVenue, VenuePrograms},
+};
+
+/// Maximum number of resting orders matched by an order of the fund, bounds the compute units of the order.
+pub const ORDER_MATCH_LIMIT: u16 = 16;
+
+/// Length of an open orders account of the DEX program, with the padding of the DEX accounts.
+pub const OPEN_ORDERS_LEN: usize = ACCOUNT_HEAD_PADDING.len() + size_of::<OpenOrders>() + ACCOUNT_TAIL_PADDING.len();
+
+/// Base and quote token mints of a Serum DEX market.
+pub fn market_mints(market: &AccountInfo, dex_program_id: &Pubkey) -> Result<(Pubkey, Pubkey), ProgramError> {
+    let market_state = load_market(market, dex_program_id)?;
+    let (coin_mint, pc_mint) = (market_state.coin_mint, market_state.pc_mint);
+    Ok((dex_pubkey(coin_mint), dex_pubkey(pc_mint)))
+}
+
+/// Base and quote lot sizes of a Serum DEX market.
+pub fn lot_sizes(market: &AccountInfo, dex_program_id: &Pubkey) -> Result<(u64, u64), ProgramError> {
+    let market_state = load_market(market, dex_program_id)?;
+    Ok((market_state.coin_lot_size, market_state.pc_lot_size))
+}
+
+/// Bids and asks accounts of a Serum DEX market.
+pub fn order_book(market: &AccountInfo, dex_program_id: &Pubkey) -> Result<(Pubkey, Pubkey), ProgramError> {
+    let market_state = load_market(market, dex_program_id)?;
+    let (bids, asks) = (market_state.bids, market_state.asks);
+    Ok((dex_pubkey(bids), dex_pubkey(asks)))
+}
+
+/// Mid price of the best bid and the best ask of a Serum DEX market, as the rate of the base token to the quote token.
+pub fn book_rate(
+    market: &AccountInfo,
+    bids: &AccountInfo,
+    asks: &AccountInfo,
+    dex_program_id: &Pubkey,
+) -> Result<ExchangeRate, ProgramError> {
+    let market_state = load_market(market, dex_program_id)?;
+    let load_error = |err: DexError| {
+        msg!("Load Serum DEX order book error: {:?}", err);
+        ProgramError::InvalidAccountData
+    };
+    let bids = market_state.load_bids_mut(bids).map_err(load_error)?;
+    let asks = market_state.load_asks_mut(asks).map_err(load_error)?;
+    let best_bid = bids
+        .find_max()
+        .and_then(|handle| bids.get(handle))
+        .and_then(|node| node.as_leaf());
+    let best_ask = asks
+        .find_min()
+        .and_then(|handle| asks.get(handle))
+        .and_then(|node| node.as_leaf());
+    let (best_bid, best_ask) = match (best_bid, best_ask) {
+        (Some(best_bid), Some(best_ask)) => (best_bid.price().get(), best_ask.price().get()),
+        _ => {
+            msg!("Serum DEX order book is empty");
+            return Err(ProgramError::InvalidAccountData);
+        },
+    };
+
+    // In quote lots per base lot, then scaled to the token amounts
+    let mid_rate = ExchangeRate {
+        asset: 2,
+        basic_asset: best_bid as u128 + best_ask as u128,
+    };
+    mid_rate.then(&ExchangeRate {
+        asset: market_state.coin_lot_size as u128,
+        basic_asset: market_state.pc_lot_size as u128,
+    })
+}
+
+fn load_market<'a>(
+    market: &'a AccountInfo,
+    dex_program_id: &Pubkey,
+) -> Result<std::cell::RefMut<'a, MarketState>, ProgramError> {
+    MarketState::load(market, dex_program_id).map_err(|err| {
+        msg!("Load Serum DEX market error: {:?}", err);
+        ProgramError::InvalidAccountData
+    })
+}
+
+/// Checks that the `open_orders` account of the DEX program is for the `market` and the `owner`. An account not yet
+/// initialized is assigned to the market and to the owner of the first order by the DEX.
+pub fn check_open_orders(
+    open_orders: &AccountInfo,
+    market: &Pubkey,
+    owner: &Pubkey,
+    dex_program_id: &Pubkey,
+) -> ProgramResult {
+    if open_orders.owner != dex_program_id {
+        msg!("Open orders account not owned by Serum DEX program");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let data = open_orders.try_borrow_data()?;
+    if data.len() != OPEN_ORDERS_LEN {
+        msg!("Open orders account must be {} bytes long", OPEN_ORDERS_LEN);
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    // The account flags, the market and the owner lead the open orders after the head padding
+    let open_orders_data = &data[ACCOUNT_HEAD_PADDING.len()..];
+    let (account_flags, open_orders_data) = open_orders_data.split_at(size_of::<u64>());
+    let (open_orders_market, open_orders_data) = open_orders_data.split_at(size_of::<Pubkey>());
+    let open_orders_owner = &open_orders_data[..size_of::<Pubkey>()];
+    if account_flags.iter().all(|&byte| byte == 0) {
+        return Ok(());
+    }
+    if open_orders_market != market.as_ref() || open_orders_owner != owner.as_ref() {
+        msg!("Open orders account of another market or owner");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Public key stored by the DEX as 64-bit words.
+pub fn dex_pubkey(words: [u64; 4]) -> Pubkey {
+    let mut bytes = [0; 32];
+    for (chunk, word) in bytes.chunks_mut(8).zip(words.iter()) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    Pubkey::new(&bytes)
+}
+
+/// Serum DEX market accounts of an asset, the base token, and the basic asset, the quote token.
+pub struct MarketAccounts<'a, 'b> {
+    pub market: &'a AccountInfo<'b>,
+    pub open_orders: &'a AccountInfo<'b>,
+    pub request_queue: &'a AccountInfo<'b>,
+    pub event_queue: &'a AccountInfo<'b>,
+    pub bids: &'a AccountInfo<'b>,
+    pub asks: &'a AccountInfo<'b>,
+    pub coin_vault: &'a AccountInfo<'b>,
+    pub pc_vault: &'a AccountInfo<'b>,
+    pub vault_signer: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> MarketAccounts<'a, 'b> {
+    pub fn next(accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>) -> Result<Self, ProgramError> {
+        Ok(Self {
+            market: next_account_info(accounts_iter)?,
+            open_orders: next_account_info(accounts_iter)?,
+            request_queue: next_account_info(accounts_iter)?,
+            event_queue: next_account_info(accounts_iter)?,
+            bids: next_account_info(accounts_iter)?,
+            asks: next_account_info(accounts_iter)?,
+            coin_vault: next_account_info(accounts_iter)?,
+            pc_vault: next_account_info(accounts_iter)?,
+            vault_signer: next_account_info(accounts_iter)?,
+        })
+    }
+
+    /// Checks the accounts against the approved market of the asset, the DEX program checks the other market accounts.
+    pub fn check(&self, asset_market: &AssetMarket, dex_program: &DexProgram) -> ProgramResult {
+        if dex_program.program.key != asset_market.program_id.as_ref() {
+            msg!(
+                "Serum DEX program {} not approved for the asset",
+                dex_program.program.key
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        check_account_address(self.market, &asset_market.market, stringify!(market))?;
+        check_account_address(self.open_orders, &asset_market.open_orders, stringify!(open_orders))
+    }
+}
+
+/// Serum DEX program accounts of a fund instruction.
+#[derive(Clone, Copy)]
+pub struct DexProgram<'a, 'b> {
+    pub program: &'a AccountInfo<'b>,
+    pub rent: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> DexProgram<'a, 'b> {
+    pub fn next(accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>) -> Result<Self, ProgramError> {
+        Ok(Self {
+            program: next_account_info(accounts_iter)?,
+            rent: next_account_info(accounts_iter)?,
+        })
+    }
+}
+
+/// Accounts shared by the Serum DEX orders of a fund instruction, and the fund accounts placing the orders.
+pub struct DexContext<'a, 'b> {
+    pub dex_program: DexProgram<'a, 'b>,
+    pub spl_token_program: &'a AccountInfo<'b>,
+    /// Owner of the fund open orders accounts and of the fund vaults.
+    pub owner: &'a AccountInfo<'b>,
+    pub owner_seeds: &'a [&'a [u8]],
+}
+
+impl<'a, 'b> DexContext<'a, 'b> {
+    /// Places an immediate-or-cancel order paid from the `payer` fund vault, the unmatched part is cancelled.
+    pub fn new_order(
+        &self,
+        market: &MarketAccounts<'_, 'b>,
+        payer: &AccountInfo<'b>,
+        side: Side,
+        limit_price: u64,
+        max_coin_qty: u64,
+        max_native_pc_qty_including_fees: u64,
+    ) -> ProgramResult {
+        let (limit_price, max_coin_qty, max_native_pc_qty_including_fees) = match (
+            NonZeroU64::new(limit_price),
+            NonZeroU64::new(max_coin_qty),
+            NonZeroU64::new(max_native_pc_qty_including_fees),
+        ) {
+            (Some(limit_price), Some(max_coin_qty), Some(max_native_pc_qty_including_fees)) => {
+                (limit_price, max_coin_qty, max_native_pc_qty_including_fees)
+            },
+            _ => {
+                msg!("Order price or size is zero");
+                return Err(ProgramError::InvalidArgument);
+            },
+        };
+
+        let instruction = serum_dex::instruction::new_order(
+            market.market.key,
+            market.open_orders.key,
+            market.request_queue.key,
+            market.event_queue.key,
+            market.bids.key,
+            market.asks.key,
+            payer.key,
+            self.owner.key,
+            market.coin_vault.key,
+            market.pc_vault.key,
+            self.spl_token_program.key,
+            self.dex_program.rent.key,
+            None,
+            self.dex_program.program.key,
+            side,
+            limit_price,
+            max_coin_qty,
+            OrderType::ImmediateOrCancel,
+            0,
+            SelfTradeBehavior::DecrementTake,
+            ORDER_MATCH_LIMIT,
+            max_native_pc_qty_including_fees,
+        )
+        .map_err(|err| {
+            msg!("Create new order instruction error: {:?}", err);
+            ProgramError::InvalidArgument
+        })?;
+
+        let account_infos = vec![
+            market.market.clone(),
+            market.open_orders.clone(),
+            market.request_queue.clone(),
+            market.event_queue.clone(),
+            market.bids.clone(),
+            market.asks.clone(),
+            payer.clone(),
+            self.owner.clone(),
+            market.coin_vault.clone(),
+            market.pc_vault.clone(),
+            self.spl_token_program.clone(),
+            self.dex_program.rent.clone(),
+            self.dex_program.program.clone(),
+        ];
+
+        invoke_signed(&instruction, &account_infos, &[self.owner_seeds])
+    }
+
+    /// Moves the filled amounts of the fund open orders to the fund vaults of the asset and of the basic asset.
+    pub fn settle_funds(
+        &self,
+        market: &MarketAccounts<'_, 'b>,
+        asset_vault: &AccountInfo<'b>,
+        basic_asset_vault: &AccountInfo<'b>,
+    ) -> ProgramResult {
+        let instruction = serum_dex::instruction::settle_funds(
+            self.dex_program.program.key,
+            market.market.key,
+            self.spl_token_program.key,
+            market.open_orders.key,
+            self.owner.key,
+            market.coin_vault.key,
+            asset_vault.key,
+            market.pc_vault.key,
+            basic_asset_vault.key,
+            None,
+            market.vault_signer.key,
+        )
+        .map_err(|err| {
+            msg!("Create settle funds instruction error: {:?}", err);
+            ProgramError::InvalidArgument
+        })?;
+
+        let account_infos = vec![
+            market.market.clone(),
+            market.open_orders.clone(),
+            self.owner.clone(),
+            market.coin_vault.clone(),
+            market.pc_vault.clone(),
+            asset_vault.clone(),
+            basic_asset_vault.clone(),
+            market.vault_signer.clone(),
+            self.spl_token_program.clone(),
+            self.dex_program.program.clone(),
+        ];
+
+        invoke_signed(&instruction, &account_infos, &[self.owner_seeds])
+    }
+}
+
+/// Approved Serum DEX market of the asset `index`.
+fn asset_market(fund_state: &FundState, index: usize) -> Result<&AssetMarket, ProgramError> {
+    fund_state.asset_markets[index].as_ref().ok_or_else(|| {
+        msg!("Asset {} has no Serum DEX market", index);
+        ProgramError::InvalidArgument
+    })
+}
+
+/// Serum DEX market of an asset, see `AssetMarket`.
+pub struct SerumDexVenue<'a, 'b> {
+    pub dex_program: DexProgram<'a, 'b>,
+    pub market: MarketAccounts<'a, 'b>,
+}
+
+impl<'a, 'b> SerumDexVenue<'a, 'b> {
+    fn lot_sizes(&self) -> Result<(u64, u64), ProgramError> {
+        lot_sizes(self.market.market, self.dex_program.program.key)
+    }
+
+    /// Serum DEX orders on behalf of the pool vault signer.
+    fn dex_context<'c>(&'c self, context: &'c SwapContext<'_, 'b>, owner_seeds: &'c [&'c [u8]]) -> DexContext<'c, 'b> {
+        DexContext {
+            dex_program: self.dex_program,
+            spl_token_program: context.spl_token_program,
+            owner: context.vault_signer,
+            owner_seeds,
+        }
+    }
+}
+
+impl<'a, 'b> SwapVenue<'a, 'b> for SerumDexVenue<'a, 'b> {
+    /// Reads the market accounts of the asset and checks them against its approved market.
+    fn next(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        programs: &VenuePrograms<'a, 'b>,
+        fund_state: &FundState,
+        index: usize,
+        _asset_mint: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let asset_market = asset_market(fund_state, index)?;
+        let dex_program = programs.dex_program.ok_or_else(|| {
+            msg!("Missing Serum DEX program accounts");
+            ProgramError::NotEnoughAccountKeys
+        })?;
+        let market = MarketAccounts::next(accounts_iter)?;
+        market.check(asset_market, &dex_program)?;
+        Ok(Self { dex_program, market })
+    }
+
+    /// Mid price of the market from its market account and order book.
+    fn next_quote(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        fund_state: &FundState,
+        index: usize,
+        _asset_mint: &Pubkey,
+    ) -> Result<ExchangeRate, ProgramError> {
+        let asset_market = asset_market(fund_state, index)?;
+        let market = next_account_info(accounts_iter)?;
+        let bids = next_account_info(accounts_iter)?;
+        let asks = next_account_info(accounts_iter)?;
+
+        check_account_address(market, &asset_market.market, stringify!(market))?;
+        book_rate(market, bids, asks, &asset_market.program_id)
+    }
+
+    fn quote(&self) -> Result<ExchangeRate, ProgramError> {
+        book_rate(
+            self.market.market,
+            self.market.bids,
+            self.market.asks,
+            self.dex_program.program.key,
+        )
+    }
+
+    /// Sells by an immediate-or-cancel order at the lowest price giving `minimum_amount_out`.
+    fn sell(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        asset_vault: &AccountInfo<'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let (coin_lot_size, pc_lot_size) = self.lot_sizes()?;
+
+        let max_coin_qty = amount_in / coin_lot_size;
+        if max_coin_qty == 0 {
+            msg!("Sell amount {} is below the market lot size", amount_in);
+            return Err(FundError::BelowLotSize.into());
+        }
+        // In quote lots per base lot
+        let lots_value = max_coin_qty as u128 * pc_lot_size as u128;
+        let limit_price = ((minimum_amount_out as u128)
+            .checked_add(lots_value - 1)
+            .ok_or(FundError::OperationOverflow)?
+            / lots_value) as u64;
+
+        let owner_seeds = context.signer_seeds();
+        let dex_context = self.dex_context(context, &owner_seeds);
+        let balance = parse_token_account(context.basic_asset_vault)?.amount;
+        dex_context.new_order(
+            &self.market,
+            asset_vault,
+            Side::Ask,
+            limit_price.max(1),
+            max_coin_qty,
+            u64::MAX,
+        )?;
+        dex_context.settle_funds(&self.market, asset_vault, context.basic_asset_vault)?;
+        check_amount_out(context.basic_asset_vault, balance, minimum_amount_out)
+    }
+
+    /// Buys by an immediate-or-cancel order at the highest price giving `minimum_amount_out`.
+    fn buy(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        asset_vault: &AccountInfo<'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let (coin_lot_size, pc_lot_size) = self.lot_sizes()?;
+
+        // The base lots bought at the lowest price, the order spends at most `amount_in` anyway
+        let max_coin_qty = amount_in / pc_lot_size;
+        if max_coin_qty == 0 {
+            msg!("Buy amount {} is below the market lot size", amount_in);
+            return Err(FundError::BelowLotSize.into());
+        }
+        // In quote lots per base lot
+        let limit_price = (amount_in as u128 * coin_lot_size as u128
+            / (pc_lot_size as u128 * minimum_amount_out.max(1) as u128))
+            .min(u64::MAX as u128) as u64;
+        if limit_price == 0 {
+            msg!(
+                "Buy amount {} cannot get {} tokens on the market",
+                amount_in,
+                minimum_amount_out
+            );
+            return Err(FundError::MinimumAmountOut.into());
+        }
+
+        let owner_seeds = context.signer_seeds();
+        let dex_context = self.dex_context(context, &owner_seeds);
+        let balance = parse_token_account(asset_vault)?.amount;
+        dex_context.new_order(
+            &self.market,
+            context.basic_asset_vault,
+            Side::Bid,
+            limit_price,
+            max_coin_qty,
+            amount_in,
+        )?;
+        dex_context.settle_funds(&self.market, asset_vault, context.basic_asset_vault)?;
+        check_amount_out(asset_vault, balance, minimum_amount_out)
+    }
+}
@@ -5,6 +5,26 @@ use thiserror::Error;
 pub enum FundError {
     #[error("Operation overflow")]
     OperationOverflow,
+    #[error("Pool price deviates from the oracle price")]
+    OraclePriceDeviation,
+    #[error("Rebalance cooldown has not elapsed")]
+    RebalanceCooldown,
+    #[error("Trade output is below the minimum amount")]
+    MinimumAmountOut,
+    #[error("Minted fund tokens are below the minimum amount")]
+    MinimumFundTokensOut,
+    #[error("Rebalance decreased the fund value beyond the bound")]
+    RebalanceNavLoss,
+    #[error("Rebalance moved an asset away from its target")]
+    RebalanceDrift,
+    #[error("Oracle price is too old")]
+    OraclePriceStale,
+    #[error("Oracle price confidence interval is too wide")]
+    OraclePriceUncertain,
+    #[error("Asset has no price oracle")]
+    MissingAssetOracle,
+    #[error("Trade amount is below the market lot size")]
+    BelowLotSize,
 }
 
 impl From<FundError> for ProgramError {
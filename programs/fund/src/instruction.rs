@@ -1,6 +1,20 @@
+//! Requests of the fund program, in addition to the `PoolRequest`s of serum-pool.
+//!
+//! A creation or a redemption by `PoolAction::Create` or `PoolAction::Redeem` also transfers the created or redeemed
+//! share of the fund vault of basic asset, rounded as the basket of the pool assets. The additional accounts of
+//! `PoolRequestInner::Execute`, after the spl-token program, are:
+//!
+//! - `[writable]` Fund vault account of basic asset
+//! - `[writable]` User basic asset account, may be omitted if the fund holds no basic asset, i.e. its basic asset
+//!   weight is zero and the vault is empty
+//!
+//! The additional accounts of `PoolAction::Swap` are described at `swap_request`.
+
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use serum_pool::schema::declare_tag;
 
+use crate::state::{AssetSwap, CrankConfig};
+
 declare_tag!(FundRequestTag, u64, 0x112ea41452f06767);
 
 /// Additional data for `PoolRequestInner::Initialize`.
@@ -13,11 +27,31 @@ declare_tag!(FundRequestTag, u64, 0x112ea41452f06767);
 /// - `[]` spl-token program ID
 #[derive(Clone, PartialEq, Eq, Debug, Default, BorshSerialize, BorshDeserialize)]
 pub struct InitializeFundData {
-    pub slippage_divider: u64,
+    pub rebalance_threshold_bps: u16,
+    pub max_slippage_bps: u16,
+    pub max_oracle_deviation_bps: u16,
     pub asset_weights: Vec<u32>,
+    pub asset_swaps: Vec<AssetSwap>,
     pub fund_token_initial_supply: u64,
 }
 
+/// Request of `PoolAction::Swap`, the amounts of the assets deposited by the user followed by the amounts of the assets
+/// withdrawn, one per asset in `PoolState::assets` each.
+///
+/// The assets are valued at their oracle prices only, so a swap fails unless every asset has a price oracle. The
+/// withdrawn value must not exceed the deposited value less the fund fee rate, which is kept by the fund. A swap must
+/// move the fund towards the target weights: a deposited asset must stay at or below its target value, and a withdrawn
+/// asset at or above it.
+///
+/// Additional accounts:
+///
+/// - `[]` Fund vault account of basic asset
+/// - `[]` Clock sysvar, to check the age of the oracle prices
+/// - `[]` Price account for each of the pool assets, in the assets order
+pub fn swap_request(inputs: &[u64], outputs: &[u64]) -> Vec<u64> {
+    inputs.iter().chain(outputs.iter()).copied().collect()
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize)]
 pub struct FundRequest {
     pub tag: FundRequestTag,
@@ -46,6 +80,20 @@ pub enum FundInstructionInner {
 
     /// Rebalances the fund assets.
     ///
+    /// Each asset is valued and traded on its swap venue: its Serum DEX market if it has one, at the mid price of the
+    /// order book and by immediate-or-cancel orders, otherwise its token-swap route. The assets with a price oracle
+    /// are valued at the oracle price, and the rebalance is aborted if their venue price deviates from it by more
+    /// than `FundState::max_oracle_deviation_bps`. Fails until `FundState::rebalance_cooldown` has elapsed since
+    /// the last rebalance.
+    ///
+    /// A two-hop route trades through the fund account of the intermediate token, the `FundState::max_slippage_bps`
+    /// bound applies to the output of the whole route and in the same proportion to the output of its first hop, and
+    /// to the settled amount of a market order. Intermediate tokens left in the fund account are valued in the basic
+    /// asset at the rate of the second hop pool.
+    ///
+    /// After the trades the fund is valued again at the same exchange rates, and the rebalance is aborted if the fund
+    /// value decreased by more than `FundState::max_nav_loss_bps` or an asset ended further from its target value.
+    ///
     /// Accounts:
     ///
     /// - `[writable]` Pool account
@@ -53,15 +101,34 @@ pub enum FundInstructionInner {
     /// - `[writable]` Pool vault account for each of the N pool assets
     /// - `[]` Pool vault authority
     /// - `[writable]` Fund vault account of basic asset
-    /// For each of the N pool assets:
-    ///   * `[]` Token-swap account
-    ///   * `[]` Swap authority
-    ///   * `[writable]` Swap asset account
-    ///   * `[writable]` Swap basic asset account
-    ///   * `[writable]` Swap pool token mint, to generate trading fees
-    ///   * `[writable]` Swap fee account, to receive trading fees
     /// - `[]` spl-token program ID
     /// - `[]` spl-token-swap program ID
+    /// - Only if one of the pool assets has a Serum DEX market:
+    ///   * `[]` Serum DEX program ID
+    ///   * `[]` Rent sysvar
+    /// - For each of the N pool assets, the venue accounts:
+    ///   * For an asset with a Serum DEX market:
+    ///     * `[writable]` Market account
+    ///     * `[writable]` Fund open orders account
+    ///     * `[writable]` Market request queue
+    ///     * `[writable]` Market event queue
+    ///     * `[writable]` Market bids
+    ///     * `[writable]` Market asks
+    ///     * `[writable]` Market base token vault
+    ///     * `[writable]` Market quote token vault
+    ///     * `[]` Market vault signer
+    ///   * Otherwise:
+    ///     * `[]` Token-swap account
+    ///     * `[]` Swap authority
+    ///     * `[writable]` Swap asset account
+    ///     * `[writable]` Swap basic asset account, or swap intermediate token account for a two-hop route
+    ///     * `[writable]` Swap pool token mint, to generate trading fees
+    ///     * `[writable]` Swap fee account, to receive trading fees
+    ///     * Only for a two-hop route, see `AssetSwap::intermediate`:
+    ///       * `[writable]` Fund account of the intermediate token
+    ///       * The 6 token-swap accounts of the intermediate token and the basic asset pool, in the same order
+    /// - `[]` Price account for each of the pool assets with an oracle, in the assets order
+    /// - `[]` Clock sysvar
     Rebalance,
 
     /// Approves an account to spend tokens on behalf of the pool.
@@ -92,4 +159,358 @@ pub enum FundInstructionInner {
     /// - `[signer]` Current admin account
     /// - `[signer]` New admin account
     UpdateAdmin,
+
+    /// Adds a new asset with the given target weight and token-swap route to the fund. The pool vault must not have a
+    /// delegate or a close authority, and the route is checked as by `UpdateAssetSwap`.
+    ///
+    /// The pool account must be allocated with room for the additional asset, e.g. with
+    /// `fund::state::calc_len(name, max_assets_count)`. The program cannot resize it, a fund without room is moved to
+    /// a larger account by `MigrateFund` first.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[]` Pool vault account for the new asset
+    /// - `[]` Token-swap account of the asset and the basic asset, or of the asset and the intermediate token
+    /// - `[]` spl-token-swap program ID
+    /// - Only if `with_intermediate`, for a two-hop route through an intermediate token:
+    ///   * `[]` Token-swap account of the intermediate token and the basic asset
+    ///   * `[]` Fund account of the intermediate token
+    AddAsset { weight: u32, with_intermediate: bool },
+
+    /// Removes an asset from the fund. The whole asset vault balance is sold for the basic asset first, on the swap
+    /// venue of the asset as in `Rebalance`, and the sale fails if it yields less than `minimum_amount_out`, e.g. the
+    /// amount quoted by the caller less its slippage.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[writable]` Pool vault account of the asset to remove
+    /// - `[]` Pool vault authority
+    /// - `[writable]` Fund vault account of basic asset
+    /// - `[]` spl-token program ID
+    /// - `[]` spl-token-swap program ID
+    /// - Only if the asset has a Serum DEX market:
+    ///   * `[]` Serum DEX program ID
+    ///   * `[]` Rent sysvar
+    /// - The venue accounts of the asset as in `Rebalance`
+    RemoveAsset { minimum_amount_out: u64 },
+
+    /// Sets new target weights for the fund assets, one per asset in `PoolState::assets`.
+    ///
+    /// If the `Rebalance` accounts follow the admin account, the fund is rebalanced to the new weights in the same
+    /// instruction.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - Optional `Rebalance` accounts after the admin account
+    UpdateWeights { asset_weights: Vec<u32> },
+
+    /// Modifies the maximum price impact, in basis points, accepted by the rebalance swaps.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    UpdateSlippage { max_slippage_bps: u16 },
+
+    /// Modifies the deviation of an asset value from its target, in basis points, before a rebalance trades the asset.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    UpdateRebalanceThreshold { rebalance_threshold_bps: u16 },
+
+    /// Approves the token-swap route used to trade an asset against the basic asset, directly or through an
+    /// intermediate token. Each pool of the route must be an initialized spl-token-swap pool trading the tokens of its
+    /// hop.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[]` Pool vault account of the asset
+    /// - `[]` Token-swap account of the asset and the basic asset, or of the asset and the intermediate token
+    /// - `[]` spl-token-swap program ID
+    /// - Only if `with_intermediate`, for a two-hop route through an intermediate token:
+    ///   * `[]` Token-swap account of the intermediate token and the basic asset
+    ///   * `[]` Fund account of the intermediate token
+    UpdateAssetSwap { with_intermediate: bool },
+
+    /// Sets the price oracle valuing an asset in the rebalance. A price published more than `max_price_age` slots ago,
+    /// or with a confidence interval above `max_confidence_bps` of the price, aborts the rebalance.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[]` Pool vault account of the asset
+    /// - `[]` Asset mint
+    /// - `[]` Basic asset mint
+    /// - `[]` Pyth-style price account of the asset quoted in the basic asset
+    /// - `[]` Oracle program ID, the owner of the price account
+    UpdateAssetOracle {
+        max_price_age: u64,
+        max_confidence_bps: u16,
+    },
+
+    /// Removes the price oracle of an asset, the rebalance values the asset at its swap venue price.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[]` Pool vault account of the asset
+    RemoveAssetOracle,
+
+    /// Modifies the maximum deviation of a swap venue price from the oracle price, in basis points, accepted by the
+    /// rebalance.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    UpdateMaxOracleDeviation { max_oracle_deviation_bps: u16 },
+
+    /// Rebalances the fund assets without the admin signature, once the largest asset drift exceeds
+    /// `CrankConfig::drift_threshold_bps` or `CrankConfig::min_interval` has elapsed since the last rebalance. Every
+    /// asset must have a price oracle, the assets are valued and the trades bounded at the oracle prices. The caller
+    /// is paid the `CrankConfig::reward` if at least one trade was executed, the instruction fails otherwise. A reward
+    /// in the basic asset counts in the fund value lost by the rebalance, bounded by `FundState::max_nav_loss_bps`.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[writable]` Keeper token account receiving the reward, of the basic asset or of the fund token
+    /// - `[writable]` Fund token mint
+    /// - `Rebalance` accounts after the admin account
+    Crank,
+
+    /// Modifies the permissionless rebalance parameters. The crank can only be enabled once every asset has a price
+    /// oracle, see `UpdateAssetOracle`.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    UpdateCrank { crank: CrankConfig },
+
+    /// Modifies the minimum seconds between two rebalances.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    UpdateRebalanceCooldown { rebalance_cooldown: i64 },
+
+    /// Values the fund and persists the trades of a rebalance as `FundState::rebalance_plan`, to be executed by
+    /// `RebalanceAssets`. Creations and redemptions are blocked until all the trades are done or the rebalance is
+    /// cancelled. Fails until `FundState::rebalance_cooldown` has elapsed since the last rebalance.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[]` Clock sysvar
+    /// - `[]` Pool vault account for each of the N pool assets
+    /// - `[]` Fund vault account of basic asset
+    /// - For each of the N pool assets:
+    ///   * Only if the asset has a Serum DEX market:
+    ///     * `[]` Market account
+    ///     * `[]` Market bids
+    ///     * `[]` Market asks
+    ///   * Otherwise:
+    ///     * `[]` Token-swap account
+    ///     * `[]` Swap asset account
+    ///     * `[]` Swap basic asset account, or swap intermediate token account for a two-hop route
+    ///     * Only for a two-hop route:
+    ///       * `[]` Token-swap account of the intermediate token and the basic asset
+    ///       * `[]` Swap intermediate token account
+    ///       * `[]` Swap basic asset account
+    ///   * `[]` Price account, only if the asset has an oracle
+    StartRebalance,
+
+    /// Executes the planned trades of the assets with the given indices in `PoolState::assets`, in strictly increasing
+    /// order. An asset is bought only once all the planned sells are done, so the sells may be split across
+    /// instructions before the buys.
+    ///
+    /// A trade is bounded by `FundState::max_slice_value`, and stays pending until the asset is within the rebalance
+    /// threshold of its target amount, so a large trade is executed in slices by successive instructions. Every slice
+    /// is priced at the `RebalancePlan::exchange_rates` of `StartRebalance`, its minimum amount out is the slice value
+    /// at these rates less `FundState::max_slippage_bps`.
+    ///
+    /// After the trades the traded assets and the fund vault of basic asset are valued again at the same rates, and
+    /// the slice is aborted if their value decreased by more than `FundState::max_nav_loss_bps` or an asset ended
+    /// further from its planned target amount. Fails while the fund is paused.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[]` Pool vault authority
+    /// - `[writable]` Fund vault account of basic asset
+    /// - `[]` spl-token program ID
+    /// - `[]` spl-token-swap program ID
+    /// - Only if one of the `indices` assets has a Serum DEX market:
+    ///   * `[]` Serum DEX program ID
+    ///   * `[]` Rent sysvar
+    /// - For each of the `indices`:
+    ///   * `[writable]` Pool vault account of the asset
+    ///   * The venue accounts of the asset as in `Rebalance`
+    RebalanceAssets { indices: Vec<u8> },
+
+    /// Drops the pending trades of the rebalance started by `StartRebalance`.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    CancelRebalance,
+
+    /// Sets the Serum DEX market valuing and trading an asset in the rebalance instead of its token-swap route.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[]` Pool vault account of the asset
+    /// - `[]` Market account of the asset, the base token, and the basic asset, the quote token
+    /// - `[]` Fund open orders account of the market, owned by the Serum DEX program, either for the market and the
+    ///   pool vault signer or not yet initialized, see `dex::check_open_orders`
+    /// - `[]` Serum DEX program ID
+    UpdateAssetMarket,
+
+    /// Removes the Serum DEX market of an asset, the rebalance trades the asset through its token-swap route.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - `[]` Pool vault account of the asset
+    RemoveAssetMarket,
+
+    /// Plans the trades of a `Rebalance` with the current balances and prices without executing them, and writes them
+    /// to the retbuf account as a borsh `Vec<PlannedTrade>`, one per asset in `PoolState::assets`. Does not require
+    /// the admin signature.
+    ///
+    /// Accounts:
+    ///
+    /// - `[]` Pool account
+    /// - `Rebalance` accounts after the admin account
+    /// - `[writable]` Retbuf account
+    /// - `[]` Retbuf program ID
+    SimulateRebalance,
+
+    /// Creates fund tokens for the basic asset only. The `basic_asset_amount` is split by the asset weights and each
+    /// part buys the asset on its swap venue, as by `Rebalance`. The fund tokens are minted for the value added to the
+    /// fund at the asset exchange rates before the trades, so the price impact of the trades is paid by the user, and
+    /// the creation fees are minted to the fee accounts. Every asset must have a price oracle, the exchange rates are
+    /// the oracle prices. Fails if the user would receive less than `min_fund_tokens_out`. Does not require the admin
+    /// signature.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[writable]` User basic asset account
+    /// - `[writable]` User fund token account
+    /// - `[signer]` User authority of the basic asset account
+    /// - `[writable]` Fund token mint
+    /// - `[writable]` Liquidity fee vault
+    /// - `[writable]` Initializer fee vault
+    /// - `[writable]` Referrer fee fund token account
+    /// - `Rebalance` accounts after the admin account
+    ZapIn {
+        basic_asset_amount: u64,
+        min_fund_tokens_out: u64,
+    },
+
+    /// Redeems fund tokens for the basic asset only. The fees are transferred to the fee accounts and the rest of the
+    /// `fund_token_amount` is burned, as by a redemption. The redeemed share of each asset vault is sold on its swap
+    /// venue, as by `Rebalance`, and the user receives the proceeds with the redeemed share of the basic asset vault.
+    /// Every asset must have a price oracle, the sells are bounded at the oracle prices less `max_slippage_bps`. Fails
+    /// if the user would receive less than `minimum_amount_out`. Does not require the admin signature.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[writable]` User fund token account
+    /// - `[writable]` User basic asset account
+    /// - `[signer]` User authority of the fund token account
+    /// - `[writable]` Fund token mint
+    /// - `[writable]` Liquidity fee vault
+    /// - `[writable]` Initializer fee vault
+    /// - `[writable]` Referrer fee fund token account
+    /// - `Rebalance` accounts after the admin account
+    ZapOut {
+        fund_token_amount: u64,
+        minimum_amount_out: u64,
+    },
+
+    /// Sets the target weight of the basic asset, see `FundState::basic_asset_weight`.
+    ///
+    /// If the `Rebalance` accounts follow the admin account, the fund is rebalanced to the new weight in the same
+    /// instruction.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    /// - Optional `Rebalance` accounts after the admin account
+    UpdateBasicAssetWeight { basic_asset_weight: u32 },
+
+    /// Modifies the maximum decrease of the fund value by a rebalance, in basis points.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    UpdateMaxNavLoss { max_nav_loss_bps: u16 },
+
+    /// Modifies the largest value of the trade of an asset by one rebalance slice, see `FundState::max_slice_value`.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[signer]` Admin account
+    UpdateMaxSliceValue { max_slice_value: u64 },
+
+    /// Executes a slice of the planned trades of the assets with the given indices, as `RebalanceAssets`, without the
+    /// admin signature. Requires `CrankConfig::enabled`, and fails if a keeper has already executed a slice in the
+    /// current slot. The caller is not paid a reward.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[]` Clock sysvar
+    /// - `RebalanceAssets` accounts after the admin account
+    CrankRebalanceAssets { indices: Vec<u8> },
+
+    /// Moves the fund to a new, larger pool account, e.g. to make room for `AddAsset`. The new account must be owned
+    /// by the fund program, zeroed, rent exempt and large enough for another asset, see `fund::state::calc_len`.
+    /// The vault signer of the new account, derived with `vault_signer_nonce`, becomes the owner of the fund
+    /// vaults and the minter of the fund token; the old pool account is closed and its lamports transferred to the
+    /// admin. Fails while a rebalance plan is in progress or an asset has a Serum DEX market, as its open orders
+    /// cannot be moved.
+    ///
+    /// Accounts:
+    ///
+    /// - `[writable]` Pool account
+    /// - `[writable, signer]` Admin account
+    /// - `[writable]` New pool account
+    /// - `[]` Pool vault authority
+    /// - `[writable]` Fund token mint
+    /// - `[writable]` Pool vault account for each of the N pool assets
+    /// - `[writable]` Fund vault account of basic asset
+    /// - `[writable]` Fund account of each intermediate token of the two-hop routes, once per token in the assets
+    ///   order
+    /// - `[]` Rent sysvar
+    /// - `[]` spl-token program ID
+    /// - `[writable]` Any other token accounts owned by the pool vault authority, e.g. the initial supply fund token
+    ///   account
+    MigrateFund { vault_signer_nonce: u8 },
 }
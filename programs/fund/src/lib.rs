@@ -1,6 +1,12 @@
+pub mod dex;
 #[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 pub mod error;
 pub mod instruction;
+pub mod oracle;
+pub mod price;
 pub mod processor;
 pub mod state;
+pub mod token_swap;
+pub mod valuation;
+pub mod venue;
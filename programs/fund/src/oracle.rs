@@ -0,0 +1,100 @@
+use std::convert::TryInto;
+
+use solana_program::{msg, program_error::ProgramError};
+
+/// Magic number of the Pyth accounts.
+pub const MAGIC: u32 = 0xa1b2_c3d4;
+/// Supported Pyth account layout version.
+pub const VERSION: u32 = 2;
+/// Pyth account type of the price accounts.
+pub const ACCOUNT_TYPE_PRICE: u32 = 3;
+/// Aggregate price status of a price being traded.
+pub const PRICE_STATUS_TRADING: u32 = 1;
+
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPO_OFFSET: usize = 20;
+const AGG_PRICE_OFFSET: usize = 208;
+const AGG_CONF_OFFSET: usize = 216;
+const AGG_STATUS_OFFSET: usize = 224;
+const AGG_PUB_SLOT_OFFSET: usize = 232;
+
+/// Aggregate price of a Pyth-style price account, `price * 10^expo` per whole token.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Price {
+    pub expo: i32,
+    pub price: i64,
+    pub conf: u64,
+    pub status: u32,
+    pub pub_slot: u64,
+}
+
+impl Price {
+    /// Length of the price account prefix up to the aggregate price.
+    pub const LEN: usize = 240;
+
+    /// Reads the aggregate price, only a trading price is accepted.
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < Self::LEN {
+            msg!("Price account data too small");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if read_u32(data, MAGIC_OFFSET) != MAGIC
+            || read_u32(data, VERSION_OFFSET) != VERSION
+            || read_u32(data, ACCOUNT_TYPE_OFFSET) != ACCOUNT_TYPE_PRICE
+        {
+            msg!("Not a price account");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let price = Self {
+            expo: read_u32(data, EXPO_OFFSET) as i32,
+            price: read_u64(data, AGG_PRICE_OFFSET) as i64,
+            conf: read_u64(data, AGG_CONF_OFFSET),
+            status: read_u32(data, AGG_STATUS_OFFSET),
+            pub_slot: read_u64(data, AGG_PUB_SLOT_OFFSET),
+        };
+        if price.status != PRICE_STATUS_TRADING {
+            msg!("Oracle price is not trading");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if price.price <= 0 {
+            msg!("Oracle price is not positive");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(price)
+    }
+
+    /// Writes a price account prefix, e.g. for the local price accounts of the tests.
+    pub fn pack(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        write_u32(data, MAGIC_OFFSET, MAGIC);
+        write_u32(data, VERSION_OFFSET, VERSION);
+        write_u32(data, ACCOUNT_TYPE_OFFSET, ACCOUNT_TYPE_PRICE);
+        write_u32(data, EXPO_OFFSET, self.expo as u32);
+        write_u64(data, AGG_PRICE_OFFSET, self.price as u64);
+        write_u64(data, AGG_CONF_OFFSET, self.conf);
+        write_u32(data, AGG_STATUS_OFFSET, self.status);
+        write_u64(data, AGG_PUB_SLOT_OFFSET, self.pub_slot);
+        Ok(())
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u64(data: &mut [u8], offset: usize, value: u64) {
+    data[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+}
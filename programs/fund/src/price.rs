@@ -0,0 +1,172 @@
+use std::convert::TryFrom;
+
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use solana_program::{msg, program_error::ProgramError};
+
+use crate::{error::FundError, oracle::Price, state::BPS_DENOMINATOR};
+
+/// Max decimal exponent of an exchange rate, keeps `10^exponent` and the amounts products within `u128`.
+const MAX_EXPONENT: u32 = 18;
+
+/// Exchange rate of an asset to the basic asset, as the ratio of their amounts in the smallest units.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub struct ExchangeRate {
+    pub asset: u128,
+    pub basic_asset: u128,
+}
+
+impl ExchangeRate {
+    /// Spot rate of a token-swap pool with the given reserves.
+    pub fn from_reserves(asset_reserve: u64, basic_asset_reserve: u64) -> Result<Self, ProgramError> {
+        if asset_reserve == 0 || basic_asset_reserve == 0 {
+            msg!("Swap pool reserves are empty");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(Self {
+            asset: asset_reserve as u128,
+            basic_asset: basic_asset_reserve as u128,
+        })
+    }
+
+    /// Rate of an oracle price of a whole asset token in whole basic asset tokens.
+    pub fn from_price(price: &Price, asset_decimals: u8, basic_asset_decimals: u8) -> Result<Self, ProgramError> {
+        if price.price <= 0 {
+            msg!("Oracle price is not positive");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let exponent = price.expo as i64 + basic_asset_decimals as i64 - asset_decimals as i64;
+        if exponent.abs() > MAX_EXPONENT as i64 {
+            msg!("Oracle price exponent {} out of range", exponent);
+            return Err(FundError::OperationOverflow.into());
+        }
+        let scale = 10_u128.pow(exponent.abs() as u32);
+        Ok(if exponent >= 0 {
+            Self {
+                asset: 1,
+                basic_asset: price.price as u128 * scale,
+            }
+        } else {
+            Self {
+                asset: scale,
+                basic_asset: price.price as u128,
+            }
+        })
+    }
+
+    /// Rate of trading through this rate's quote token and then by the `next` rate, e.g. an asset to an intermediate
+    /// token and the intermediate token to the basic asset.
+    pub fn then(&self, next: &ExchangeRate) -> Result<Self, ProgramError> {
+        let asset = self.asset.checked_mul(next.asset).ok_or(FundError::OperationOverflow)?;
+        let basic_asset = self
+            .basic_asset
+            .checked_mul(next.basic_asset)
+            .ok_or(FundError::OperationOverflow)?;
+
+        // Keep the terms within u64 for the amounts conversions, at a negligible precision loss
+        let shift = (128 - asset.max(basic_asset).leading_zeros()).saturating_sub(64);
+        let rate = Self {
+            asset: asset >> shift,
+            basic_asset: basic_asset >> shift,
+        };
+        if rate.asset == 0 || rate.basic_asset == 0 {
+            msg!("Chained exchange rate out of range");
+            return Err(FundError::OperationOverflow.into());
+        }
+        Ok(rate)
+    }
+
+    /// Value of the asset `amount` in the basic asset.
+    pub fn to_basic_asset(&self, amount: u64) -> Result<u64, ProgramError> {
+        convert(amount, self.basic_asset, self.asset)
+    }
+
+    /// Amount of the asset worth `value` of the basic asset.
+    pub fn to_asset(&self, value: u64) -> Result<u64, ProgramError> {
+        convert(value, self.asset, self.basic_asset)
+    }
+
+    /// Deviation of the `other` rate from this one, in basis points of this rate.
+    pub fn deviation_bps(&self, other: &ExchangeRate) -> Result<u128, ProgramError> {
+        // Compare the basic asset values of the same asset amount
+        let value = self
+            .basic_asset
+            .checked_mul(other.asset)
+            .ok_or(FundError::OperationOverflow)?;
+        let other_value = other
+            .basic_asset
+            .checked_mul(self.asset)
+            .ok_or(FundError::OperationOverflow)?;
+        let diff = if value > other_value {
+            value - other_value
+        } else {
+            other_value - value
+        };
+        diff.checked_mul(BPS_DENOMINATOR as u128)
+            .and_then(|diff| diff.checked_div(value))
+            .ok_or_else(|| FundError::OperationOverflow.into())
+    }
+}
+
+fn convert(amount: u64, numerator: u128, denominator: u128) -> Result<u64, ProgramError> {
+    let result = (amount as u128)
+        .checked_mul(numerator)
+        .and_then(|value| value.checked_div(denominator))
+        .ok_or(FundError::OperationOverflow)?;
+    u64::try_from(result).map_err(|err| {
+        msg!("Converted amount overflowed: {}", err);
+        FundError::OperationOverflow.into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_from_price() {
+        // 2.5 basic asset tokens (6 decimals) per asset token (9 decimals)
+        let price = Price {
+            expo: -6,
+            price: 2_500_000,
+            ..Default::default()
+        };
+        let rate = ExchangeRate::from_price(&price, 9, 6).unwrap();
+        assert_eq!(rate.to_basic_asset(1_000_000_000).unwrap(), 2_500_000);
+        assert_eq!(rate.to_asset(2_500_000).unwrap(), 1_000_000_000);
+    }
+
+    #[test]
+    fn test_rate_from_reserves() {
+        let rate = ExchangeRate::from_reserves(1_000, 4_000).unwrap();
+        assert_eq!(rate.to_basic_asset(10).unwrap(), 40);
+        assert_eq!(rate.to_asset(40).unwrap(), 10);
+        assert!(ExchangeRate::from_reserves(0, 4_000).is_err());
+        assert!(ExchangeRate::from_reserves(1_000, 0).is_err());
+    }
+
+    #[test]
+    fn test_chained_rate() {
+        // 4 intermediate tokens per asset token, then 2 basic asset tokens per intermediate token
+        let first = ExchangeRate::from_reserves(1_000, 4_000).unwrap();
+        let second = ExchangeRate::from_reserves(3_000, 6_000).unwrap();
+        let rate = first.then(&second).unwrap();
+        assert_eq!(rate.to_basic_asset(10).unwrap(), 80);
+        assert_eq!(rate.to_asset(80).unwrap(), 10);
+
+        // Large reserves are scaled down to u64 terms
+        let rate = ExchangeRate::from_reserves(u64::MAX, u64::MAX / 2)
+            .unwrap()
+            .then(&ExchangeRate::from_reserves(u64::MAX / 4, u64::MAX).unwrap())
+            .unwrap();
+        assert!(rate.asset <= u64::MAX as u128 && rate.basic_asset <= u64::MAX as u128);
+        assert_eq!(rate.to_basic_asset(1_000_000).unwrap(), 2_000_000);
+    }
+
+    #[test]
+    fn test_deviation_bps() {
+        let rate = ExchangeRate::from_reserves(1_000, 4_000).unwrap();
+        let other = ExchangeRate::from_reserves(1_000, 4_200).unwrap();
+        assert_eq!(rate.deviation_bps(&rate).unwrap(), 0);
+        assert_eq!(rate.deviation_bps(&other).unwrap(), 500);
+    }
+}
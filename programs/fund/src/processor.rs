@@ -1,27 +1,42 @@
-use std::convert::TryFrom;
-
 use borsh::{BorshDeserialize, BorshSerialize};
 use serum_pool::{
-    context::{check_account_address, check_token_account},
+    context::{check_account_address, check_token_account, FeeAccounts, Fees, RetbufAccounts},
     next_account_infos,
     schema::{AssetInfo, Basket, InitializePoolRequest, PoolState, FEE_RATE_DENOMINATOR, MIN_FEE_RATE},
     Pool, PoolContext,
 };
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
+    sysvar::{rent::Rent, Sysvar},
+};
+use spl_token::{
+    instruction::AuthorityType,
+    state::{Account as TokenAccount, Mint},
 };
-use spl_token::state::Account as TokenAccount;
 
 use crate::{
+    dex,
     error::FundError,
     instruction::{FundInstructionInner, FundRequest, InitializeFundData},
-    state::{FundState, FundStateContainer},
+    oracle::Price,
+    price::ExchangeRate,
+    state::{
+        calc_len, AssetMarket, AssetOracle, AssetSwap, FundState, FundStateContainer, IntermediateHop, KeeperReward,
+        PendingTrade, RebalancePlan, BPS_DENOMINATOR,
+    },
+    token_swap,
+    valuation::{
+        bps_of, checked_oracle_rate, created_amount, fund_tokens_for_value, oracle_exchange_rate, rebalance_slice,
+        redeemed_amounts, split_by_weights, swap_basket, user_fees, Valuation,
+    },
+    venue::{self, SwapContext, SwapVenue, VenuePrograms},
 };
 
 pub struct Fund;
@@ -32,7 +47,7 @@ impl Pool for Fund {
         state: &mut PoolState,
         request: &InitializePoolRequest,
     ) -> Result<(), ProgramError> {
-        let admin_account = context.custom_accounts.get(0).ok_or_else(|| {
+        let admin_account = context.custom_accounts.first().ok_or_else(|| {
             msg!("Missing fund admin account");
             ProgramError::NotEnoughAccountKeys
         })?;
@@ -79,19 +94,37 @@ impl Pool for Fund {
             })?
         };
 
-        if fund_data.asset_weights.len() != state.assets.len() {
+        check_asset_weights(&fund_data.asset_weights, state.assets.len())?;
+        if fund_data.asset_swaps.len() != state.assets.len() {
             msg!(
-                "Asset weights count {} does not match the assets count {}",
-                fund_data.asset_weights.len(),
+                "Asset swaps count {} does not match the assets count {}",
+                fund_data.asset_swaps.len(),
                 state.assets.len()
             );
             return Err(ProgramError::InvalidInstructionData);
         }
+        check_bps(fund_data.rebalance_threshold_bps, stringify!(rebalance_threshold_bps))?;
+        check_bps(fund_data.max_slippage_bps, stringify!(max_slippage_bps))?;
+        check_bps(fund_data.max_oracle_deviation_bps, stringify!(max_oracle_deviation_bps))?;
 
         state.write_fund_state(&FundState {
+            tag: Default::default(),
             paused: false,
-            slippage_divider: fund_data.slippage_divider,
+            rebalance_threshold_bps: fund_data.rebalance_threshold_bps,
+            max_slippage_bps: fund_data.max_slippage_bps,
+            max_oracle_deviation_bps: fund_data.max_oracle_deviation_bps,
+            max_nav_loss_bps: fund_data.max_slippage_bps,
+            max_slice_value: 0,
             asset_weights: fund_data.asset_weights,
+            basic_asset_weight: 0,
+            asset_swaps: fund_data.asset_swaps,
+            asset_oracles: vec![None; state.assets.len()],
+            asset_markets: vec![None; state.assets.len()],
+            crank: Default::default(),
+            rebalance_cooldown: 0,
+            last_rebalance_slot: 0,
+            last_rebalance_timestamp: 0,
+            rebalance_plan: None,
             basic_asset,
         })?;
 
@@ -127,6 +160,7 @@ impl Pool for Fund {
             msg!("Fund is paused");
             return Err(ProgramError::InvalidArgument);
         }
+        check_no_rebalance_plan(&custom_state)?;
         context.get_simple_basket(creation_size, true)
     }
 
@@ -140,16 +174,137 @@ impl Pool for Fund {
             msg!("Fund is paused");
             return Err(ProgramError::InvalidArgument);
         }
+        check_no_rebalance_plan(&custom_state)?;
         context.get_simple_basket(redemption_size, false)
     }
 
+    /// Transfers the creation basket and the created share of the fund vault of basic asset from the user, then mints
+    /// the fund tokens.
+    ///
+    /// Additional accounts:
+    ///
+    /// - `[writable]` Fund vault account of basic asset
+    /// - `[writable]` User basic asset account, may be omitted if the fund holds no basic asset
+    fn process_creation(context: &PoolContext, state: &mut PoolState, creation_size: u64) -> Result<(), ProgramError> {
+        let basket = Self::get_creation_basket(context, state, creation_size)?;
+        let (basic_asset_vault, basic_asset_balance, user_basic_asset_account) =
+            basic_asset_accounts(context, &state.read_fund_state()?)?;
+        let basic_asset_amount = created_amount(basic_asset_balance, creation_size, context.total_pool_tokens()?)?;
+
+        context.transfer_basket_from_user(&basket)?;
+        if basic_asset_amount > 0 {
+            msg!("Transfer basic asset from user");
+            let user_basic_asset_account = user_basic_asset_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let user_accounts = context.user_accounts.as_ref().ok_or(ProgramError::InvalidArgument)?;
+            let spl_token_program = context.spl_token_program.ok_or(ProgramError::InvalidArgument)?;
+            invoke(
+                &spl_token::instruction::transfer(
+                    &spl_token::ID,
+                    user_basic_asset_account.key,
+                    basic_asset_vault.key,
+                    user_accounts.authority.key,
+                    &[],
+                    basic_asset_amount,
+                )?,
+                &[
+                    user_basic_asset_account.clone(),
+                    basic_asset_vault.clone(),
+                    user_accounts.authority.clone(),
+                    spl_token_program.clone(),
+                ],
+            )?;
+        }
+        context.mint_tokens(state, creation_size)
+    }
+
+    /// Burns the redeemed fund tokens and collects the fees, then transfers the redemption basket and the redeemed
+    /// share of the fund vault of basic asset to the user.
+    ///
+    /// Additional accounts, as for a creation:
+    ///
+    /// - `[writable]` Fund vault account of basic asset
+    /// - `[writable]` User basic asset account, may be omitted if the fund holds no basic asset
+    fn process_redemption(
+        context: &PoolContext,
+        state: &mut PoolState,
+        redemption_size: u64,
+    ) -> Result<(), ProgramError> {
+        let fees = context.get_fees(state, redemption_size)?;
+        let redemption_size = redemption_size - fees.total_fee();
+        let basket = Self::get_redemption_basket(context, state, redemption_size)?;
+        let (basic_asset_vault, basic_asset_balance, user_basic_asset_account) =
+            basic_asset_accounts(context, &state.read_fund_state()?)?;
+        let basic_asset_amount =
+            redeemed_amounts(&[basic_asset_balance], redemption_size, context.total_pool_tokens()?)?[0];
+
+        context.burn_tokens_and_collect_fees(redemption_size, fees)?;
+        context.transfer_basket_to_user(state, &basket)?;
+        if basic_asset_amount > 0 {
+            msg!("Transfer basic asset to user");
+            let user_basic_asset_account = user_basic_asset_account.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            let spl_token_program = context.spl_token_program.ok_or(ProgramError::InvalidArgument)?;
+            invoke_signed(
+                &spl_token::instruction::transfer(
+                    &spl_token::ID,
+                    basic_asset_vault.key,
+                    user_basic_asset_account.key,
+                    context.pool_authority.key,
+                    &[],
+                    basic_asset_amount,
+                )?,
+                &[
+                    basic_asset_vault.clone(),
+                    user_basic_asset_account.clone(),
+                    context.pool_authority.clone(),
+                    spl_token_program.clone(),
+                ],
+                &[&[context.pool_account.key.as_ref(), &[state.vault_signer_nonce]]],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn get_swap_basket(context: &PoolContext, state: &PoolState, request: &[u64]) -> Result<Basket, ProgramError> {
+        let custom_state = state.read_fund_state()?;
+        if custom_state.paused {
+            msg!("Fund is paused");
+            return Err(ProgramError::InvalidArgument);
+        }
+        check_no_rebalance_plan(&custom_state)?;
+
+        let assets_count = state.assets.len();
+        if request.len() != 2 * assets_count {
+            msg!(
+                "Swap request must have the inputs and the outputs of {} assets",
+                assets_count
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (inputs, outputs) = request.split_at(assets_count);
+
+        let valuation = swap_valuation(context, state, &custom_state)?;
+        Ok(Basket {
+            quantities: swap_basket(&valuation, inputs, outputs, state.fee_rate)?,
+        })
+    }
+
+    fn process_swap(context: &PoolContext, state: &mut PoolState, request: &[u64]) -> Result<(), ProgramError> {
+        let basket = Self::get_swap_basket(context, state, request)?;
+        context.transfer_basket_from_user(&Basket {
+            quantities: basket.quantities.iter().map(|&quantity| quantity.max(0)).collect(),
+        })?;
+        context.transfer_basket_to_user(state, &Basket {
+            quantities: basket.quantities.iter().map(|&quantity| (-quantity).max(0)).collect(),
+        })
+    }
+
     #[allow(unused_variables)]
     fn process_foreign_instruction(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
         instruction_data: &[u8],
     ) -> ProgramResult {
-        let accounts_iter = &mut accounts.into_iter();
+        let accounts_iter = &mut accounts.iter();
 
         let pool_account = next_account_info(accounts_iter)?;
         if pool_account.owner != program_id {
@@ -165,16 +320,6 @@ impl Pool for Fund {
             })?
         };
 
-        let admin_account = next_account_info(accounts_iter)?;
-        if pool_state.admin_key.as_ref().map(AsRef::as_ref) != Some(admin_account.key) {
-            msg!("Incorrect admin account");
-            return Err(ProgramError::InvalidArgument);
-        }
-        if !admin_account.is_signer {
-            msg!("Admin account not signer");
-            return Err(ProgramError::MissingRequiredSignature);
-        }
-
         let instruction: FundInstructionInner = FundRequest::try_from_slice(instruction_data)
             .map_err(|_| {
                 msg!("Invalid instruction data");
@@ -182,29 +327,78 @@ impl Pool for Fund {
             })?
             .inner;
 
-        Self::process_admin_request(&pool_account, accounts_iter, &mut pool_state, &instruction)?;
+        match instruction {
+            FundInstructionInner::Crank => Self::process_crank(pool_account, accounts_iter, &mut pool_state)?,
+            // The pool account is read-only, its state is not written back
+            FundInstructionInner::SimulateRebalance => {
+                return Self::simulate_rebalance(pool_account, accounts_iter, &pool_state);
+            },
+            FundInstructionInner::ZapIn {
+                basic_asset_amount,
+                min_fund_tokens_out,
+            } => Self::zap_in(
+                pool_account,
+                accounts_iter,
+                &pool_state,
+                basic_asset_amount,
+                min_fund_tokens_out,
+            )?,
+            FundInstructionInner::ZapOut {
+                fund_token_amount,
+                minimum_amount_out,
+            } => Self::zap_out(
+                pool_account,
+                accounts_iter,
+                &pool_state,
+                fund_token_amount,
+                minimum_amount_out,
+            )?,
+            FundInstructionInner::CrankRebalanceAssets { indices } => {
+                Self::crank_rebalance_assets(pool_account, accounts_iter, &mut pool_state, &indices)?
+            },
+            // The state moves to the new pool account, the old one is closed
+            FundInstructionInner::MigrateFund { vault_signer_nonce } => {
+                let admin_account = next_admin_account(accounts_iter, &pool_state)?;
+                return Self::migrate_fund(
+                    program_id,
+                    pool_account,
+                    admin_account,
+                    accounts_iter,
+                    pool_state,
+                    vault_signer_nonce,
+                );
+            },
+            _ => {
+                next_admin_account(accounts_iter, &pool_state)?;
+                Self::process_admin_request(pool_account, accounts_iter, &mut pool_state, &instruction)?;
+            },
+        }
 
         let mut buf = Vec::new();
         BorshSerialize::serialize(&pool_state, &mut buf).map_err(|_| ProgramError::AccountDataTooSmall)?;
 
-        if pool_account.data_len() != buf.len() {
+        // The fund state may grow or shrink with its assets, the unused tail of the account is kept zeroed.
+        if pool_account.data_len() < buf.len() {
             msg!(
-                "Actual pool account data len {} does not match the required {}",
-                buf.len(),
-                pool_account.data_len()
+                "Actual pool account data len {} is less than the required {}",
+                pool_account.data_len(),
+                buf.len()
             );
             Err(ProgramError::InvalidAccountData)
         } else {
-            pool_account.try_borrow_mut_data()?.copy_from_slice(&buf);
+            let mut data = pool_account.try_borrow_mut_data()?;
+            let (state_data, tail) = data.split_at_mut(buf.len());
+            state_data.copy_from_slice(&buf);
+            tail.iter_mut().for_each(|byte| *byte = 0);
             Ok(())
         }
     }
 }
 
 impl Fund {
-    fn process_admin_request(
-        pool_account: &AccountInfo,
-        accounts_iter: &mut std::slice::Iter<AccountInfo>,
+    fn process_admin_request<'a, 'b>(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
         pool_state: &mut PoolState,
         request: &FundInstructionInner,
     ) -> Result<(), ProgramError> {
@@ -213,7 +407,7 @@ impl Fund {
         match request {
             FundInstructionInner::Pause => {
                 fund_state.paused = true;
-            }
+            },
             FundInstructionInner::Unpause => {
                 for asset in &pool_state.assets {
                     let vault_account = next_account_info(accounts_iter)?;
@@ -228,234 +422,10 @@ impl Fund {
                     }
                 }
                 fund_state.paused = false;
-            }
+            },
             FundInstructionInner::Rebalance => {
-                if fund_state.paused {
-                    msg!("Fund is paused");
-                    return Err(ProgramError::InvalidArgument);
-                }
-
-                let assets_count = pool_state.assets.len();
-                let pool_vaults = next_account_infos(accounts_iter, assets_count)?;
-                let vault_signer = next_account_info(accounts_iter)?;
-                let basic_asset_vault = next_account_info(accounts_iter)?;
-
-                let mut swaps = Vec::with_capacity(assets_count);
-                for _ in 0..assets_count {
-                    let swap = next_account_info(accounts_iter)?;
-                    let authority = next_account_info(accounts_iter)?;
-                    let asset = next_account_info(accounts_iter)?;
-                    let basic_asset = next_account_info(accounts_iter)?;
-                    let pool_token_mint = next_account_info(accounts_iter)?;
-                    let fee = next_account_info(accounts_iter)?;
-
-                    swaps.push(SwapAccounts {
-                        swap,
-                        authority,
-                        asset,
-                        basic_asset,
-                        pool_token_mint,
-                        fee,
-                    });
-                }
-
-                let spl_token_program = next_account_info(accounts_iter)?;
-                let spl_token_swap_program = next_account_info(accounts_iter)?;
-                let spl_token_swap_id = spl_token_swap_program.key;
-
-                // Check the accounts
-                check_account_address(vault_signer, &pool_state.vault_signer, stringify!(vault_signer))?;
-                check_token_account(
-                    basic_asset_vault,
-                    &fund_state.basic_asset.mint,
-                    Some(&pool_state.vault_signer),
-                )?;
-                check_account_address(vault_signer, &pool_state.vault_signer, stringify!(vault_signer))?;
-                if spl_token_program.key != &spl_token::ID {
-                    msg!("Incorrect spl-token program ID");
-                    return Err(ProgramError::InvalidArgument);
-                }
-                for i in 0..assets_count {
-                    let asset = &pool_state.assets[i];
-                    let asset_vault = &pool_vaults[i];
-                    let swap = swaps[i].swap;
-                    let swap_asset = swaps[i].asset;
-                    let swap_basic_asset = swaps[i].basic_asset;
-
-                    msg!("Check accounts for asset number {}", i);
-                    check_account_address(asset_vault, &asset.vault_address, stringify!(asset_vault))?;
-                    check_token_account(asset_vault, &asset.mint, Some(&pool_state.vault_signer))?;
-                    if swap.owner != spl_token_swap_id {
-                        msg!("Token-swap account {} not owned by spl-token-swap program", i);
-                        return Err(ProgramError::InvalidAccountData);
-                    }
-                    check_token_account(swap_asset, &asset.mint, Some(&pool_state.vault_signer))?;
-                    check_token_account(
-                        swap_basic_asset,
-                        &fund_state.basic_asset.mint,
-                        Some(&pool_state.vault_signer),
-                    )?;
-                }
-
-                // Calc the current amounts in the basic asset
-                let basic_asset_vault_token_account = TokenAccount::unpack(&basic_asset_vault.try_borrow_data()?)?;
-                let mut current_asset_amounts = Vec::with_capacity(pool_vaults.len());
-                let mut asset_vault_token_accounts = Vec::with_capacity(pool_vaults.len());
-                let mut total_amount = basic_asset_vault_token_account.amount as u128;
-
-                for (i, vault) in pool_vaults.iter().enumerate() {
-                    let vault_token_account = TokenAccount::unpack(&vault.try_borrow_data()?)?;
-                    let swap_asset_token_account = TokenAccount::unpack(&swaps[i].asset.try_borrow_data()?)?;
-                    let swap_basic_asset_token_account =
-                        TokenAccount::unpack(&swaps[i].basic_asset.try_borrow_data()?)?;
-                    let amount = vault_token_account.amount as u128 * swap_asset_token_account.amount as u128
-                        / swap_basic_asset_token_account.amount as u128;
-
-                    total_amount += amount;
-                    let amount = u64::try_from(amount).map_err(|err| {
-                        msg!("Amount overflowed: {}", err);
-                        FundError::OperationOverflow
-                    })?;
-                    current_asset_amounts.push(amount);
-                    asset_vault_token_accounts.push(vault_token_account);
-                }
-
-                // Calc the needed amounts in the basic asset
-                let total_weight = fund_state
-                    .asset_weights
-                    .iter()
-                    .fold(0_u128, |sum, weight| sum + *weight as u128);
-                let mut to_buy = Vec::new();
-
-                for (i, &amount) in current_asset_amounts.iter().enumerate() {
-                    let need_amount = u64::try_from(fund_state.asset_weights[i] as u128 * total_amount / total_weight)
-                        .map_err(|err| {
-                            msg!("Need amount overflow: {}", err);
-                            FundError::OperationOverflow
-                        })?;
-
-                    if need_amount < amount - amount / fund_state.slippage_divider {
-                        msg!("To sell asset {}", i);
-
-                        let amount_delta = amount - need_amount;
-                        let amount_in = u64::try_from(
-                            amount_delta as u128 * asset_vault_token_accounts[i].amount as u128 / amount as u128,
-                        )
-                        .map_err(|err| {
-                            msg!("Sell amount_in overflowed: {}", err);
-                            FundError::OperationOverflow
-                        })?;
-                        let minimum_amount_out = amount_delta - amount_delta / fund_state.slippage_divider;
-
-                        let swap_instruction = spl_token_swap::instruction::swap(
-                            spl_token_swap_id,
-                            &spl_token::id(),
-                            swaps[i].swap.key,
-                            swaps[i].authority.key,
-                            &pool_state.vault_signer,
-                            pool_vaults[i].key,
-                            swaps[i].asset.key,
-                            swaps[i].basic_asset.key,
-                            basic_asset_vault.key,
-                            swaps[i].pool_token_mint.key,
-                            swaps[i].fee.key,
-                            None,
-                            spl_token_swap::instruction::Swap {
-                                amount_in,
-                                minimum_amount_out,
-                            },
-                        )
-                        .map_err(|err| {
-                            msg!("Create swap instruction error for token {}: {}", i, err);
-                            err
-                        })?;
-
-                        let account_infos = vec![
-                            swaps[i].swap.clone(),
-                            swaps[i].authority.clone(),
-                            vault_signer.clone(),
-                            pool_vaults[i].clone(),
-                            swaps[i].asset.clone(),
-                            swaps[i].basic_asset.clone(),
-                            basic_asset_vault.clone(),
-                            swaps[i].pool_token_mint.clone(),
-                            swaps[i].fee.clone(),
-                            spl_token_program.clone(),
-                        ];
-
-                        invoke_signed(
-                            &swap_instruction,
-                            &account_infos,
-                            &[&[pool_account.key.as_ref(), &[pool_state.vault_signer_nonce]]],
-                        )
-                        .map_err(|err| {
-                            msg!("Invoke swap error for token {}: {}", i, err);
-                            err
-                        })?;
-                    } else if need_amount > amount + amount / fund_state.slippage_divider {
-                        to_buy.push((i, amount, need_amount));
-                    }
-                }
-
-                for (i, amount, need_amount) in to_buy {
-                    msg!("To buy asset {}", i);
-
-                    let amount_delta = need_amount - amount;
-                    let asset_amount_delta = u64::try_from(
-                        amount_delta as u128 * asset_vault_token_accounts[i].amount as u128 / amount as u128,
-                    )
-                    .map_err(|err| {
-                        msg!("Buy amount_in overflowed: {}", err);
-                        FundError::OperationOverflow
-                    })?;
-
-                    let swap_instruction = spl_token_swap::instruction::swap(
-                        spl_token_swap_id,
-                        &spl_token::id(),
-                        swaps[i].swap.key,
-                        swaps[i].authority.key,
-                        &pool_state.vault_signer,
-                        basic_asset_vault.key,
-                        swaps[i].basic_asset.key,
-                        swaps[i].asset.key,
-                        pool_vaults[i].key,
-                        swaps[i].pool_token_mint.key,
-                        swaps[i].fee.key,
-                        None,
-                        spl_token_swap::instruction::Swap {
-                            amount_in: amount_delta,
-                            minimum_amount_out: asset_amount_delta - asset_amount_delta / fund_state.slippage_divider,
-                        },
-                    )
-                    .map_err(|err| {
-                        msg!("Create swap instruction error for token {}: {}", i, err);
-                        err
-                    })?;
-
-                    let account_infos = vec![
-                        swaps[i].swap.clone(),
-                        swaps[i].authority.clone(),
-                        vault_signer.clone(),
-                        basic_asset_vault.clone(),
-                        swaps[i].basic_asset.clone(),
-                        swaps[i].asset.clone(),
-                        pool_vaults[i].clone(),
-                        swaps[i].pool_token_mint.clone(),
-                        swaps[i].fee.clone(),
-                        spl_token_program.clone(),
-                    ];
-
-                    invoke_signed(
-                        &swap_instruction,
-                        &account_infos,
-                        &[&[pool_account.key.as_ref(), &[pool_state.vault_signer_nonce]]],
-                    )
-                    .map_err(|err| {
-                        msg!("Invoke swap error for token {}: {}", i, err);
-                        err
-                    })?;
-                }
-            }
+                Self::rebalance(pool_account, accounts_iter, pool_state, &mut fund_state)?;
+            },
             FundInstructionInner::ApproveDelegate { amount } => {
                 let vault_account = next_account_info(accounts_iter)?;
                 let delegate_account = next_account_info(accounts_iter)?;
@@ -495,12 +465,10 @@ impl Fund {
                     vault_signer_account.clone(),
                     spl_token_program.clone(),
                 ];
-                invoke_signed(
-                    &instruction,
-                    account_infos,
-                    &[&[pool_account.key.as_ref(), &[pool_state.vault_signer_nonce]]],
-                )?;
-            }
+                invoke_signed(&instruction, account_infos, &[&[pool_account.key.as_ref(), &[
+                    pool_state.vault_signer_nonce,
+                ]]])?;
+            },
             FundInstructionInner::UpdateFee { fee_rate } => {
                 pool_state.fee_rate = *fee_rate;
                 if pool_state.fee_rate < MIN_FEE_RATE {
@@ -511,7 +479,7 @@ impl Fund {
                     msg!("Fee too high");
                     return Err(ProgramError::InvalidArgument);
                 }
-            }
+            },
             FundInstructionInner::UpdateAdmin => {
                 let new_admin_account = next_account_info(accounts_iter)?;
                 if !new_admin_account.is_signer {
@@ -519,33 +487,1645 @@ impl Fund {
                     return Err(ProgramError::MissingRequiredSignature);
                 }
                 pool_state.admin_key = Some(new_admin_account.key.into());
-            }
+            },
+            FundInstructionInner::AddAsset {
+                weight,
+                with_intermediate,
+            } => {
+                check_no_rebalance_plan(&fund_state)?;
+
+                // The asset may later get an oracle and a market, the account must have room for all of them
+                let required_len = calc_len(pool_state.name.as_str(), pool_state.assets.len() + 1);
+                if pool_account.data_len() < required_len {
+                    msg!(
+                        "Pool account data len {} has no room for another asset, {} required, see MigrateFund",
+                        pool_account.data_len(),
+                        required_len
+                    );
+                    return Err(ProgramError::AccountDataTooSmall);
+                }
+
+                let vault_account = next_account_info(accounts_iter)?;
+
+                let parsed_vault_account = parse_token_account(vault_account)?;
+                if pool_state.assets.iter().any(|asset| {
+                    asset.vault_address.as_ref() == vault_account.key
+                        || asset.mint.as_ref() == &parsed_vault_account.mint
+                }) {
+                    msg!("Asset already in fund");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                if fund_state.basic_asset.mint.as_ref() == &parsed_vault_account.mint {
+                    msg!("Basic asset cannot be a fund asset");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                if &parsed_vault_account.owner != pool_state.vault_signer.as_ref() {
+                    msg!("Token account not owned by fund");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                if parsed_vault_account.delegate.is_some() {
+                    msg!("Asset vault must not have a delegate");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                if parsed_vault_account.close_authority.is_some() {
+                    msg!("Asset vault must not have a close authority");
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                let asset_swap = next_asset_swap(
+                    accounts_iter,
+                    pool_state,
+                    &fund_state,
+                    &parsed_vault_account.mint,
+                    *with_intermediate,
+                )?;
+
+                pool_state.assets.push(AssetInfo {
+                    mint: parsed_vault_account.mint.into(),
+                    vault_address: vault_account.key.into(),
+                });
+                fund_state.asset_weights.push(*weight);
+                fund_state.asset_swaps.push(asset_swap);
+                fund_state.asset_oracles.push(None);
+                fund_state.asset_markets.push(None);
+            },
+            FundInstructionInner::RemoveAsset { minimum_amount_out } => {
+                check_no_rebalance_plan(&fund_state)?;
+
+                let asset_vault = next_account_info(accounts_iter)?;
+                let vault_signer = next_account_info(accounts_iter)?;
+                let basic_asset_vault = next_account_info(accounts_iter)?;
+
+                let index = pool_state
+                    .assets
+                    .iter()
+                    .position(|asset| asset.vault_address.as_ref() == asset_vault.key)
+                    .ok_or_else(|| {
+                        msg!("Asset not found");
+                        ProgramError::InvalidArgument
+                    })?;
+                if pool_state.assets.len() == 1 {
+                    msg!("Cannot remove the last fund asset");
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                let spl_token_program = next_account_info(accounts_iter)?;
+                // The asset is sold on the venue it is valued and rebalanced on
+                let venue_programs = VenuePrograms::next(accounts_iter, fund_state.asset_markets[index].is_some())?;
+                let venue =
+                    venue_programs.next_venue(accounts_iter, &fund_state, index, &pool_state.assets[index].mint)?;
+
+                // Check the accounts
+                check_account_address(vault_signer, &pool_state.vault_signer, stringify!(vault_signer))?;
+                check_account_address(
+                    basic_asset_vault,
+                    &fund_state.basic_asset.vault_address,
+                    stringify!(basic_asset_vault),
+                )?;
+                check_token_account(
+                    basic_asset_vault,
+                    &fund_state.basic_asset.mint,
+                    Some(&pool_state.vault_signer),
+                )?;
+                if spl_token_program.key != &spl_token::ID {
+                    msg!("Incorrect spl-token program ID");
+                    return Err(ProgramError::InvalidArgument);
+                }
+
+                // Sell the whole asset balance for the basic asset
+                let amount_in = parse_token_account(asset_vault)?.amount;
+                if amount_in > 0 {
+                    let swap_context = SwapContext {
+                        pool_account,
+                        vault_signer,
+                        vault_signer_nonce: pool_state.vault_signer_nonce,
+                        basic_asset_vault,
+                        spl_token_program,
+                    };
+                    venue
+                        .sell(&swap_context, asset_vault, amount_in, *minimum_amount_out)
+                        .map_err(|err| {
+                            msg!("Invoke swap error for token {}: {}", index, err);
+                            err
+                        })?;
+                }
+
+                pool_state.assets.remove(index);
+                fund_state.asset_weights.remove(index);
+                fund_state.asset_swaps.remove(index);
+                fund_state.asset_oracles.remove(index);
+                fund_state.asset_markets.remove(index);
+                check_asset_weights(&fund_state.asset_weights, pool_state.assets.len())?;
+            },
+            FundInstructionInner::UpdateWeights { asset_weights } => {
+                check_no_rebalance_plan(&fund_state)?;
+                check_asset_weights(asset_weights, pool_state.assets.len())?;
+                fund_state.asset_weights = asset_weights.clone();
+
+                if accounts_iter.len() > 0 {
+                    Self::rebalance(pool_account, accounts_iter, pool_state, &mut fund_state)?;
+                }
+            },
+            FundInstructionInner::UpdateBasicAssetWeight { basic_asset_weight } => {
+                check_no_rebalance_plan(&fund_state)?;
+                fund_state.basic_asset_weight = *basic_asset_weight;
+
+                if accounts_iter.len() > 0 {
+                    Self::rebalance(pool_account, accounts_iter, pool_state, &mut fund_state)?;
+                }
+            },
+            FundInstructionInner::UpdateSlippage { max_slippage_bps } => {
+                check_bps(*max_slippage_bps, stringify!(max_slippage_bps))?;
+                fund_state.max_slippage_bps = *max_slippage_bps;
+            },
+            FundInstructionInner::UpdateMaxNavLoss { max_nav_loss_bps } => {
+                check_bps(*max_nav_loss_bps, stringify!(max_nav_loss_bps))?;
+                fund_state.max_nav_loss_bps = *max_nav_loss_bps;
+            },
+            FundInstructionInner::UpdateMaxSliceValue { max_slice_value } => {
+                fund_state.max_slice_value = *max_slice_value;
+            },
+            FundInstructionInner::UpdateRebalanceThreshold {
+                rebalance_threshold_bps,
+            } => {
+                check_bps(*rebalance_threshold_bps, stringify!(rebalance_threshold_bps))?;
+                fund_state.rebalance_threshold_bps = *rebalance_threshold_bps;
+            },
+            FundInstructionInner::UpdateAssetSwap { with_intermediate } => {
+                let vault_account = next_account_info(accounts_iter)?;
+
+                let index = pool_state
+                    .assets
+                    .iter()
+                    .position(|asset| asset.vault_address.as_ref() == vault_account.key)
+                    .ok_or_else(|| {
+                        msg!("Asset not found");
+                        ProgramError::InvalidArgument
+                    })?;
+
+                fund_state.asset_swaps[index] = next_asset_swap(
+                    accounts_iter,
+                    pool_state,
+                    &fund_state,
+                    &pool_state.assets[index].mint,
+                    *with_intermediate,
+                )?;
+            },
+            FundInstructionInner::UpdateAssetOracle {
+                max_price_age,
+                max_confidence_bps,
+            } => {
+                let vault_account = next_account_info(accounts_iter)?;
+                let asset_mint = next_account_info(accounts_iter)?;
+                let basic_asset_mint = next_account_info(accounts_iter)?;
+                let price_account = next_account_info(accounts_iter)?;
+                let oracle_program = next_account_info(accounts_iter)?;
+
+                let index = pool_state
+                    .assets
+                    .iter()
+                    .position(|asset| asset.vault_address.as_ref() == vault_account.key)
+                    .ok_or_else(|| {
+                        msg!("Asset not found");
+                        ProgramError::InvalidArgument
+                    })?;
+                check_account_address(asset_mint, &pool_state.assets[index].mint, stringify!(asset_mint))?;
+                check_account_address(
+                    basic_asset_mint,
+                    &fund_state.basic_asset.mint,
+                    stringify!(basic_asset_mint),
+                )?;
+                if price_account.owner != oracle_program.key {
+                    msg!("Price account not owned by oracle program");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                check_bps(*max_confidence_bps, stringify!(max_confidence_bps))?;
+                let asset_oracle = AssetOracle {
+                    program_id: oracle_program.key.into(),
+                    price_account: price_account.key.into(),
+                    asset_decimals: parse_mint(asset_mint)?.decimals,
+                    basic_asset_decimals: parse_mint(basic_asset_mint)?.decimals,
+                    max_price_age: *max_price_age,
+                    max_confidence_bps: *max_confidence_bps,
+                };
+
+                // The price must be usable by the rebalance
+                let price = Price::unpack(&price_account.try_borrow_data()?)?;
+                ExchangeRate::from_price(&price, asset_oracle.asset_decimals, asset_oracle.basic_asset_decimals)?;
+
+                fund_state.asset_oracles[index] = Some(asset_oracle);
+            },
+            FundInstructionInner::RemoveAssetOracle => {
+                let vault_account = next_account_info(accounts_iter)?;
+
+                let index = pool_state
+                    .assets
+                    .iter()
+                    .position(|asset| asset.vault_address.as_ref() == vault_account.key)
+                    .ok_or_else(|| {
+                        msg!("Asset not found");
+                        ProgramError::InvalidArgument
+                    })?;
+                fund_state.asset_oracles[index] = None;
+            },
+            FundInstructionInner::UpdateMaxOracleDeviation {
+                max_oracle_deviation_bps,
+            } => {
+                check_bps(*max_oracle_deviation_bps, stringify!(max_oracle_deviation_bps))?;
+                fund_state.max_oracle_deviation_bps = *max_oracle_deviation_bps;
+            },
+            FundInstructionInner::Crank => {
+                msg!("Crank does not require the admin signature");
+                return Err(ProgramError::InvalidInstructionData);
+            },
+            FundInstructionInner::UpdateCrank { crank } => {
+                check_bps(crank.drift_threshold_bps, stringify!(drift_threshold_bps))?;
+                if crank.min_interval <= 0 {
+                    msg!("Crank min interval must be positive");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                if crank.enabled {
+                    check_asset_oracles(&fund_state)?;
+                }
+                fund_state.crank = crank.clone();
+            },
+            FundInstructionInner::UpdateRebalanceCooldown { rebalance_cooldown } => {
+                if *rebalance_cooldown < 0 {
+                    msg!("Rebalance cooldown must not be negative");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                fund_state.rebalance_cooldown = *rebalance_cooldown;
+            },
+            FundInstructionInner::StartRebalance => {
+                Self::start_rebalance(accounts_iter, pool_state, &mut fund_state)?;
+            },
+            FundInstructionInner::RebalanceAssets { indices } => {
+                Self::rebalance_assets(pool_account, accounts_iter, pool_state, &mut fund_state, indices)?;
+            },
+            FundInstructionInner::CancelRebalance => {
+                fund_state.rebalance_plan = None;
+            },
+            FundInstructionInner::UpdateAssetMarket => {
+                let vault_account = next_account_info(accounts_iter)?;
+                let market = next_account_info(accounts_iter)?;
+                let open_orders = next_account_info(accounts_iter)?;
+                let dex_program = next_account_info(accounts_iter)?;
+
+                let index = pool_state
+                    .assets
+                    .iter()
+                    .position(|asset| asset.vault_address.as_ref() == vault_account.key)
+                    .ok_or_else(|| {
+                        msg!("Asset not found");
+                        ProgramError::InvalidArgument
+                    })?;
+                let (coin_mint, pc_mint) = dex::market_mints(market, dex_program.key)?;
+                if &coin_mint != pool_state.assets[index].mint.as_ref()
+                    || &pc_mint != fund_state.basic_asset.mint.as_ref()
+                {
+                    msg!("Market must trade the asset against the basic asset");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                dex::check_open_orders(
+                    open_orders,
+                    market.key,
+                    pool_state.vault_signer.as_ref(),
+                    dex_program.key,
+                )?;
+
+                fund_state.asset_markets[index] = Some(AssetMarket {
+                    program_id: dex_program.key.into(),
+                    market: market.key.into(),
+                    open_orders: open_orders.key.into(),
+                });
+            },
+            FundInstructionInner::RemoveAssetMarket => {
+                let vault_account = next_account_info(accounts_iter)?;
+
+                let index = pool_state
+                    .assets
+                    .iter()
+                    .position(|asset| asset.vault_address.as_ref() == vault_account.key)
+                    .ok_or_else(|| {
+                        msg!("Asset not found");
+                        ProgramError::InvalidArgument
+                    })?;
+
+                fund_state.asset_markets[index] = None;
+            },
+            FundInstructionInner::SimulateRebalance => {
+                msg!("Rebalance simulation does not require the admin signature");
+                return Err(ProgramError::InvalidInstructionData);
+            },
+            FundInstructionInner::ZapIn { .. } => {
+                msg!("Zap in does not require the admin signature");
+                return Err(ProgramError::InvalidInstructionData);
+            },
+            FundInstructionInner::ZapOut { .. } => {
+                msg!("Zap out does not require the admin signature");
+                return Err(ProgramError::InvalidInstructionData);
+            },
+            FundInstructionInner::CrankRebalanceAssets { .. } => {
+                msg!("Crank rebalance assets does not require the admin signature");
+                return Err(ProgramError::InvalidInstructionData);
+            },
+            FundInstructionInner::MigrateFund { .. } => {
+                msg!("Fund migration does not update the pool account in place");
+                return Err(ProgramError::InvalidInstructionData);
+            },
         };
 
         pool_state.write_fund_state(&fund_state)?;
 
         Ok(())
     }
-}
 
-fn parse_token_account(account_info: &AccountInfo) -> Result<TokenAccount, ProgramError> {
-    if account_info.owner != &spl_token::ID {
-        msg!("Account not owned by spl-token program");
-        return Err(ProgramError::IncorrectProgramId);
-    }
-    let parsed = TokenAccount::unpack(&account_info.try_borrow_data()?)?;
-    if !parsed.is_initialized() {
-        msg!("Token account not initialized");
-        return Err(ProgramError::UninitializedAccount);
-    }
-    Ok(parsed)
+    /// Moves the fund to the new pool account of `MigrateFund`: the vaults and the fund token mint are handed over to
+    /// the vault signer of the new account, the state is copied to it and the old account is closed.
+    fn migrate_fund<'a, 'b>(
+        program_id: &Pubkey,
+        pool_account: &'a AccountInfo<'b>,
+        admin_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        mut pool_state: PoolState,
+        vault_signer_nonce: u8,
+    ) -> ProgramResult {
+        let fund_state = pool_state.read_fund_state()?;
+        check_no_rebalance_plan(&fund_state)?;
+        if fund_state.asset_markets.iter().any(Option::is_some) {
+            msg!("Asset markets must be removed first, their open orders cannot be migrated");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let new_pool_account = next_account_info(accounts_iter)?;
+        let vault_signer = next_account_info(accounts_iter)?;
+        let pool_token_mint = next_account_info(accounts_iter)?;
+        let asset_vaults = next_account_infos(accounts_iter, pool_state.assets.len())?;
+        let basic_asset_vault = next_account_info(accounts_iter)?;
+        let mut intermediate_vault_addresses = Vec::new();
+        for intermediate in fund_state
+            .asset_swaps
+            .iter()
+            .filter_map(|swap| swap.intermediate.as_ref())
+        {
+            if !intermediate_vault_addresses.contains(&&intermediate.vault) {
+                intermediate_vault_addresses.push(&intermediate.vault);
+            }
+        }
+        let intermediate_vaults = next_account_infos(accounts_iter, intermediate_vault_addresses.len())?;
+        let rent = Rent::from_account_info(next_account_info(accounts_iter)?)?;
+        let spl_token_program = next_account_info(accounts_iter)?;
+        let other_token_accounts = accounts_iter.as_slice();
+
+        check_account_address(vault_signer, &pool_state.vault_signer, stringify!(vault_signer))?;
+        check_account_address(
+            pool_token_mint,
+            &pool_state.pool_token_mint,
+            stringify!(pool_token_mint),
+        )?;
+        for (asset_vault, asset) in asset_vaults.iter().zip(&pool_state.assets) {
+            check_account_address(asset_vault, &asset.vault_address, stringify!(asset_vault))?;
+        }
+        check_account_address(
+            basic_asset_vault,
+            &fund_state.basic_asset.vault_address,
+            stringify!(basic_asset_vault),
+        )?;
+        for (intermediate_vault, address) in intermediate_vaults.iter().zip(&intermediate_vault_addresses) {
+            check_account_address(intermediate_vault, address, stringify!(intermediate_vault))?;
+        }
+        if spl_token_program.key != &spl_token::ID {
+            msg!("Incorrect spl-token program ID");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if new_pool_account.owner != program_id {
+            msg!("New pool account not owned by fund program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if new_pool_account.key == pool_account.key {
+            msg!("New pool account must differ from the pool account");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if new_pool_account.try_borrow_data()?.iter().any(|byte| *byte != 0) {
+            msg!("New pool account must be zeroed");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let required_len = calc_len(pool_state.name.as_str(), pool_state.assets.len() + 1);
+        if new_pool_account.data_len() < required_len {
+            msg!(
+                "New pool account data len {} has no room for another asset, {} required",
+                new_pool_account.data_len(),
+                required_len
+            );
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        if !rent.is_exempt(new_pool_account.lamports(), new_pool_account.data_len()) {
+            msg!("New pool account is not rent exempt");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let new_vault_signer =
+            Pubkey::create_program_address(&[new_pool_account.key.as_ref(), &[vault_signer_nonce]], program_id)
+                .map_err(|_| {
+                    msg!("Invalid vault signer nonce for the new pool account");
+                    ProgramError::InvalidArgument
+                })?;
+
+        let set_authority = |account: &AccountInfo<'b>, authority_type| {
+            invoke_signed(
+                &spl_token::instruction::set_authority(
+                    &spl_token::ID,
+                    account.key,
+                    Some(&new_vault_signer),
+                    authority_type,
+                    vault_signer.key,
+                    &[],
+                )?,
+                &[account.clone(), vault_signer.clone(), spl_token_program.clone()],
+                &[&[pool_account.key.as_ref(), &[pool_state.vault_signer_nonce]]],
+            )
+        };
+        set_authority(pool_token_mint, AuthorityType::MintTokens)?;
+        for vault in asset_vaults
+            .iter()
+            .chain(std::iter::once(basic_asset_vault))
+            .chain(intermediate_vaults)
+            .chain(other_token_accounts)
+        {
+            set_authority(vault, AuthorityType::AccountOwner)?;
+        }
+
+        // Also migrates a state of the untagged layout
+        pool_state.vault_signer = new_vault_signer.into();
+        pool_state.vault_signer_nonce = vault_signer_nonce;
+        pool_state.write_fund_state(&fund_state)?;
+        let mut buf = Vec::new();
+        BorshSerialize::serialize(&pool_state, &mut buf).map_err(|_| ProgramError::AccountDataTooSmall)?;
+        if new_pool_account.data_len() < buf.len() {
+            msg!(
+                "New pool account data len {} is less than the required {}",
+                new_pool_account.data_len(),
+                buf.len()
+            );
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        new_pool_account.try_borrow_mut_data()?[..buf.len()].copy_from_slice(&buf);
+
+        pool_account
+            .try_borrow_mut_data()?
+            .iter_mut()
+            .for_each(|byte| *byte = 0);
+        let lamports = pool_account.lamports();
+        **admin_account.try_borrow_mut_lamports()? = admin_account
+            .lamports()
+            .checked_add(lamports)
+            .ok_or(FundError::OperationOverflow)?;
+        **pool_account.try_borrow_mut_lamports()? = 0;
+        Ok(())
+    }
+
+    /// Trades the fund assets through their swap venues towards the target weights.
+    ///
+    /// Expects the `Rebalance` accounts after the admin account.
+    fn rebalance<'a, 'b>(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &PoolState,
+        fund_state: &mut FundState,
+    ) -> ProgramResult {
+        if fund_state.paused {
+            msg!("Fund is paused");
+            return Err(ProgramError::InvalidArgument);
+        }
+        check_no_rebalance_plan(fund_state)?;
+
+        let accounts = RebalanceAccounts::next(pool_account, accounts_iter, pool_state, fund_state)?;
+        record_rebalance(fund_state, &accounts.clock)?;
+        let valuation = accounts.valuation(fund_state)?;
+        Self::trade(&accounts, fund_state, &valuation)?;
+        Self::check_trade_outcome(&accounts, fund_state, &valuation)
+    }
+
+    fn process_crank<'a, 'b>(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &mut PoolState,
+    ) -> ProgramResult {
+        let mut fund_state = pool_state.read_fund_state()?;
+
+        let keeper_account = next_account_info(accounts_iter)?;
+        let fund_token_mint = next_account_info(accounts_iter)?;
+
+        if !fund_state.crank.enabled {
+            msg!("Crank is disabled");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if fund_state.paused {
+            msg!("Fund is paused");
+            return Err(ProgramError::InvalidArgument);
+        }
+        check_no_rebalance_plan(&fund_state)?;
+        // The keeper trades at the oracle prices only
+        check_asset_oracles(&fund_state)?;
+
+        let accounts = RebalanceAccounts::next(pool_account, accounts_iter, pool_state, &fund_state)?;
+        let valuation = accounts.valuation(&fund_state)?;
+
+        let drift_bps = valuation.max_drift_bps();
+        let elapsed = accounts
+            .clock
+            .unix_timestamp
+            .saturating_sub(fund_state.last_rebalance_timestamp);
+        if drift_bps <= fund_state.crank.drift_threshold_bps as u128 && elapsed < fund_state.crank.min_interval {
+            msg!(
+                "Rebalance not needed: drift {} bps, {} seconds since the last rebalance",
+                drift_bps,
+                elapsed
+            );
+            return Err(ProgramError::InvalidArgument);
+        }
+        record_rebalance(&mut fund_state, &accounts.clock)?;
+
+        // The keeper is paid for trades only
+        if Self::trade(&accounts, &fund_state, &valuation)? == 0 {
+            msg!("Rebalance executed no trade");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let swap_context = &accounts.swap_context;
+        let signers_seeds: &[&[u8]] = &[pool_account.key.as_ref(), &[pool_state.vault_signer_nonce]];
+        match fund_state.crank.reward {
+            KeeperReward::None => {},
+            KeeperReward::BasicAsset { amount } => {
+                check_token_account(keeper_account, &fund_state.basic_asset.mint, None)?;
+                let instruction = spl_token::instruction::transfer(
+                    &spl_token::ID,
+                    swap_context.basic_asset_vault.key,
+                    keeper_account.key,
+                    swap_context.vault_signer.key,
+                    &[],
+                    amount,
+                )?;
+                let account_infos = &[
+                    swap_context.basic_asset_vault.clone(),
+                    keeper_account.clone(),
+                    swap_context.vault_signer.clone(),
+                    swap_context.spl_token_program.clone(),
+                ];
+                invoke_signed(&instruction, account_infos, &[signers_seeds])?;
+            },
+            KeeperReward::FundTokens { amount } => {
+                check_account_address(
+                    fund_token_mint,
+                    &pool_state.pool_token_mint,
+                    stringify!(fund_token_mint),
+                )?;
+                check_token_account(keeper_account, &pool_state.pool_token_mint, None)?;
+                let instruction = spl_token::instruction::mint_to(
+                    &spl_token::ID,
+                    fund_token_mint.key,
+                    keeper_account.key,
+                    swap_context.vault_signer.key,
+                    &[],
+                    amount,
+                )?;
+                let account_infos = &[
+                    fund_token_mint.clone(),
+                    keeper_account.clone(),
+                    swap_context.vault_signer.clone(),
+                    swap_context.spl_token_program.clone(),
+                ];
+                invoke_signed(&instruction, account_infos, &[signers_seeds])?;
+            },
+        }
+        // A reward paid from the fund vault of basic asset is part of the fund value lost by the rebalance
+        Self::check_trade_outcome(&accounts, &fund_state, &valuation)?;
+
+        pool_state.write_fund_state(&fund_state)
+    }
+
+    /// Plans the trades of a rebalance without executing them, and writes them to the retbuf account.
+    fn simulate_rebalance<'a, 'b>(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &PoolState,
+    ) -> ProgramResult {
+        let fund_state = pool_state.read_fund_state()?;
+
+        let accounts = RebalanceAccounts::next(pool_account, accounts_iter, pool_state, &fund_state)?;
+        let retbuf = RetbufAccounts::new(next_account_info(accounts_iter)?, next_account_info(accounts_iter)?)?;
+
+        let trades = accounts
+            .valuation(&fund_state)?
+            .planned_trades(fund_state.rebalance_threshold_bps, fund_state.max_slippage_bps)?;
+
+        // The first 8 bytes are the offset in the retbuf account
+        let mut result = vec![0; 8];
+        trades
+            .serialize(&mut result)
+            .map_err(|_| ProgramError::InvalidInstructionData)?;
+        retbuf.write_data(result)
+    }
+
+    /// Buys the fund assets with the basic asset of the user, and mints the fund tokens for the value added to the
+    /// fund.
+    fn zap_in<'a, 'b>(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &PoolState,
+        basic_asset_amount: u64,
+        min_fund_tokens_out: u64,
+    ) -> ProgramResult {
+        let fund_state = pool_state.read_fund_state()?;
+
+        let user_basic_asset_account = next_account_info(accounts_iter)?;
+        let user_fund_token_account = next_account_info(accounts_iter)?;
+        let user_authority = next_account_info(accounts_iter)?;
+        let fund_token_mint = next_account_info(accounts_iter)?;
+        let fee_accounts = FeeAccounts::new(
+            pool_state,
+            next_account_info(accounts_iter)?,
+            next_account_info(accounts_iter)?,
+            next_account_info(accounts_iter)?,
+        )?;
+
+        if fund_state.paused {
+            msg!("Fund is paused");
+            return Err(ProgramError::InvalidArgument);
+        }
+        check_no_rebalance_plan(&fund_state)?;
+        // The fund tokens are priced at the oracle prices only
+        check_asset_oracles(&fund_state)?;
+
+        let accounts = RebalanceAccounts::next(pool_account, accounts_iter, pool_state, &fund_state)?;
+        let swap_context = &accounts.swap_context;
+
+        // Check the accounts
+        check_token_account(user_basic_asset_account, &fund_state.basic_asset.mint, None)?;
+        check_token_account(user_fund_token_account, &pool_state.pool_token_mint, None)?;
+        if !user_authority.is_signer {
+            msg!("User authority not signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        check_account_address(
+            fund_token_mint,
+            &pool_state.pool_token_mint,
+            stringify!(fund_token_mint),
+        )?;
+
+        let valuation = accounts.valuation(&fund_state)?;
+        let fund_token_supply = parse_mint(fund_token_mint)?.supply;
+
+        msg!("Transfer basic asset from user");
+        invoke(
+            &spl_token::instruction::transfer(
+                &spl_token::ID,
+                user_basic_asset_account.key,
+                swap_context.basic_asset_vault.key,
+                user_authority.key,
+                &[],
+                basic_asset_amount,
+            )?,
+            &[
+                user_basic_asset_account.clone(),
+                swap_context.basic_asset_vault.clone(),
+                user_authority.clone(),
+                swap_context.spl_token_program.clone(),
+            ],
+        )?;
+
+        let amounts_in = split_by_weights(
+            basic_asset_amount,
+            &fund_state.asset_weights,
+            fund_state.basic_asset_weight,
+        )?;
+        for (i, &amount_in) in amounts_in.iter().enumerate() {
+            if amount_in == 0 {
+                continue;
+            }
+            msg!("To buy asset {}", i);
+
+            let amount_out = valuation.exchange_rates[i].to_asset(amount_in)?;
+            let minimum_amount_out = amount_out - bps_of(amount_out, fund_state.max_slippage_bps);
+            accounts.venues[i]
+                .buy(swap_context, &accounts.pool_vaults[i], amount_in, minimum_amount_out)
+                .map_err(|err| {
+                    msg!("Invoke swap error for token {}: {}", i, err);
+                    err
+                })?;
+        }
+
+        // The bought assets are valued at the exchange rates before the trades
+        let new_valuation = accounts.value_at(&fund_state, valuation.exchange_rates.clone())?;
+        let value = new_valuation.total_amount.saturating_sub(valuation.total_amount);
+        let fund_tokens = fund_tokens_for_value(fund_token_supply, valuation.total_amount, value)?;
+
+        let fees = fund_token_fees(pool_state, &fee_accounts, user_fund_token_account, fund_tokens)?;
+        let user_fund_tokens = fund_tokens - fees.total_fee();
+        if user_fund_tokens < min_fund_tokens_out {
+            msg!(
+                "Minted {} fund tokens, the minimum is {}",
+                user_fund_tokens,
+                min_fund_tokens_out
+            );
+            return Err(FundError::MinimumFundTokensOut.into());
+        }
+
+        for (account, amount) in &[
+            (fee_accounts.lqd_fee_account, fees.lqd_fee),
+            (fee_accounts.initializer_fee_account, fees.initializer_fee),
+            (fee_accounts.referrer_fee_account, fees.referrer_fee),
+            (user_fund_token_account, user_fund_tokens),
+        ] {
+            if *amount == 0 {
+                continue;
+            }
+            invoke_signed(
+                &spl_token::instruction::mint_to(
+                    &spl_token::ID,
+                    fund_token_mint.key,
+                    account.key,
+                    swap_context.vault_signer.key,
+                    &[],
+                    *amount,
+                )?,
+                &[
+                    fund_token_mint.clone(),
+                    (*account).clone(),
+                    swap_context.vault_signer.clone(),
+                    swap_context.spl_token_program.clone(),
+                ],
+                &[&swap_context.signer_seeds()],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Burns fund tokens of the user, sells the redeemed shares of the fund assets and transfers the proceeds in the
+    /// basic asset to the user.
+    fn zap_out<'a, 'b>(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &PoolState,
+        fund_token_amount: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let fund_state = pool_state.read_fund_state()?;
+
+        let user_fund_token_account = next_account_info(accounts_iter)?;
+        let user_basic_asset_account = next_account_info(accounts_iter)?;
+        let user_authority = next_account_info(accounts_iter)?;
+        let fund_token_mint = next_account_info(accounts_iter)?;
+        let fee_accounts = FeeAccounts::new(
+            pool_state,
+            next_account_info(accounts_iter)?,
+            next_account_info(accounts_iter)?,
+            next_account_info(accounts_iter)?,
+        )?;
+
+        if fund_state.paused {
+            msg!("Fund is paused");
+            return Err(ProgramError::InvalidArgument);
+        }
+        check_no_rebalance_plan(&fund_state)?;
+        // The sells are bounded at the oracle prices only
+        check_asset_oracles(&fund_state)?;
+
+        let accounts = RebalanceAccounts::next(pool_account, accounts_iter, pool_state, &fund_state)?;
+        let swap_context = &accounts.swap_context;
+
+        // Check the accounts
+        check_token_account(user_fund_token_account, &pool_state.pool_token_mint, None)?;
+        check_token_account(user_basic_asset_account, &fund_state.basic_asset.mint, None)?;
+        if !user_authority.is_signer {
+            msg!("User authority not signer");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        check_account_address(
+            fund_token_mint,
+            &pool_state.pool_token_mint,
+            stringify!(fund_token_mint),
+        )?;
+
+        let valuation = accounts.valuation(&fund_state)?;
+        let fund_token_supply = parse_mint(fund_token_mint)?.supply;
+        let basic_asset_balance = parse_token_account(swap_context.basic_asset_vault)?.amount;
+
+        let fees = fund_token_fees(pool_state, &fee_accounts, user_fund_token_account, fund_token_amount)?;
+        let redemption_size = fund_token_amount - fees.total_fee();
+        let balances = accounts
+            .pool_vaults
+            .iter()
+            .map(|vault| Ok(parse_token_account(vault)?.amount))
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        let amounts_in = redeemed_amounts(&balances, redemption_size, fund_token_supply)?;
+        let basic_asset_share = redeemed_amounts(&[basic_asset_balance], redemption_size, fund_token_supply)?[0];
+
+        msg!("Collect fees and burn fund tokens");
+        for (account, amount) in &[
+            (fee_accounts.lqd_fee_account, fees.lqd_fee),
+            (fee_accounts.initializer_fee_account, fees.initializer_fee),
+            (fee_accounts.referrer_fee_account, fees.referrer_fee),
+        ] {
+            if *amount == 0 {
+                continue;
+            }
+            invoke(
+                &spl_token::instruction::transfer(
+                    &spl_token::ID,
+                    user_fund_token_account.key,
+                    account.key,
+                    user_authority.key,
+                    &[],
+                    *amount,
+                )?,
+                &[
+                    user_fund_token_account.clone(),
+                    (*account).clone(),
+                    user_authority.clone(),
+                    swap_context.spl_token_program.clone(),
+                ],
+            )?;
+        }
+        invoke(
+            &spl_token::instruction::burn(
+                &spl_token::ID,
+                user_fund_token_account.key,
+                fund_token_mint.key,
+                user_authority.key,
+                &[],
+                redemption_size,
+            )?,
+            &[
+                user_fund_token_account.clone(),
+                fund_token_mint.clone(),
+                user_authority.clone(),
+                swap_context.spl_token_program.clone(),
+            ],
+        )?;
+
+        for (i, &amount_in) in amounts_in.iter().enumerate() {
+            if amount_in == 0 {
+                continue;
+            }
+            msg!("To sell asset {}", i);
+
+            let amount_out = valuation.exchange_rates[i].to_basic_asset(amount_in)?;
+            let minimum_amount_out = amount_out - bps_of(amount_out, fund_state.max_slippage_bps);
+            accounts.venues[i]
+                .sell(swap_context, &accounts.pool_vaults[i], amount_in, minimum_amount_out)
+                .map_err(|err| {
+                    msg!("Invoke swap error for token {}: {}", i, err);
+                    err
+                })?;
+        }
+
+        let proceeds = parse_token_account(swap_context.basic_asset_vault)?
+            .amount
+            .saturating_sub(basic_asset_balance);
+        let amount_out = proceeds + basic_asset_share;
+        if amount_out < minimum_amount_out {
+            msg!("Received {} tokens, the minimum is {}", amount_out, minimum_amount_out);
+            return Err(FundError::MinimumAmountOut.into());
+        }
+
+        msg!("Transfer basic asset to user");
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                &spl_token::ID,
+                swap_context.basic_asset_vault.key,
+                user_basic_asset_account.key,
+                swap_context.vault_signer.key,
+                &[],
+                amount_out,
+            )?,
+            &[
+                swap_context.basic_asset_vault.clone(),
+                user_basic_asset_account.clone(),
+                swap_context.vault_signer.clone(),
+                swap_context.spl_token_program.clone(),
+            ],
+            &[&swap_context.signer_seeds()],
+        )
+    }
+
+    /// Values the fund and plans the trades of a rebalance split across instructions.
+    fn start_rebalance<'a, 'b>(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &PoolState,
+        fund_state: &mut FundState,
+    ) -> ProgramResult {
+        if fund_state.paused {
+            msg!("Fund is paused");
+            return Err(ProgramError::InvalidArgument);
+        }
+        check_no_rebalance_plan(fund_state)?;
+
+        let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+        let assets_count = pool_state.assets.len();
+        let pool_vaults = next_account_infos(accounts_iter, assets_count)?;
+        let basic_asset_vault = next_account_info(accounts_iter)?;
+
+        check_account_address(
+            basic_asset_vault,
+            &fund_state.basic_asset.vault_address,
+            stringify!(basic_asset_vault),
+        )?;
+
+        let mut exchange_rates = Vec::with_capacity(assets_count);
+        for (i, asset) in pool_state.assets.iter().enumerate() {
+            msg!("Check accounts for asset number {}", i);
+            check_account_address(&pool_vaults[i], &asset.vault_address, stringify!(asset_vault))?;
+            let venue_rate = venue::next_venue_quote(accounts_iter, fund_state, i, &asset.mint)?;
+
+            exchange_rates.push(asset_exchange_rate(fund_state, i, venue_rate, accounts_iter, &clock)?);
+        }
+
+        record_rebalance(fund_state, &clock)?;
+
+        let valuation = value_fund(fund_state, pool_vaults, basic_asset_vault, 0, exchange_rates)?;
+        let plan = RebalancePlan {
+            target_amounts: valuation
+                .need_amounts
+                .iter()
+                .zip(valuation.exchange_rates.iter())
+                .map(|(&need_amount, exchange_rate)| exchange_rate.to_asset(need_amount))
+                .collect::<Result<Vec<_>, ProgramError>>()?,
+            exchange_rates: valuation.exchange_rates.clone(),
+            pending_trades: (0..assets_count)
+                .map(|i| valuation.pending_trade(i, fund_state.rebalance_threshold_bps))
+                .collect(),
+            last_keeper_slot: None,
+        };
+
+        if plan.is_finished() {
+            msg!("Fund is balanced");
+        } else {
+            fund_state.rebalance_plan = Some(plan);
+        }
+        Ok(())
+    }
+
+    /// Executes the planned trades of the assets with the given indices, at the exchange rates of the plan.
+    fn rebalance_assets<'a, 'b>(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &PoolState,
+        fund_state: &mut FundState,
+        indices: &[u8],
+    ) -> ProgramResult {
+        if fund_state.paused {
+            msg!("Fund is paused");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let mut plan = fund_state.rebalance_plan.take().ok_or_else(|| {
+            msg!("No rebalance in progress");
+            ProgramError::InvalidArgument
+        })?;
+        if indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+            msg!("Asset indices must be strictly increasing");
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let vault_signer = next_account_info(accounts_iter)?;
+        let basic_asset_vault = next_account_info(accounts_iter)?;
+        let spl_token_program = next_account_info(accounts_iter)?;
+        let venue_programs = VenuePrograms::next(
+            accounts_iter,
+            indices
+                .iter()
+                .any(|&index| matches!(fund_state.asset_markets.get(index as usize), Some(Some(_)))),
+        )?;
+
+        // Check the accounts
+        check_account_address(vault_signer, &pool_state.vault_signer, stringify!(vault_signer))?;
+        check_account_address(
+            basic_asset_vault,
+            &fund_state.basic_asset.vault_address,
+            stringify!(basic_asset_vault),
+        )?;
+        if spl_token_program.key != &spl_token::ID {
+            msg!("Incorrect spl-token program ID");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let swap_context = SwapContext {
+            pool_account,
+            vault_signer,
+            vault_signer_nonce: pool_state.vault_signer_nonce,
+            basic_asset_vault,
+            spl_token_program,
+        };
+
+        let basic_asset_balance = parse_token_account(basic_asset_vault)?.amount;
+        let mut asset_vaults = Vec::with_capacity(indices.len());
+        let mut balances = Vec::with_capacity(indices.len());
+        for &index in indices {
+            let i = index as usize;
+            let asset = pool_state.assets.get(i).ok_or_else(|| {
+                msg!("Asset {} not found", i);
+                ProgramError::InvalidArgument
+            })?;
+            let asset_vault = next_account_info(accounts_iter)?;
+
+            msg!("Check accounts for asset number {}", i);
+            check_account_address(asset_vault, &asset.vault_address, stringify!(asset_vault))?;
+            let venue = venue_programs.next_venue(accounts_iter, fund_state, i, &asset.mint)?;
+            let exchange_rate = plan.exchange_rates[i];
+
+            let amount = parse_token_account(asset_vault)?.amount;
+            asset_vaults.push(asset_vault);
+            balances.push(amount);
+            let target_amount = plan.target_amounts[i];
+            let band = bps_of(amount, fund_state.rebalance_threshold_bps);
+            let max_slice_amount = match fund_state.max_slice_value {
+                0 => None,
+                max_slice_value => Some(exchange_rate.to_asset(max_slice_value)?.max(1)),
+            };
+
+            let finished = match plan.pending_trades[i] {
+                PendingTrade::Sell => {
+                    msg!("To sell asset {}", i);
+
+                    // The amount may have moved since the start by the previous trades of the asset pool
+                    let (amount_in, finished) =
+                        rebalance_slice(amount.saturating_sub(target_amount), band, max_slice_amount);
+                    if amount_in > 0 {
+                        let amount_out = exchange_rate.to_basic_asset(amount_in)?;
+                        let minimum_amount_out = amount_out - bps_of(amount_out, fund_state.max_slippage_bps);
+
+                        venue
+                            .sell(&swap_context, asset_vault, amount_in, minimum_amount_out)
+                            .map_err(|err| {
+                                msg!("Invoke swap error for token {}: {}", i, err);
+                                err
+                            })?;
+                    }
+                    finished
+                },
+                PendingTrade::Buy => {
+                    msg!("To buy asset {}", i);
+
+                    if plan.has_pending_sells() {
+                        msg!("Asset {} must be bought after all the planned sells", i);
+                        return Err(ProgramError::InvalidArgument);
+                    }
+                    let (slice_amount, finished) =
+                        rebalance_slice(target_amount.saturating_sub(amount), band, max_slice_amount);
+
+                    // The sells may have yielded less than planned, spend what is left and finish the trade
+                    let basic_asset_amount = parse_token_account(basic_asset_vault)?.amount;
+                    let slice_amount_in = exchange_rate.to_basic_asset(slice_amount)?;
+                    let amount_in = slice_amount_in.min(basic_asset_amount);
+                    let amount_out = exchange_rate.to_asset(amount_in)?;
+                    let minimum_amount_out = amount_out - bps_of(amount_out, fund_state.max_slippage_bps);
+
+                    if amount_in > 0 {
+                        venue
+                            .buy(&swap_context, asset_vault, amount_in, minimum_amount_out)
+                            .map_err(|err| {
+                                msg!("Invoke swap error for token {}: {}", i, err);
+                                err
+                            })?;
+                    }
+                    finished || amount_in < slice_amount_in
+                },
+                PendingTrade::None => {
+                    msg!("No pending trade for asset {}", i);
+                    return Err(ProgramError::InvalidArgument);
+                },
+            };
+            if finished {
+                plan.pending_trades[i] = PendingTrade::None;
+            } else {
+                msg!("Trade of asset {} continues in the next slice", i);
+            }
+        }
+
+        // The traded assets and the basic asset are valued at the plan exchange rates before and after the slice
+        let exchange_rates = indices
+            .iter()
+            .map(|&index| plan.exchange_rates[index as usize])
+            .collect::<Vec<_>>();
+        let target_amounts = indices
+            .iter()
+            .map(|&index| plan.target_amounts[index as usize])
+            .collect::<Vec<_>>();
+        let valuation =
+            Valuation::with_targets(&balances, &target_amounts, basic_asset_balance, exchange_rates.clone())?;
+        let new_balances = asset_vaults
+            .iter()
+            .map(|asset_vault| Ok(parse_token_account(asset_vault)?.amount))
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        let outcome = Valuation::with_targets(
+            &new_balances,
+            &target_amounts,
+            parse_token_account(basic_asset_vault)?.amount,
+            exchange_rates,
+        )?;
+        valuation.check_rebalance_outcome(&outcome, fund_state.max_nav_loss_bps)?;
+
+        if plan.is_finished() {
+            msg!("Rebalance finished");
+        } else {
+            fund_state.rebalance_plan = Some(plan);
+        }
+        Ok(())
+    }
+
+    /// Executes a slice of the planned trades of the assets with the given indices for a keeper, one per slot.
+    fn crank_rebalance_assets<'a, 'b>(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &mut PoolState,
+        indices: &[u8],
+    ) -> ProgramResult {
+        let mut fund_state = pool_state.read_fund_state()?;
+
+        let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+
+        if !fund_state.crank.enabled {
+            msg!("Crank is disabled");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let plan = fund_state.rebalance_plan.as_mut().ok_or_else(|| {
+            msg!("No rebalance in progress");
+            ProgramError::InvalidArgument
+        })?;
+        if plan.last_keeper_slot == Some(clock.slot) {
+            msg!("Rebalance slice already executed in slot {}", clock.slot);
+            return Err(ProgramError::InvalidArgument);
+        }
+        plan.last_keeper_slot = Some(clock.slot);
+
+        Self::rebalance_assets(pool_account, accounts_iter, pool_state, &mut fund_state, indices)?;
+
+        pool_state.write_fund_state(&fund_state)
+    }
+
+    /// Sells the assets above their target values, then buys the assets below them with the basic asset. Returns the
+    /// number of executed trades, their outcome is checked by `check_trade_outcome`.
+    fn trade(
+        accounts: &RebalanceAccounts,
+        fund_state: &FundState,
+        valuation: &Valuation,
+    ) -> Result<usize, ProgramError> {
+        let trades = valuation.planned_trades(fund_state.rebalance_threshold_bps, fund_state.max_slippage_bps)?;
+
+        let (sells, buys): (Vec<_>, Vec<_>) = trades
+            .iter()
+            .enumerate()
+            .filter(|(_, trade)| trade.direction != PendingTrade::None)
+            .partition(|(_, trade)| trade.direction == PendingTrade::Sell);
+        let mut executed_trades = 0;
+
+        for (i, trade) in sells {
+            msg!("To sell asset {}", i);
+
+            accounts.venues[i]
+                .sell(
+                    &accounts.swap_context,
+                    &accounts.pool_vaults[i],
+                    trade.amount_in,
+                    trade.minimum_amount_out,
+                )
+                .map_err(|err| {
+                    msg!("Invoke swap error for token {}: {}", i, err);
+                    err
+                })?;
+            executed_trades += 1;
+        }
+
+        for (i, trade) in buys {
+            msg!("To buy asset {}", i);
+
+            // The sells may have yielded less than planned, spend what is left at the planned rate
+            let basic_asset_amount = parse_token_account(accounts.swap_context.basic_asset_vault)?.amount;
+            let amount_in = trade.amount_in.min(basic_asset_amount);
+            if amount_in == 0 {
+                msg!("No basic asset left to buy asset {}", i);
+                continue;
+            }
+            let minimum_amount_out =
+                (trade.minimum_amount_out as u128 * amount_in as u128 / trade.amount_in as u128) as u64;
+
+            accounts.venues[i]
+                .buy(
+                    &accounts.swap_context,
+                    &accounts.pool_vaults[i],
+                    amount_in,
+                    minimum_amount_out,
+                )
+                .map_err(|err| {
+                    msg!("Invoke swap error for token {}: {}", i, err);
+                    err
+                })?;
+            executed_trades += 1;
+        }
+        Ok(executed_trades)
+    }
+
+    /// Checks the fund after the trades of a rebalance, and after any payout of the vaults, against its `valuation`
+    /// before them.
+    fn check_trade_outcome(
+        accounts: &RebalanceAccounts,
+        fund_state: &FundState,
+        valuation: &Valuation,
+    ) -> ProgramResult {
+        // The outcome is valued at the exchange rates before the trades
+        let outcome = accounts.value_at(fund_state, valuation.exchange_rates.clone())?;
+        valuation.check_rebalance_outcome(&outcome, fund_state.max_nav_loss_bps)
+    }
+}
+
+fn check_asset_weights(asset_weights: &[u32], assets_count: usize) -> Result<(), ProgramError> {
+    if asset_weights.len() != assets_count {
+        msg!(
+            "Asset weights count {} does not match the assets count {}",
+            asset_weights.len(),
+            assets_count
+        );
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    if asset_weights.iter().all(|&weight| weight == 0) {
+        msg!("Asset weights total is zero");
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(())
+}
+
+/// Reads the route of an asset from the `AddAsset` or `UpdateAssetSwap` accounts after the asset vault, a two-hop route
+/// if `with_intermediate`.
+fn next_asset_swap<'a, 'b: 'a>(
+    accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    pool_state: &PoolState,
+    fund_state: &FundState,
+    asset_mint: &Pubkey,
+    with_intermediate: bool,
+) -> Result<AssetSwap, ProgramError> {
+    let swap = next_account_info(accounts_iter)?;
+    let spl_token_swap_program = next_account_info(accounts_iter)?;
+    let intermediate_accounts = if with_intermediate {
+        Some((next_account_info(accounts_iter)?, next_account_info(accounts_iter)?))
+    } else {
+        None
+    };
+
+    if swap.owner != spl_token_swap_program.key {
+        msg!("Token-swap account not owned by spl-token-swap program");
+        return Err(ProgramError::InvalidAccountData);
+    }
+
+    let intermediate = match intermediate_accounts {
+        Some((intermediate_swap, intermediate_vault)) => {
+            if intermediate_swap.owner != spl_token_swap_program.key {
+                msg!("Intermediate token-swap account not owned by spl-token-swap program");
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let parsed_intermediate_vault = parse_token_account(intermediate_vault)?;
+            if &parsed_intermediate_vault.owner != pool_state.vault_signer.as_ref() {
+                msg!("Intermediate token account not owned by fund");
+                return Err(ProgramError::InvalidArgument);
+            }
+            let mint = parsed_intermediate_vault.mint;
+            if &mint == asset_mint || &mint == fund_state.basic_asset.mint.as_ref() {
+                msg!("Intermediate token must differ from the asset and the basic asset");
+                return Err(ProgramError::InvalidArgument);
+            }
+            token_swap::check_pool_mints(swap, asset_mint, &mint)?;
+            token_swap::check_pool_mints(intermediate_swap, &mint, fund_state.basic_asset.mint.as_ref())?;
+            Some(IntermediateHop {
+                swap: intermediate_swap.key.into(),
+                vault: intermediate_vault.key.into(),
+                mint: mint.into(),
+            })
+        },
+        None => {
+            token_swap::check_pool_mints(swap, asset_mint, fund_state.basic_asset.mint.as_ref())?;
+            None
+        },
+    };
+
+    Ok(AssetSwap {
+        program_id: spl_token_swap_program.key.into(),
+        swap: swap.key.into(),
+        intermediate,
+    })
+}
+
+/// Fees of a creation or a redemption of `fund_tokens` by a zap, as by `valuation::user_fees`.
+fn fund_token_fees(
+    pool_state: &PoolState,
+    fee_accounts: &FeeAccounts,
+    user_fund_token_account: &AccountInfo,
+    fund_tokens: u64,
+) -> Result<Fees, ProgramError> {
+    user_fees(
+        pool_state.fee_rate,
+        (
+            fee_accounts.lqd_fee_account.key,
+            fee_accounts.initializer_fee_account.key,
+            fee_accounts.referrer_fee_account.key,
+        ),
+        user_fund_token_account.key,
+        fund_tokens,
+    )
+}
+
+fn check_no_rebalance_plan(fund_state: &FundState) -> ProgramResult {
+    if fund_state.rebalance_plan.is_some() {
+        msg!("Rebalance in progress");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+/// Checks that every asset has a price oracle, for the instructions trading at the oracle prices only.
+fn check_asset_oracles(fund_state: &FundState) -> ProgramResult {
+    if let Some(index) = fund_state.asset_oracles.iter().position(Option::is_none) {
+        msg!("Asset {} has no price oracle", index);
+        return Err(FundError::MissingAssetOracle.into());
+    }
+    Ok(())
+}
+
+/// Checks the rebalance cooldown and records the rebalance time.
+fn record_rebalance(fund_state: &mut FundState, clock: &Clock) -> ProgramResult {
+    let elapsed = clock.unix_timestamp.saturating_sub(fund_state.last_rebalance_timestamp);
+    if elapsed < fund_state.rebalance_cooldown {
+        msg!("{} seconds since the last rebalance", elapsed);
+        return Err(FundError::RebalanceCooldown.into());
+    }
+    fund_state.last_rebalance_slot = clock.slot;
+    fund_state.last_rebalance_timestamp = clock.unix_timestamp;
+    Ok(())
+}
+
+fn check_bps(value: u16, name: &str) -> Result<(), ProgramError> {
+    if value > BPS_DENOMINATOR {
+        msg!("{} must not exceed {} bps", name, BPS_DENOMINATOR);
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(())
+}
+
+pub fn parse_token_account(account_info: &AccountInfo) -> Result<TokenAccount, ProgramError> {
+    if account_info.owner != &spl_token::ID {
+        msg!("Account not owned by spl-token program");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let parsed = TokenAccount::unpack(&account_info.try_borrow_data()?)?;
+    if !parsed.is_initialized() {
+        msg!("Token account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(parsed)
+}
+
+fn parse_mint(account_info: &AccountInfo) -> Result<Mint, ProgramError> {
+    if account_info.owner != &spl_token::ID {
+        msg!("Account not owned by spl-token program");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Mint::unpack(&account_info.try_borrow_data()?)
+}
+
+/// Accounts of the `Rebalance` instruction after the admin account.
+struct RebalanceAccounts<'a, 'b> {
+    pool_vaults: &'a [AccountInfo<'b>],
+    venues: Vec<Box<dyn SwapVenue<'a, 'b> + 'a>>,
+    price_accounts: &'a [AccountInfo<'b>],
+    swap_context: SwapContext<'a, 'b>,
+    clock: Clock,
+}
+
+impl<'a, 'b: 'a> RebalanceAccounts<'a, 'b> {
+    fn next(
+        pool_account: &'a AccountInfo<'b>,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        pool_state: &PoolState,
+        fund_state: &FundState,
+    ) -> Result<Self, ProgramError> {
+        let assets_count = pool_state.assets.len();
+        let pool_vaults = next_account_infos(accounts_iter, assets_count)?;
+        let vault_signer = next_account_info(accounts_iter)?;
+        let basic_asset_vault = next_account_info(accounts_iter)?;
+        let spl_token_program = next_account_info(accounts_iter)?;
+        let venue_programs = VenuePrograms::next(accounts_iter, fund_state.asset_markets.iter().any(Option::is_some))?;
+
+        // Check the accounts
+        check_account_address(vault_signer, &pool_state.vault_signer, stringify!(vault_signer))?;
+        check_token_account(
+            basic_asset_vault,
+            &fund_state.basic_asset.mint,
+            Some(&pool_state.vault_signer),
+        )?;
+        if spl_token_program.key != &spl_token::ID {
+            msg!("Incorrect spl-token program ID");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let mut venues = Vec::with_capacity(assets_count);
+        for (i, (asset, asset_vault)) in pool_state.assets.iter().zip(pool_vaults).enumerate() {
+            msg!("Check accounts for asset number {}", i);
+            check_account_address(asset_vault, &asset.vault_address, stringify!(asset_vault))?;
+            check_token_account(asset_vault, &asset.mint, Some(&pool_state.vault_signer))?;
+            venues.push(venue_programs.next_venue(accounts_iter, fund_state, i, &asset.mint)?);
+        }
+
+        let price_accounts = next_account_infos(accounts_iter, fund_state.asset_oracles.iter().flatten().count())?;
+        let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+
+        Ok(Self {
+            pool_vaults,
+            venues,
+            price_accounts,
+            swap_context: SwapContext {
+                pool_account,
+                vault_signer,
+                vault_signer_nonce: pool_state.vault_signer_nonce,
+                basic_asset_vault,
+                spl_token_program,
+            },
+            clock,
+        })
+    }
+
+    fn valuation(&self, fund_state: &FundState) -> Result<Valuation, ProgramError> {
+        let mut price_accounts_iter = self.price_accounts.iter();
+        let exchange_rates = self
+            .venues
+            .iter()
+            .enumerate()
+            .map(|(i, venue)| asset_exchange_rate(fund_state, i, venue.quote()?, &mut price_accounts_iter, &self.clock))
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+        self.value_at(fund_state, exchange_rates)
+    }
+
+    /// Values the fund at the asset `exchange_rates`, with the tokens held by the venues, e.g. in the intermediate
+    /// vaults of two-hop routes, counted with the basic asset.
+    fn value_at(&self, fund_state: &FundState, exchange_rates: Vec<ExchangeRate>) -> Result<Valuation, ProgramError> {
+        let held_value = self.venues.iter().try_fold(0_u64, |sum, venue| {
+            sum.checked_add(venue.held_value()?)
+                .ok_or_else(|| ProgramError::from(FundError::OperationOverflow))
+        })?;
+        value_fund(
+            fund_state,
+            self.pool_vaults,
+            self.swap_context.basic_asset_vault,
+            held_value,
+            exchange_rates,
+        )
+    }
+}
+
+/// Exchange rate of the asset `index`, from its oracle if there is one, otherwise the `venue_rate` quoted by its swap
+/// venue. The oracle price account is the next one of `price_accounts_iter`, its price is checked against the
+/// `clock` slot.
+fn asset_exchange_rate<'a, 'b: 'a>(
+    fund_state: &FundState,
+    index: usize,
+    venue_rate: ExchangeRate,
+    price_accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    clock: &Clock,
+) -> Result<ExchangeRate, ProgramError> {
+    let asset_oracle = match &fund_state.asset_oracles[index] {
+        Some(asset_oracle) => asset_oracle,
+        None => return Ok(venue_rate),
+    };
+    let price = next_oracle_price(price_accounts_iter, asset_oracle)?;
+    oracle_exchange_rate(
+        &price,
+        asset_oracle,
+        &venue_rate,
+        fund_state.max_oracle_deviation_bps,
+        clock.slot,
+    )
+    .map_err(|err| {
+        msg!("Oracle price check error for asset {}: {}", index, err);
+        err
+    })
+}
+
+/// Reads and checks the price account of the `asset_oracle`, the next one of `price_accounts_iter`.
+fn next_oracle_price<'a, 'b: 'a>(
+    price_accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    asset_oracle: &AssetOracle,
+) -> Result<Price, ProgramError> {
+    let price_account = next_account_info(price_accounts_iter)?;
+    check_account_address(price_account, &asset_oracle.price_account, stringify!(price_account))?;
+    if price_account.owner != asset_oracle.program_id.as_ref() {
+        msg!("Price account not owned by oracle program");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Price::unpack(&price_account.try_borrow_data()?)
+}
+
+/// Admin account of the fund, the next account, which must sign the request.
+fn next_admin_account<'a, 'b>(
+    accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    pool_state: &PoolState,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    let admin_account = next_account_info(accounts_iter)?;
+    if pool_state.admin_key.as_ref().map(AsRef::as_ref) != Some(admin_account.key) {
+        msg!("Incorrect admin account");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if !admin_account.is_signer {
+        msg!("Admin account not signer");
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    Ok(admin_account)
+}
+
+/// Fund vault of basic asset with its balance, and user basic asset account of a creation or a redemption, the first
+/// additional accounts of the request. The user account may be omitted if the fund holds no basic asset, i.e. its basic
+/// asset weight is zero and the vault is empty.
+fn basic_asset_accounts<'a, 'b>(
+    context: &PoolContext<'a, 'b>,
+    fund_state: &FundState,
+) -> Result<(&'a AccountInfo<'b>, u64, Option<&'a AccountInfo<'b>>), ProgramError> {
+    let accounts_iter = &mut context.custom_accounts.iter();
+    let basic_asset_vault = next_account_info(accounts_iter)?;
+    check_account_address(
+        basic_asset_vault,
+        &fund_state.basic_asset.vault_address,
+        stringify!(basic_asset_vault),
+    )?;
+    let basic_asset_balance = parse_token_account(basic_asset_vault)?.amount;
+
+    let user_basic_asset_account = match accounts_iter.next() {
+        Some(user_basic_asset_account) => {
+            check_token_account(user_basic_asset_account, &fund_state.basic_asset.mint, None)?;
+            Some(user_basic_asset_account)
+        },
+        None if fund_state.basic_asset_weight == 0 && basic_asset_balance == 0 => None,
+        None => {
+            msg!("User basic asset account is required while the fund holds basic asset");
+            return Err(ProgramError::NotEnoughAccountKeys);
+        },
+    };
+    Ok((basic_asset_vault, basic_asset_balance, user_basic_asset_account))
+}
+
+/// Values the fund for a swap from the pool vaults and the additional accounts of `PoolAction::Swap`, at the oracle
+/// prices only.
+fn swap_valuation(
+    context: &PoolContext,
+    pool_state: &PoolState,
+    fund_state: &FundState,
+) -> Result<Valuation, ProgramError> {
+    check_asset_oracles(fund_state)?;
+
+    let accounts_iter = &mut context.custom_accounts.iter();
+    let basic_asset_vault = next_account_info(accounts_iter)?;
+    let clock = Clock::from_account_info(next_account_info(accounts_iter)?)?;
+    check_account_address(
+        basic_asset_vault,
+        &fund_state.basic_asset.vault_address,
+        stringify!(basic_asset_vault),
+    )?;
+
+    let mut exchange_rates = Vec::with_capacity(pool_state.assets.len());
+    for (i, asset_oracle) in fund_state.asset_oracles.iter().flatten().enumerate() {
+        let price = next_oracle_price(accounts_iter, asset_oracle)?;
+        exchange_rates.push(checked_oracle_rate(&price, asset_oracle, clock.slot).map_err(|err| {
+            msg!("Oracle price check error for asset {}: {}", i, err);
+            err
+        })?);
+    }
+
+    value_fund(
+        fund_state,
+        context.pool_vault_accounts,
+        basic_asset_vault,
+        0,
+        exchange_rates,
+    )
 }
 
-struct SwapAccounts<'a, 'b> {
-    swap: &'a AccountInfo<'b>,
-    authority: &'a AccountInfo<'b>,
-    asset: &'a AccountInfo<'b>,
-    basic_asset: &'a AccountInfo<'b>,
-    pool_token_mint: &'a AccountInfo<'b>,
-    fee: &'a AccountInfo<'b>,
+/// Values the fund asset vaults and the basic asset vault at the asset exchange rates, the `held_value` of the fund
+/// tokens outside the vaults is counted with the basic asset balance.
+fn value_fund(
+    fund_state: &FundState,
+    pool_vaults: &[AccountInfo],
+    basic_asset_vault: &AccountInfo,
+    held_value: u64,
+    exchange_rates: Vec<ExchangeRate>,
+) -> Result<Valuation, ProgramError> {
+    let balances = pool_vaults
+        .iter()
+        .map(|vault| Ok(TokenAccount::unpack(&vault.try_borrow_data()?)?.amount))
+        .collect::<Result<Vec<_>, ProgramError>>()?;
+    let basic_asset_balance = TokenAccount::unpack(&basic_asset_vault.try_borrow_data()?)?
+        .amount
+        .checked_add(held_value)
+        .ok_or(FundError::OperationOverflow)?;
+    Valuation::new(
+        &fund_state.asset_weights,
+        fund_state.basic_asset_weight,
+        &balances,
+        basic_asset_balance,
+        exchange_rates,
+    )
 }
@@ -1,15 +1,233 @@
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
-use serum_pool::schema::{AssetInfo, PoolState};
-use solana_program::{msg, program_error::ProgramError};
+use serum_pool::schema::{declare_tag, Address, AssetInfo, PoolState};
+use solana_program::{
+    clock::{Slot, UnixTimestamp},
+    msg,
+    program_error::ProgramError,
+};
+
+use crate::price::ExchangeRate;
+
+/// Denominator of the values expressed in basis points.
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+declare_tag!(FundStateTag, u32, 0x5bd2_f07c);
 
 #[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
 pub struct FundState {
+    pub tag: FundStateTag,
+    pub paused: bool,
+    /// Deviation of an asset value from its target, in basis points, before a rebalance trades the asset.
+    pub rebalance_threshold_bps: u16,
+    /// Maximum price impact accepted by the rebalance swaps, in basis points.
+    pub max_slippage_bps: u16,
+    /// Maximum deviation of a swap venue price from the oracle price, in basis points, before a rebalance is aborted.
+    pub max_oracle_deviation_bps: u16,
+    /// Maximum decrease of the fund value by a rebalance, in basis points, valued at the exchange rates before the
+    /// trades.
+    pub max_nav_loss_bps: u16,
+    /// Largest value, in the basic asset, of the trade of an asset by one `FundInstructionInner::RebalanceAssets` or
+    /// `FundInstructionInner::CrankRebalanceAssets`, zero for no limit. A larger trade is executed in slices by the
+    /// successive instructions.
+    pub max_slice_value: u64,
+    pub asset_weights: Vec<u32>,
+    /// Target weight of the basic asset held in the fund vault of basic asset, relative to `asset_weights`. The
+    /// rebalance keeps this share of the fund value in the basic asset, and invests the rest of it in the assets.
+    pub basic_asset_weight: u32,
+    /// Token-swap pool approved for the rebalance trades, one per asset in `PoolState::assets`.
+    pub asset_swaps: Vec<AssetSwap>,
+    /// Optional price oracle valuing the asset, one per asset in `PoolState::assets`. The swap venue price is used for
+    /// the assets without an oracle.
+    pub asset_oracles: Vec<Option<AssetOracle>>,
+    /// Optional Serum DEX market, the swap venue of the asset instead of its token-swap route, one per asset in
+    /// `PoolState::assets`.
+    pub asset_markets: Vec<Option<AssetMarket>>,
+    /// Permissionless rebalance parameters.
+    pub crank: CrankConfig,
+    /// Minimum seconds between two rebalances.
+    pub rebalance_cooldown: UnixTimestamp,
+    pub last_rebalance_slot: Slot,
+    pub last_rebalance_timestamp: UnixTimestamp,
+    /// Rebalance split across several `FundInstructionInner::RebalanceAssets` instructions, creations and redemptions
+    /// are blocked until it is finished.
+    pub rebalance_plan: Option<RebalancePlan>,
+    /// Basic asset of the rebalance trades. The fund vault of basic asset is part of the fund value, and its share is
+    /// transferred by creations and redemptions along with the pool assets.
+    pub basic_asset: AssetInfo,
+}
+
+/// Rebalance trades fixed by `FundInstructionInner::StartRebalance`.
+#[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub struct RebalancePlan {
+    /// Target amount of each asset in its own units, by the fund value at the start of the rebalance.
+    pub target_amounts: Vec<u64>,
+    /// Exchange rate of each asset at the start of the rebalance, pricing the trades of all the slices.
+    pub exchange_rates: Vec<ExchangeRate>,
+    /// Trade left for each asset, all the sells go before the buys.
+    pub pending_trades: Vec<PendingTrade>,
+    /// Slot of the last `FundInstructionInner::CrankRebalanceAssets`, the keepers execute one slice per slot.
+    pub last_keeper_slot: Option<Slot>,
+}
+
+impl RebalancePlan {
+    pub fn is_finished(&self) -> bool {
+        self.pending_trades.iter().all(|trade| *trade == PendingTrade::None)
+    }
+
+    pub fn has_pending_sells(&self) -> bool {
+        self.pending_trades.contains(&PendingTrade::Sell)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum PendingTrade {
+    None,
+    Sell,
+    Buy,
+}
+
+impl Default for PendingTrade {
+    fn default() -> Self {
+        PendingTrade::None
+    }
+}
+
+/// Rebalance trade of an asset reported by `FundInstructionInner::SimulateRebalance`.
+#[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub struct PlannedTrade {
+    /// Value of the asset balance in the basic asset.
+    pub current_value: u64,
+    /// Value of the asset by its target weight in the basic asset.
+    pub target_value: u64,
+    pub direction: PendingTrade,
+    /// Asset amount sold, or basic asset amount spent on a buy, zero without a trade.
+    pub amount_in: u64,
+    /// Basic asset amount received by a sell, or asset amount received by a buy, at the maximum slippage.
+    pub minimum_amount_out: u64,
+}
+
+/// Conditions of the permissionless rebalance and the keeper reward for it.
+#[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub struct CrankConfig {
+    pub enabled: bool,
+    /// Seconds since the last rebalance after which the fund may be rebalanced whatever its drift, must be positive.
+    pub min_interval: UnixTimestamp,
+    /// Largest deviation of an asset value from its target, in basis points of the fund value, above which the fund
+    /// may be rebalanced before `min_interval` has elapsed.
+    pub drift_threshold_bps: u16,
+    pub reward: KeeperReward,
+}
+
+/// Reward paid to the caller of a crank rebalance.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema)]
+pub enum KeeperReward {
+    None,
+    /// Transferred from the fund vault of basic asset, as part of the fund value lost by the rebalance.
+    BasicAsset {
+        amount: u64,
+    },
+    /// Newly minted fund tokens.
+    FundTokens {
+        amount: u64,
+    },
+}
+
+impl Default for KeeperReward {
+    fn default() -> Self {
+        KeeperReward::None
+    }
+}
+
+/// Token-swap route of an asset to the basic asset.
+#[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub struct AssetSwap {
+    /// spl-token-swap program ID, the owner of the token-swap accounts of the route.
+    pub program_id: Address,
+    /// Token-swap account of the asset and the basic asset, or of the asset and the intermediate token of a two-hop
+    /// route.
+    pub swap: Address,
+    /// Second hop of a route through an intermediate token, for the assets without a basic asset pool.
+    pub intermediate: Option<IntermediateHop>,
+}
+
+/// Second hop of an asset route, trading the intermediate token against the basic asset.
+#[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub struct IntermediateHop {
+    /// Token-swap account of the intermediate token and the basic asset.
+    pub swap: Address,
+    /// Fund account of the intermediate token, owned by the pool vault signer, passing the tokens between the hops.
+    pub vault: Address,
+    /// Intermediate token mint.
+    pub mint: Address,
+}
+
+/// Price oracle of an asset quoted in the basic asset.
+#[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub struct AssetOracle {
+    /// Oracle program ID, the owner of the `price_account`.
+    pub program_id: Address,
+    /// Pyth-style price account, see `crate::oracle::Price`.
+    pub price_account: Address,
+    /// Decimals of the asset mint.
+    pub asset_decimals: u8,
+    /// Decimals of the basic asset mint.
+    pub basic_asset_decimals: u8,
+    /// Maximum slots since the price was published before it is rejected as stale.
+    pub max_price_age: Slot,
+    /// Maximum confidence interval of the price, in basis points of the price, before it is rejected.
+    pub max_confidence_bps: u16,
+}
+
+/// Serum DEX market of an asset, the base token, and the basic asset, the quote token.
+#[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub struct AssetMarket {
+    /// Serum DEX program ID, the owner of the `market` account.
+    pub program_id: Address,
+    pub market: Address,
+    /// Open orders account of the fund on the market, owned by the pool vault signer.
+    pub open_orders: Address,
+}
+
+/// `FundState` layout before the state tag was introduced.
+///
+/// Such a state is migrated to the current layout on the first write, so the pool account must have room for it, or
+/// the fund is moved to a larger account by `FundInstructionInner::MigrateFund`. The migrated state has no approved
+/// swap pools, they must be set with `FundInstructionInner::UpdateAssetSwap`, and no price oracles or DEX markets.
+#[derive(Clone, PartialEq, Eq, Debug, BorshSerialize, BorshDeserialize, BorshSchema, Default)]
+pub struct FundStateV1 {
     pub paused: bool,
     pub slippage_divider: u64,
     pub asset_weights: Vec<u32>,
     pub basic_asset: AssetInfo,
 }
 
+impl From<FundStateV1> for FundState {
+    fn from(state: FundStateV1) -> Self {
+        // The divider was used for both the dead-band and the swaps slippage, e.g. 100 is 1% or 100 bps
+        let bps = (BPS_DENOMINATOR as u64 / state.slippage_divider.max(1)).max(1) as u16;
+        Self {
+            tag: Default::default(),
+            paused: state.paused,
+            rebalance_threshold_bps: bps,
+            max_slippage_bps: bps,
+            max_oracle_deviation_bps: 0,
+            max_nav_loss_bps: bps,
+            max_slice_value: 0,
+            asset_swaps: vec![Default::default(); state.asset_weights.len()],
+            asset_oracles: vec![None; state.asset_weights.len()],
+            asset_markets: vec![None; state.asset_weights.len()],
+            crank: Default::default(),
+            rebalance_cooldown: 0,
+            last_rebalance_slot: 0,
+            last_rebalance_timestamp: 0,
+            rebalance_plan: None,
+            asset_weights: state.asset_weights,
+            basic_asset_weight: 0,
+            basic_asset: state.basic_asset,
+        }
+    }
+}
+
 pub trait FundStateContainer {
     fn read_fund_state(&self) -> Result<FundState, ProgramError>;
     fn write_fund_state(&mut self, custom_state: &FundState) -> Result<(), ProgramError>;
@@ -17,7 +235,12 @@ pub trait FundStateContainer {
 
 impl FundStateContainer for PoolState {
     fn read_fund_state(&self) -> Result<FundState, ProgramError> {
-        FundState::try_from_slice(&self.custom_state).map_err(|_| {
+        let state = if self.custom_state.starts_with(&FundStateTag::TAG_VALUE.to_le_bytes()) {
+            FundState::try_from_slice(&self.custom_state)
+        } else {
+            FundStateV1::try_from_slice(&self.custom_state).map(Into::into)
+        };
+        state.map_err(|_| {
             msg!("Invalid fund state");
             ProgramError::InvalidAccountData
         })
@@ -46,11 +269,99 @@ pub fn calc_len(name: impl Into<String>, assets_count: usize) -> usize {
     };
     state
         .write_fund_state(&FundState {
+            tag: Default::default(),
             paused: false,
-            slippage_divider: 100,
+            rebalance_threshold_bps: 100,
+            max_slippage_bps: 100,
+            max_oracle_deviation_bps: 100,
+            max_nav_loss_bps: 100,
+            max_slice_value: 0,
             asset_weights: vec![1; assets_count],
+            basic_asset_weight: 0,
+            asset_swaps: vec![
+                AssetSwap {
+                    intermediate: Some(Default::default()),
+                    ..Default::default()
+                };
+                assets_count
+            ],
+            asset_oracles: vec![Some(Default::default()); assets_count],
+            asset_markets: vec![Some(Default::default()); assets_count],
+            crank: CrankConfig {
+                reward: KeeperReward::BasicAsset { amount: 0 },
+                ..Default::default()
+            },
+            rebalance_cooldown: 0,
+            last_rebalance_slot: 0,
+            last_rebalance_timestamp: 0,
+            rebalance_plan: Some(RebalancePlan {
+                target_amounts: vec![0; assets_count],
+                exchange_rates: vec![Default::default(); assets_count],
+                pending_trades: vec![Default::default(); assets_count],
+                last_keeper_slot: Some(0),
+            }),
             basic_asset: Default::default(),
         })
         .expect("FundState should be writeable");
     state.try_to_vec().expect("PoolState should be serializable").len()
 }
+
+#[cfg(test)]
+mod tests {
+    use solana_program::pubkey::Pubkey;
+
+    use super::*;
+
+    fn pool_state(custom_state: Vec<u8>) -> PoolState {
+        PoolState {
+            tag: Default::default(),
+            pool_token_mint: Default::default(),
+            assets: vec![Default::default(); 2],
+            vault_signer: Default::default(),
+            vault_signer_nonce: 1,
+            account_params: vec![],
+            name: "fund".to_string(),
+            lqd_fee_vault: Default::default(),
+            initializer_fee_vault: Default::default(),
+            fee_rate: 0,
+            admin_key: Some(Default::default()),
+            custom_state,
+        }
+    }
+
+    #[test]
+    fn test_read_untagged_fund_state() {
+        // Baseline layout: paused, slippage divider, asset weights and basic asset
+        let basic_asset = AssetInfo {
+            mint: Pubkey::new_from_array([1; 32]).into(),
+            vault_address: Pubkey::new_from_array([2; 32]).into(),
+        };
+        let mut custom_state = vec![1];
+        custom_state.extend_from_slice(&50_u64.to_le_bytes());
+        custom_state.extend_from_slice(&2_u32.to_le_bytes());
+        custom_state.extend_from_slice(&3_u32.to_le_bytes());
+        custom_state.extend_from_slice(&7_u32.to_le_bytes());
+        custom_state.extend_from_slice(&[1; 32]);
+        custom_state.extend_from_slice(&[2; 32]);
+
+        let mut state = pool_state(custom_state);
+        let fund_state = state.read_fund_state().unwrap();
+        assert_eq!(fund_state, FundState {
+            paused: true,
+            rebalance_threshold_bps: 200,
+            max_slippage_bps: 200,
+            max_nav_loss_bps: 200,
+            asset_weights: vec![3, 7],
+            asset_swaps: vec![Default::default(); 2],
+            asset_oracles: vec![None; 2],
+            asset_markets: vec![None; 2],
+            basic_asset,
+            ..Default::default()
+        });
+
+        // The next write migrates it to the tagged layout
+        state.write_fund_state(&fund_state).unwrap();
+        assert!(state.custom_state.starts_with(&FundStateTag::TAG_VALUE.to_le_bytes()));
+        assert_eq!(state.read_fund_state().unwrap(), fund_state);
+    }
+}
@@ -0,0 +1,477 @@
+use std::ops::Deref;
+
+use serum_pool::context::check_account_address;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::invoke_signed,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::Account as TokenAccount;
+use spl_token_swap::state::{SwapState, SwapVersion};
+
+use crate::{
+    price::ExchangeRate,
+    processor::parse_token_account,
+    state::{AssetSwap, FundState},
+    venue::{SwapContext, SwapVenue, VenuePrograms},
+};
+
+/// Checks that the token-swap account is the one approved for the asset.
+pub fn check_asset_swap(
+    asset_swap: &AssetSwap,
+    swap: &AccountInfo,
+    spl_token_swap_program: &AccountInfo,
+) -> Result<(), ProgramError> {
+    if spl_token_swap_program.key != asset_swap.program_id.as_ref() {
+        msg!(
+            "Token-swap program {} not approved for the asset",
+            spl_token_swap_program.key
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    check_account_address(swap, &asset_swap.swap, stringify!(swap))?;
+    if swap.owner != spl_token_swap_program.key {
+        msg!("Token-swap account not owned by spl-token-swap program");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+fn unpack_swap_state(swap: &AccountInfo) -> Result<impl Deref<Target = dyn SwapState>, ProgramError> {
+    let swap_state = SwapVersion::unpack(&swap.try_borrow_data()?).map_err(|err| {
+        msg!("Token-swap state unpack error: {}", err);
+        err
+    })?;
+    if !swap_state.is_initialized() {
+        msg!("Token-swap account not initialized");
+        return Err(ProgramError::UninitializedAccount);
+    }
+    Ok(swap_state)
+}
+
+/// Reserve account of the pool for `mint`.
+fn pool_reserve<'a>(swap_state: &'a dyn SwapState, mint: &Pubkey) -> Result<&'a Pubkey, ProgramError> {
+    if swap_state.token_a_mint() == mint {
+        Ok(swap_state.token_a_account())
+    } else if swap_state.token_b_mint() == mint {
+        Ok(swap_state.token_b_account())
+    } else {
+        msg!("Token-swap pool does not trade the mint {}", mint);
+        Err(ProgramError::InvalidArgument)
+    }
+}
+
+fn check_token_program(swap_state: &dyn SwapState) -> ProgramResult {
+    if swap_state.token_program_id() != &spl_token::id() {
+        msg!("Token-swap pool does not use the spl-token program");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Checks that `swap_source` and `swap_destination` are the reserves of the pool for `source_mint` and
+/// `destination_mint`.
+fn check_reserves<'a>(
+    swap_state: &dyn SwapState,
+    swap_source: &AccountInfo<'a>,
+    swap_destination: &AccountInfo<'a>,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+) -> ProgramResult {
+    for &(reserve, mint) in &[(swap_source, source_mint), (swap_destination, destination_mint)] {
+        check_account_address(reserve, pool_reserve(swap_state, mint)?, stringify!(reserve))?;
+    }
+    Ok(())
+}
+
+/// Checks that the token-swap pool approved for a hop of an asset route is initialized, uses the spl-token program
+/// and trades `source_mint` against `destination_mint`, as `SwapAccounts::check_state` does for the trades.
+pub fn check_pool_mints(swap: &AccountInfo, source_mint: &Pubkey, destination_mint: &Pubkey) -> ProgramResult {
+    let swap_state = unpack_swap_state(swap)?;
+    check_token_program(&*swap_state)?;
+    pool_reserve(&*swap_state, source_mint)?;
+    pool_reserve(&*swap_state, destination_mint)?;
+    Ok(())
+}
+
+/// Token-swap accounts of a swap pool. For a hop of a two-hop route, `asset` and `basic_asset` are the swap accounts of
+/// the tokens sold and bought by a sell through the route.
+pub struct SwapAccounts<'a, 'b> {
+    pub swap: &'a AccountInfo<'b>,
+    pub authority: &'a AccountInfo<'b>,
+    pub asset: &'a AccountInfo<'b>,
+    pub basic_asset: &'a AccountInfo<'b>,
+    pub pool_token_mint: &'a AccountInfo<'b>,
+    pub fee: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> SwapAccounts<'a, 'b> {
+    pub fn next(accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>) -> Result<Self, ProgramError> {
+        Ok(Self {
+            swap: next_account_info(accounts_iter)?,
+            authority: next_account_info(accounts_iter)?,
+            asset: next_account_info(accounts_iter)?,
+            basic_asset: next_account_info(accounts_iter)?,
+            pool_token_mint: next_account_info(accounts_iter)?,
+            fee: next_account_info(accounts_iter)?,
+        })
+    }
+
+    /// Checks the accounts against the state of the swap pool, selling `source_mint` for `destination_mint`.
+    pub fn check_state(
+        &self,
+        spl_token_swap_program: &AccountInfo,
+        source_mint: &Pubkey,
+        destination_mint: &Pubkey,
+    ) -> ProgramResult {
+        let swap_state = unpack_swap_state(self.swap)?;
+        check_token_program(&*swap_state)?;
+        let authority = Pubkey::create_program_address(
+            &[self.swap.key.as_ref(), &[swap_state.nonce()]],
+            spl_token_swap_program.key,
+        )
+        .map_err(|_| ProgramError::InvalidAccountData)?;
+        check_account_address(self.authority, &authority, "swap_authority")?;
+        check_reserves(
+            &*swap_state,
+            self.asset,
+            self.basic_asset,
+            source_mint,
+            destination_mint,
+        )?;
+        check_account_address(self.pool_token_mint, swap_state.pool_mint(), "swap_pool_token_mint")?;
+        check_account_address(self.fee, swap_state.pool_fee_account(), "swap_fee")
+    }
+}
+
+/// Token-swap accounts of an asset route, see `AssetSwap`.
+pub struct RouteAccounts<'a, 'b> {
+    pub swap: SwapAccounts<'a, 'b>,
+    /// Fund vault of the intermediate token and the second hop accounts, for a two-hop route.
+    pub intermediate: Option<(&'a AccountInfo<'b>, SwapAccounts<'a, 'b>)>,
+}
+
+impl<'a, 'b> RouteAccounts<'a, 'b> {
+    pub fn next(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        asset_swap: &AssetSwap,
+    ) -> Result<Self, ProgramError> {
+        let swap = SwapAccounts::next(accounts_iter)?;
+        let intermediate = match asset_swap.intermediate {
+            Some(_) => Some((next_account_info(accounts_iter)?, SwapAccounts::next(accounts_iter)?)),
+            None => None,
+        };
+        Ok(Self { swap, intermediate })
+    }
+
+    /// Checks the accounts against the approved route of the asset.
+    pub fn check(
+        &self,
+        asset_swap: &AssetSwap,
+        asset_mint: &Pubkey,
+        basic_asset_mint: &Pubkey,
+        spl_token_swap_program: &AccountInfo,
+    ) -> ProgramResult {
+        check_asset_swap(asset_swap, self.swap.swap, spl_token_swap_program)?;
+
+        match (&asset_swap.intermediate, &self.intermediate) {
+            (Some(hop), Some((intermediate_vault, intermediate_swap))) => {
+                self.swap.check_state(spl_token_swap_program, asset_mint, &hop.mint)?;
+                check_account_address(intermediate_vault, &hop.vault, stringify!(intermediate_vault))?;
+                check_account_address(intermediate_swap.swap, &hop.swap, stringify!(intermediate_swap))?;
+                if intermediate_swap.swap.owner != spl_token_swap_program.key {
+                    msg!("Intermediate token-swap account not owned by spl-token-swap program");
+                    return Err(ProgramError::InvalidAccountData);
+                }
+                intermediate_swap.check_state(spl_token_swap_program, &hop.mint, basic_asset_mint)
+            },
+            (None, None) => self
+                .swap
+                .check_state(spl_token_swap_program, asset_mint, basic_asset_mint),
+            _ => {
+                msg!("Route accounts do not match the asset route");
+                Err(ProgramError::InvalidArgument)
+            },
+        }
+    }
+
+    /// Spot rate of the asset to the basic asset through the route pools.
+    pub fn pool_rate(&self) -> Result<ExchangeRate, ProgramError> {
+        let rate = reserves_rate(self.swap.asset, self.swap.basic_asset)?;
+        match &self.intermediate {
+            Some((_, intermediate_swap)) => {
+                rate.then(&reserves_rate(intermediate_swap.asset, intermediate_swap.basic_asset)?)
+            },
+            None => Ok(rate),
+        }
+    }
+}
+
+/// Spot rate of a token-swap pool from its accounts of the sold and the bought tokens.
+fn reserves_rate(swap_source: &AccountInfo, swap_destination: &AccountInfo) -> Result<ExchangeRate, ProgramError> {
+    let swap_source_token_account = TokenAccount::unpack(&swap_source.try_borrow_data()?)?;
+    let swap_destination_token_account = TokenAccount::unpack(&swap_destination.try_borrow_data()?)?;
+    ExchangeRate::from_reserves(swap_source_token_account.amount, swap_destination_token_account.amount)
+}
+
+/// Spot rate of a token-swap pool from its `StartRebalance` accounts, see `SwapVenue::next_quote`.
+fn next_hop_rate<'a, 'b: 'a>(
+    accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    swap_address: &Pubkey,
+    spl_token_swap_program_id: &Pubkey,
+    source_mint: &Pubkey,
+    destination_mint: &Pubkey,
+) -> Result<ExchangeRate, ProgramError> {
+    let swap = next_account_info(accounts_iter)?;
+    let swap_source = next_account_info(accounts_iter)?;
+    let swap_destination = next_account_info(accounts_iter)?;
+
+    check_account_address(swap, swap_address, stringify!(swap))?;
+    if swap.owner != spl_token_swap_program_id {
+        msg!("Token-swap account not owned by spl-token-swap program");
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let swap_state = unpack_swap_state(swap)?;
+    check_reserves(
+        &*swap_state,
+        swap_source,
+        swap_destination,
+        source_mint,
+        destination_mint,
+    )?;
+    reserves_rate(swap_source, swap_destination)
+}
+
+/// Minimum output of the first hop of a two-hop route, the route `minimum_amount_out` scaled by the spot outputs of the
+/// hop and of the whole route for the same input. The route minimum is then enforced by the second hop.
+fn first_hop_minimum(hop_amount_out: u64, route_amount_out: u64, minimum_amount_out: u64) -> u64 {
+    if route_amount_out == 0 {
+        return 0;
+    }
+    let minimum = hop_amount_out as u128 * minimum_amount_out as u128 / route_amount_out as u128;
+    minimum.min(hop_amount_out as u128) as u64
+}
+
+/// Token-swap pools of an asset route.
+pub struct TokenSwapVenue<'a, 'b> {
+    pub spl_token_swap_program: &'a AccountInfo<'b>,
+    pub route: RouteAccounts<'a, 'b>,
+}
+
+impl<'a, 'b> TokenSwapVenue<'a, 'b> {
+    /// Invokes the first hop of a two-hop route into the intermediate vault and returns the intermediate tokens
+    /// received, at least `minimum_amount_out`.
+    fn invoke_first_hop(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        swap: &SwapAccounts<'_, 'b>,
+        source: (&AccountInfo<'b>, &AccountInfo<'b>),
+        (swap_destination, intermediate_vault): (&AccountInfo<'b>, &AccountInfo<'b>),
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<u64, ProgramError> {
+        let balance = parse_token_account(intermediate_vault)?.amount;
+        self.invoke(
+            context,
+            swap,
+            source,
+            (swap_destination, intermediate_vault),
+            spl_token_swap::instruction::Swap {
+                amount_in,
+                minimum_amount_out,
+            },
+        )?;
+        Ok(parse_token_account(intermediate_vault)?.amount.saturating_sub(balance))
+    }
+
+    /// Invokes token-swap on behalf of the pool vault signer. The `source` and `destination` pairs are
+    /// `(fund account, swap account)` and `(swap account, fund account)` respectively.
+    fn invoke(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        swap: &SwapAccounts<'_, 'b>,
+        (source, swap_source): (&AccountInfo<'b>, &AccountInfo<'b>),
+        (swap_destination, destination): (&AccountInfo<'b>, &AccountInfo<'b>),
+        data: spl_token_swap::instruction::Swap,
+    ) -> ProgramResult {
+        let swap_instruction = spl_token_swap::instruction::swap(
+            self.spl_token_swap_program.key,
+            &spl_token::id(),
+            swap.swap.key,
+            swap.authority.key,
+            context.vault_signer.key,
+            source.key,
+            swap_source.key,
+            swap_destination.key,
+            destination.key,
+            swap.pool_token_mint.key,
+            swap.fee.key,
+            None,
+            data,
+        )
+        .map_err(|err| {
+            msg!("Create swap instruction error: {}", err);
+            err
+        })?;
+
+        let account_infos = vec![
+            swap.swap.clone(),
+            swap.authority.clone(),
+            context.vault_signer.clone(),
+            source.clone(),
+            swap_source.clone(),
+            swap_destination.clone(),
+            destination.clone(),
+            swap.pool_token_mint.clone(),
+            swap.fee.clone(),
+            context.spl_token_program.clone(),
+            self.spl_token_swap_program.clone(),
+        ];
+
+        invoke_signed(&swap_instruction, &account_infos, &[&context.signer_seeds()])
+    }
+}
+
+impl<'a, 'b> SwapVenue<'a, 'b> for TokenSwapVenue<'a, 'b> {
+    /// Reads the route accounts of the asset and checks them against its approved route.
+    fn next(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        programs: &VenuePrograms<'a, 'b>,
+        fund_state: &FundState,
+        index: usize,
+        asset_mint: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let asset_swap = &fund_state.asset_swaps[index];
+        let route = RouteAccounts::next(accounts_iter, asset_swap)?;
+        route.check(
+            asset_swap,
+            asset_mint,
+            &fund_state.basic_asset.mint,
+            programs.spl_token_swap_program,
+        )?;
+        Ok(Self {
+            spl_token_swap_program: programs.spl_token_swap_program,
+            route,
+        })
+    }
+
+    /// Spot rate of the asset route, for each hop the token-swap account followed by its accounts of the tokens sold
+    /// and bought by a sell through the route.
+    fn next_quote(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        fund_state: &FundState,
+        index: usize,
+        asset_mint: &Pubkey,
+    ) -> Result<ExchangeRate, ProgramError> {
+        let asset_swap = &fund_state.asset_swaps[index];
+        let basic_asset_mint = &fund_state.basic_asset.mint;
+        let program_id = &asset_swap.program_id;
+        let first_hop_mint = match &asset_swap.intermediate {
+            Some(hop) => &hop.mint,
+            None => basic_asset_mint,
+        };
+        let rate = next_hop_rate(accounts_iter, &asset_swap.swap, program_id, asset_mint, first_hop_mint)?;
+        match &asset_swap.intermediate {
+            Some(hop) => rate.then(&next_hop_rate(
+                accounts_iter,
+                &hop.swap,
+                program_id,
+                &hop.mint,
+                basic_asset_mint,
+            )?),
+            None => Ok(rate),
+        }
+    }
+
+    fn quote(&self) -> Result<ExchangeRate, ProgramError> {
+        self.route.pool_rate()
+    }
+
+    /// Intermediate tokens of a two-hop route valued at the spot rate of the second hop pool.
+    fn held_value(&self) -> Result<u64, ProgramError> {
+        match &self.route.intermediate {
+            Some((intermediate_vault, intermediate_swap)) => {
+                let balance = parse_token_account(intermediate_vault)?.amount;
+                reserves_rate(intermediate_swap.asset, intermediate_swap.basic_asset)?.to_basic_asset(balance)
+            },
+            None => Ok(0),
+        }
+    }
+
+    fn sell(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        asset_vault: &AccountInfo<'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let swap = &self.route.swap;
+        let (swap, source, amount_in) = match &self.route.intermediate {
+            Some((intermediate_vault, intermediate_swap)) => {
+                let hop_amount_out = reserves_rate(swap.asset, swap.basic_asset)?.to_basic_asset(amount_in)?;
+                let route_amount_out = self.route.pool_rate()?.to_basic_asset(amount_in)?;
+                let intermediate_amount = self.invoke_first_hop(
+                    context,
+                    swap,
+                    (asset_vault, swap.asset),
+                    (swap.basic_asset, intermediate_vault),
+                    amount_in,
+                    first_hop_minimum(hop_amount_out, route_amount_out, minimum_amount_out),
+                )?;
+                (intermediate_swap, *intermediate_vault, intermediate_amount)
+            },
+            None => (swap, asset_vault, amount_in),
+        };
+        self.invoke(
+            context,
+            swap,
+            (source, swap.asset),
+            (swap.basic_asset, context.basic_asset_vault),
+            spl_token_swap::instruction::Swap {
+                amount_in,
+                minimum_amount_out,
+            },
+        )
+    }
+
+    fn buy(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        asset_vault: &AccountInfo<'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let swap = &self.route.swap;
+        let (source, amount_in) = match &self.route.intermediate {
+            Some((intermediate_vault, intermediate_swap)) => {
+                let hop_amount_out =
+                    reserves_rate(intermediate_swap.basic_asset, intermediate_swap.asset)?.to_basic_asset(amount_in)?;
+                let route_amount_out = self.route.pool_rate()?.to_asset(amount_in)?;
+                let intermediate_amount = self.invoke_first_hop(
+                    context,
+                    intermediate_swap,
+                    (context.basic_asset_vault, intermediate_swap.basic_asset),
+                    (intermediate_swap.asset, intermediate_vault),
+                    amount_in,
+                    first_hop_minimum(hop_amount_out, route_amount_out, minimum_amount_out),
+                )?;
+                (*intermediate_vault, intermediate_amount)
+            },
+            None => (context.basic_asset_vault, amount_in),
+        };
+        self.invoke(
+            context,
+            swap,
+            (source, swap.basic_asset),
+            (swap.asset, asset_vault),
+            spl_token_swap::instruction::Swap {
+                amount_in,
+                minimum_amount_out,
+            },
+        )
+    }
+}
@@ -0,0 +1,700 @@
+use std::convert::TryFrom;
+
+use serum_pool::{context::Fees, schema::FEE_RATE_DENOMINATOR};
+use solana_program::{clock::Slot, entrypoint::ProgramResult, msg, program_error::ProgramError, pubkey::Pubkey};
+
+use crate::{
+    error::FundError,
+    oracle::Price,
+    price::ExchangeRate,
+    state::{AssetOracle, FundState, PendingTrade, PlannedTrade, BPS_DENOMINATOR},
+};
+
+/// Part of `amount` in basis points, never greater than `amount` for valid `bps`.
+pub fn bps_of(amount: u64, bps: u16) -> u64 {
+    (amount as u128 * bps as u128 / BPS_DENOMINATOR as u128) as u64
+}
+
+/// Exchange rate of an asset at its oracle `price`, unless the price was published more than
+/// `AssetOracle::max_price_age` slots before `current_slot` or its confidence interval exceeds
+/// `AssetOracle::max_confidence_bps` of it.
+pub fn checked_oracle_rate(
+    price: &Price,
+    asset_oracle: &AssetOracle,
+    current_slot: Slot,
+) -> Result<ExchangeRate, ProgramError> {
+    let oracle_rate = ExchangeRate::from_price(price, asset_oracle.asset_decimals, asset_oracle.basic_asset_decimals)?;
+
+    let age = current_slot.saturating_sub(price.pub_slot);
+    if age > asset_oracle.max_price_age {
+        msg!("Oracle price published {} slots ago", age);
+        return Err(FundError::OraclePriceStale.into());
+    }
+    if price.conf as u128 * BPS_DENOMINATOR as u128 > price.price as u128 * asset_oracle.max_confidence_bps as u128 {
+        msg!("Oracle price confidence {} for the price {}", price.conf, price.price);
+        return Err(FundError::OraclePriceUncertain.into());
+    }
+    Ok(oracle_rate)
+}
+
+/// `checked_oracle_rate` of an asset, unless its `venue_rate` deviates from the oracle price by more than
+/// `max_oracle_deviation_bps`.
+pub fn oracle_exchange_rate(
+    price: &Price,
+    asset_oracle: &AssetOracle,
+    venue_rate: &ExchangeRate,
+    max_oracle_deviation_bps: u16,
+    current_slot: Slot,
+) -> Result<ExchangeRate, ProgramError> {
+    let oracle_rate = checked_oracle_rate(price, asset_oracle, current_slot)?;
+
+    let deviation_bps = oracle_rate.deviation_bps(venue_rate)?;
+    if deviation_bps > max_oracle_deviation_bps as u128 {
+        msg!("Venue price deviates from the oracle price by {} bps", deviation_bps);
+        return Err(FundError::OraclePriceDeviation.into());
+    }
+    Ok(oracle_rate)
+}
+
+/// Splits `amount` of the basic asset by the `asset_weights`, rounded down. The share of the `basic_asset_weight` is
+/// left out.
+pub fn split_by_weights(amount: u64, asset_weights: &[u32], basic_asset_weight: u32) -> Result<Vec<u64>, ProgramError> {
+    let total_weight = total_weight(asset_weights, basic_asset_weight);
+    if total_weight == 0 {
+        msg!("Asset weights total is zero");
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(asset_weights
+        .iter()
+        .map(|&weight| (amount as u128 * weight as u128 / total_weight) as u64)
+        .collect())
+}
+
+/// Fund tokens worth `value` of the basic asset, for a fund of `total_amount` value with `fund_token_supply` tokens,
+/// rounded down.
+pub fn fund_tokens_for_value(fund_token_supply: u64, total_amount: u128, value: u128) -> Result<u64, ProgramError> {
+    if fund_token_supply == 0 || total_amount == 0 {
+        msg!("Fund is empty");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let tokens = value
+        .checked_mul(fund_token_supply as u128)
+        .ok_or(FundError::OperationOverflow)?
+        / total_amount;
+    u64::try_from(tokens).map_err(|err| {
+        msg!("Fund tokens overflow: {}", err);
+        FundError::OperationOverflow.into()
+    })
+}
+
+/// Shares of the vault `balances` redeemed by `fund_tokens` of the `fund_token_supply`, rounded down as by
+/// `PoolContext::get_simple_basket` for a redemption.
+pub fn redeemed_amounts(balances: &[u64], fund_tokens: u64, fund_token_supply: u64) -> Result<Vec<u64>, ProgramError> {
+    if fund_token_supply == 0 {
+        msg!("Fund is empty");
+        return Err(ProgramError::InvalidArgument);
+    }
+    if fund_tokens > fund_token_supply {
+        msg!("Redeemed fund tokens exceed the supply");
+        return Err(ProgramError::InvalidArgument);
+    }
+    // Never greater than the balance
+    Ok(balances
+        .iter()
+        .map(|&balance| (balance as u128 * fund_tokens as u128 / fund_token_supply as u128) as u64)
+        .collect())
+}
+
+/// Fees at `fee_rate` of a creation or a redemption of `fund_tokens` by the `user_fund_token_account`, to the
+/// `(lqd, initializer, referrer)` fee accounts. The fee accounts of the user are exempted as by
+/// `PoolContext::get_fees`.
+pub fn user_fees(
+    fee_rate: u32,
+    (lqd_fee_account, initializer_fee_account, referrer_fee_account): (&Pubkey, &Pubkey, &Pubkey),
+    user_fund_token_account: &Pubkey,
+    fund_tokens: u64,
+) -> Result<Fees, ProgramError> {
+    let mut fees = Fees::from_fee_rate_and_tokens(fee_rate, fund_tokens)?;
+    if lqd_fee_account == user_fund_token_account {
+        fees.lqd_fee = 0;
+        fees.initializer_fee = 0;
+        fees.referrer_fee = 0;
+    }
+    if initializer_fee_account == user_fund_token_account {
+        fees.initializer_fee = 0;
+    }
+    if referrer_fee_account == user_fund_token_account {
+        fees.referrer_fee = 0;
+    }
+    Ok(fees)
+}
+
+/// Share of the vault `balance` created by `fund_tokens` of the `fund_token_supply`, rounded up as by
+/// `PoolContext::get_simple_basket` for a creation.
+pub fn created_amount(balance: u64, fund_tokens: u64, fund_token_supply: u64) -> Result<u64, ProgramError> {
+    if fund_token_supply == 0 {
+        msg!("Fund is empty");
+        return Err(ProgramError::InvalidArgument);
+    }
+    let amount = (balance as u128 * fund_tokens as u128)
+        .checked_add(fund_token_supply as u128 - 1)
+        .ok_or(FundError::OperationOverflow)?
+        / fund_token_supply as u128;
+    u64::try_from(amount).map_err(|err| {
+        msg!("Created amount overflow: {}", err);
+        FundError::OperationOverflow.into()
+    })
+}
+
+/// Amount traded by one slice of a planned rebalance trade for an asset `amount_delta` away from its target amount, and
+/// whether the slice finishes the trade. Nothing is traded within the `band` around the target, and at most
+/// `max_slice_amount` otherwise.
+pub fn rebalance_slice(amount_delta: u64, band: u64, max_slice_amount: Option<u64>) -> (u64, bool) {
+    if amount_delta <= band {
+        return (0, true);
+    }
+    match max_slice_amount {
+        Some(max_slice_amount) if amount_delta > max_slice_amount => {
+            (max_slice_amount, amount_delta - max_slice_amount <= band)
+        },
+        _ => (amount_delta, true),
+    }
+}
+
+fn total_weight(asset_weights: &[u32], basic_asset_weight: u32) -> u128 {
+    asset_weights
+        .iter()
+        .fold(basic_asset_weight as u128, |sum, weight| sum + *weight as u128)
+}
+
+/// Basket of a swap of the `inputs` deposited for the `outputs` withdrawn, positive for the deposited amounts and
+/// negative for the withdrawn ones, see `instruction::swap_request`.
+pub fn swap_basket(
+    valuation: &Valuation,
+    inputs: &[u64],
+    outputs: &[u64],
+    fee_rate: u32,
+) -> Result<Vec<i64>, ProgramError> {
+    let assets_count = valuation.current_amounts.len();
+    if inputs.len() != assets_count || outputs.len() != assets_count {
+        msg!(
+            "Swap request must have the inputs and the outputs of {} assets",
+            assets_count
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut value_in = 0_u128;
+    let mut value_out = 0_u128;
+    let mut quantities = Vec::with_capacity(assets_count);
+    for i in 0..assets_count {
+        let (input, output) = (inputs[i], outputs[i]);
+        let exchange_rate = &valuation.exchange_rates[i];
+        let amount = valuation.current_amounts[i];
+        let need_amount = valuation.need_amounts[i];
+
+        if input > 0 && output > 0 {
+            msg!("Asset {} both deposited and withdrawn", i);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if input > 0 {
+            let value = exchange_rate.to_basic_asset(input)?;
+            if amount as u128 + value as u128 > need_amount as u128 {
+                msg!("Deposit of asset {} exceeds its target value", i);
+                return Err(ProgramError::InvalidArgument);
+            }
+            value_in += value as u128;
+        }
+        if output > 0 {
+            // Rounded up in favor of the fund
+            let value = exchange_rate.to_basic_asset(output)? as u128 + 1;
+            if (amount as u128) < value + need_amount as u128 {
+                msg!("Withdrawal of asset {} falls below its target value", i);
+                return Err(ProgramError::InvalidArgument);
+            }
+            value_out += value;
+        }
+
+        let quantity = input as i128 - output as i128;
+        quantities.push(i64::try_from(quantity).map_err(|err| {
+            msg!("Swap quantity overflow: {}", err);
+            ProgramError::from(FundError::OperationOverflow)
+        })?);
+    }
+
+    let fee = (value_in * fee_rate as u128)
+        .checked_add(FEE_RATE_DENOMINATOR as u128 - 1)
+        .ok_or(FundError::OperationOverflow)?
+        / FEE_RATE_DENOMINATOR as u128;
+    if value_out + fee > value_in {
+        msg!(
+            "Withdrawn value {} exceeds the deposited value {} less the fee {}",
+            value_out,
+            value_in,
+            fee
+        );
+        return Err(ProgramError::InvalidArgument);
+    }
+    Ok(quantities)
+}
+
+/// Plans the trades of a rebalance from the fund asset balances, the basic asset balance and the asset exchange rates,
+/// the same way as the `Rebalance` instruction.
+pub fn plan_rebalance(
+    fund_state: &FundState,
+    balances: &[u64],
+    basic_asset_balance: u64,
+    exchange_rates: Vec<ExchangeRate>,
+) -> Result<Vec<PlannedTrade>, ProgramError> {
+    let valuation = Valuation::new(
+        &fund_state.asset_weights,
+        fund_state.basic_asset_weight,
+        balances,
+        basic_asset_balance,
+        exchange_rates,
+    )?;
+    valuation.planned_trades(fund_state.rebalance_threshold_bps, fund_state.max_slippage_bps)
+}
+
+/// Values of the fund assets in the basic asset.
+pub struct Valuation {
+    pub exchange_rates: Vec<ExchangeRate>,
+    pub current_amounts: Vec<u64>,
+    /// Target values by the asset weights.
+    pub need_amounts: Vec<u64>,
+    /// Value of the fund including the basic asset vault.
+    pub total_amount: u128,
+}
+
+impl Valuation {
+    /// Values the asset `balances` at their `exchange_rates`, and splits the fund value by the `asset_weights`. The
+    /// share of the `basic_asset_weight` is kept in the basic asset.
+    pub fn new(
+        asset_weights: &[u32],
+        basic_asset_weight: u32,
+        balances: &[u64],
+        basic_asset_balance: u64,
+        exchange_rates: Vec<ExchangeRate>,
+    ) -> Result<Self, ProgramError> {
+        if asset_weights.len() != balances.len() || exchange_rates.len() != balances.len() {
+            msg!("Asset weights, balances and exchange rates counts do not match");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Calc the current amounts in the basic asset
+        let mut current_amounts = Vec::with_capacity(balances.len());
+        let mut total_amount = basic_asset_balance as u128;
+
+        for (balance, exchange_rate) in balances.iter().zip(exchange_rates.iter()) {
+            let amount = exchange_rate.to_basic_asset(*balance)?;
+
+            total_amount += amount as u128;
+            current_amounts.push(amount);
+        }
+
+        // Calc the needed amounts in the basic asset
+        let total_weight = total_weight(asset_weights, basic_asset_weight);
+        if total_weight == 0 {
+            msg!("Asset weights total is zero");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let need_amounts = asset_weights
+            .iter()
+            .map(|&weight| {
+                u64::try_from(weight as u128 * total_amount / total_weight).map_err(|err| {
+                    msg!("Need amount overflow: {}", err);
+                    FundError::OperationOverflow.into()
+                })
+            })
+            .collect::<Result<Vec<_>, ProgramError>>()?;
+
+        Ok(Self {
+            exchange_rates,
+            current_amounts,
+            need_amounts,
+            total_amount,
+        })
+    }
+
+    /// Values the asset `balances` at their `exchange_rates` as `new`, with the `target_amounts` of the assets, in
+    /// their own units, as their target values, e.g. the assets traded by a rebalance slice and their
+    /// `RebalancePlan` targets.
+    pub fn with_targets(
+        balances: &[u64],
+        target_amounts: &[u64],
+        basic_asset_balance: u64,
+        exchange_rates: Vec<ExchangeRate>,
+    ) -> Result<Self, ProgramError> {
+        if target_amounts.len() != balances.len() || exchange_rates.len() != balances.len() {
+            msg!("Target amounts, balances and exchange rates counts do not match");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut current_amounts = Vec::with_capacity(balances.len());
+        let mut need_amounts = Vec::with_capacity(balances.len());
+        let mut total_amount = basic_asset_balance as u128;
+        for ((&balance, &target_amount), exchange_rate) in
+            balances.iter().zip(target_amounts.iter()).zip(exchange_rates.iter())
+        {
+            let amount = exchange_rate.to_basic_asset(balance)?;
+
+            total_amount += amount as u128;
+            current_amounts.push(amount);
+            need_amounts.push(exchange_rate.to_basic_asset(target_amount)?);
+        }
+
+        Ok(Self {
+            exchange_rates,
+            current_amounts,
+            need_amounts,
+            total_amount,
+        })
+    }
+
+    /// Trade moving the asset `index` towards its target value, none within the rebalance threshold.
+    pub fn pending_trade(&self, index: usize, rebalance_threshold_bps: u16) -> PendingTrade {
+        let amount = self.current_amounts[index];
+        let need_amount = self.need_amounts[index];
+        if need_amount < amount - bps_of(amount, rebalance_threshold_bps) {
+            PendingTrade::Sell
+        } else if need_amount > amount + bps_of(amount, rebalance_threshold_bps) {
+            PendingTrade::Buy
+        } else {
+            PendingTrade::None
+        }
+    }
+
+    /// Trade of the asset `index` by a rebalance in a single instruction, with the amounts bounded by the maximum
+    /// slippage.
+    pub fn planned_trade(
+        &self,
+        index: usize,
+        rebalance_threshold_bps: u16,
+        max_slippage_bps: u16,
+    ) -> Result<PlannedTrade, ProgramError> {
+        let amount = self.current_amounts[index];
+        let need_amount = self.need_amounts[index];
+        let direction = self.pending_trade(index, rebalance_threshold_bps);
+
+        let (amount_in, minimum_amount_out) = match direction {
+            PendingTrade::Sell => {
+                let amount_delta = amount - need_amount;
+                let amount_in = self.exchange_rates[index].to_asset(amount_delta)?;
+                (amount_in, amount_delta - bps_of(amount_delta, max_slippage_bps))
+            },
+            PendingTrade::Buy => {
+                let amount_delta = need_amount - amount;
+                let asset_amount_delta = self.exchange_rates[index].to_asset(amount_delta)?;
+                (
+                    amount_delta,
+                    asset_amount_delta - bps_of(asset_amount_delta, max_slippage_bps),
+                )
+            },
+            PendingTrade::None => (0, 0),
+        };
+
+        Ok(PlannedTrade {
+            current_value: amount,
+            target_value: need_amount,
+            direction,
+            amount_in,
+            minimum_amount_out,
+        })
+    }
+
+    /// Trades of all the assets by a rebalance in a single instruction.
+    pub fn planned_trades(
+        &self,
+        rebalance_threshold_bps: u16,
+        max_slippage_bps: u16,
+    ) -> Result<Vec<PlannedTrade>, ProgramError> {
+        (0..self.current_amounts.len())
+            .map(|i| self.planned_trade(i, rebalance_threshold_bps, max_slippage_bps))
+            .collect()
+    }
+
+    /// Largest deviation of an asset value from its target, in basis points of the fund value.
+    pub fn max_drift_bps(&self) -> u128 {
+        if self.total_amount == 0 {
+            return 0;
+        }
+        self.current_amounts
+            .iter()
+            .zip(self.need_amounts.iter())
+            .map(|(&amount, &need_amount)| {
+                let drift = if amount > need_amount {
+                    amount - need_amount
+                } else {
+                    need_amount - amount
+                };
+                drift as u128 * BPS_DENOMINATOR as u128 / self.total_amount
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Checks the `outcome` of the rebalance trades, the fund valued again at the same exchange rates: the fund value
+    /// must not decrease by more than `max_nav_loss_bps`, and no asset may end further from its target value than it
+    /// started.
+    pub fn check_rebalance_outcome(&self, outcome: &Valuation, max_nav_loss_bps: u16) -> ProgramResult {
+        let nav_loss = self.total_amount.saturating_sub(outcome.total_amount);
+        if nav_loss * BPS_DENOMINATOR as u128 > self.total_amount * max_nav_loss_bps as u128 {
+            msg!(
+                "Rebalance decreased the fund value from {} to {}",
+                self.total_amount,
+                outcome.total_amount
+            );
+            return Err(FundError::RebalanceNavLoss.into());
+        }
+
+        let amounts = self.current_amounts.iter().zip(outcome.current_amounts.iter());
+        for (i, ((&amount, &new_amount), &need_amount)) in amounts.zip(self.need_amounts.iter()).enumerate() {
+            let drift = if amount > need_amount {
+                amount - need_amount
+            } else {
+                need_amount - amount
+            };
+            let new_drift = if new_amount > need_amount {
+                new_amount - need_amount
+            } else {
+                need_amount - new_amount
+            };
+            if new_drift > drift {
+                msg!("Asset {} value moved from {} to {}", i, amount, new_amount);
+                msg!("Asset {} target value is {}", i, need_amount);
+                return Err(FundError::RebalanceDrift.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fund_state(asset_weights: Vec<u32>, rebalance_threshold_bps: u16) -> FundState {
+        FundState {
+            asset_weights,
+            rebalance_threshold_bps,
+            max_slippage_bps: 100,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_plan_rebalance() {
+        // Asset 0 is worth 2 basic asset tokens and holds the whole fund value
+        let exchange_rates = vec![
+            ExchangeRate::from_reserves(1_000, 2_000).unwrap(),
+            ExchangeRate::from_reserves(1_000, 1_000).unwrap(),
+        ];
+        let trades = plan_rebalance(&fund_state(vec![1, 1], 0), &[100, 0], 0, exchange_rates).unwrap();
+        assert_eq!(trades, vec![
+            PlannedTrade {
+                current_value: 200,
+                target_value: 100,
+                direction: PendingTrade::Sell,
+                amount_in: 50,
+                minimum_amount_out: 99,
+            },
+            PlannedTrade {
+                current_value: 0,
+                target_value: 100,
+                direction: PendingTrade::Buy,
+                amount_in: 100,
+                minimum_amount_out: 99,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_plan_rebalance_from_empty_vaults() {
+        // A fund holding only the basic asset buys every asset at the pool rates
+        let exchange_rates = vec![
+            ExchangeRate::from_reserves(1_000, 2_000).unwrap(),
+            ExchangeRate::from_reserves(1_000, 1_000).unwrap(),
+        ];
+        let trades = plan_rebalance(&fund_state(vec![1, 3], 0), &[0, 0], 1_000, exchange_rates).unwrap();
+        assert_eq!(trades, vec![
+            PlannedTrade {
+                current_value: 0,
+                target_value: 250,
+                direction: PendingTrade::Buy,
+                amount_in: 250,
+                minimum_amount_out: 124,
+            },
+            PlannedTrade {
+                current_value: 0,
+                target_value: 750,
+                direction: PendingTrade::Buy,
+                amount_in: 750,
+                minimum_amount_out: 743,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_plan_rebalance_within_threshold() {
+        // The basic asset balance is split by the weights too
+        let exchange_rates = vec![ExchangeRate::from_reserves(1_000, 1_000).unwrap(); 2];
+        let trades = plan_rebalance(&fund_state(vec![1, 1], 500), &[1_000, 980], 20, exchange_rates).unwrap();
+        assert!(trades.iter().all(|trade| trade.direction == PendingTrade::None));
+        assert_eq!(trades[1], PlannedTrade {
+            current_value: 980,
+            target_value: 1_000,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn test_plan_rebalance_with_basic_asset_weight() {
+        // A quarter of the fund value is kept in the basic asset
+        let exchange_rates = vec![ExchangeRate::from_reserves(1_000, 1_000).unwrap(); 2];
+        let mut fund_state = fund_state(vec![1, 2], 0);
+        fund_state.basic_asset_weight = 1;
+        let trades = plan_rebalance(&fund_state, &[1_000, 1_000], 0, exchange_rates).unwrap();
+        assert_eq!(trades, vec![
+            PlannedTrade {
+                current_value: 1_000,
+                target_value: 500,
+                direction: PendingTrade::Sell,
+                amount_in: 500,
+                minimum_amount_out: 495,
+            },
+            PlannedTrade {
+                current_value: 1_000,
+                target_value: 1_000,
+                ..Default::default()
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_check_rebalance_outcome() {
+        // Asset 0 is above its target by 200 and asset 1 below it by 200
+        let exchange_rates = vec![ExchangeRate::from_reserves(1_000, 1_000).unwrap(); 2];
+        let valuation = Valuation::new(&[1, 1], 0, &[1_200, 800], 0, exchange_rates.clone()).unwrap();
+        let outcome = |balances: &[u64]| Valuation::new(&[1, 1], 0, balances, 0, exchange_rates.clone()).unwrap();
+
+        // 10 of 2_000 lost to the trades is 50 bps
+        assert!(valuation.check_rebalance_outcome(&outcome(&[1_000, 990]), 50).is_ok());
+        assert!(valuation.check_rebalance_outcome(&outcome(&[1_000, 990]), 49).is_err());
+        // Asset 0 overshot its target
+        assert!(valuation.check_rebalance_outcome(&outcome(&[790, 1_210]), 0).is_err());
+        assert!(valuation.check_rebalance_outcome(&outcome(&[1_200, 800]), 0).is_ok());
+    }
+
+    #[test]
+    fn test_valuation_with_targets() {
+        // Asset 0 is worth 2 basic asset tokens
+        let exchange_rates = vec![ExchangeRate::from_reserves(1_000, 2_000).unwrap()];
+        let valuation = Valuation::with_targets(&[100], &[60], 50, exchange_rates.clone()).unwrap();
+        assert_eq!(valuation.current_amounts, vec![200]);
+        assert_eq!(valuation.need_amounts, vec![120]);
+        assert_eq!(valuation.total_amount, 250);
+
+        // A sell overshooting the target drifts the asset away from it
+        let outcome = Valuation::with_targets(&[10], &[60], 230, exchange_rates.clone()).unwrap();
+        assert!(valuation.check_rebalance_outcome(&outcome, 10_000).is_err());
+        let outcome = Valuation::with_targets(&[60], &[60], 130, exchange_rates).unwrap();
+        assert!(valuation.check_rebalance_outcome(&outcome, 100).is_ok());
+        assert!(Valuation::with_targets(&[100], &[], 50, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_rebalance_slice() {
+        // Without a limit the whole delta is traded at once
+        assert_eq!(rebalance_slice(1_000, 10, None), (1_000, true));
+        assert_eq!(rebalance_slice(1_000, 10, Some(400)), (400, false));
+        assert_eq!(rebalance_slice(600, 10, Some(400)), (400, false));
+        // The rest is within the band
+        assert_eq!(rebalance_slice(405, 10, Some(400)), (400, true));
+        assert_eq!(rebalance_slice(200, 10, Some(400)), (200, true));
+        assert_eq!(rebalance_slice(10, 10, Some(400)), (0, true));
+    }
+
+    #[test]
+    fn test_split_by_weights() {
+        assert_eq!(split_by_weights(1_000, &[1, 2], 0).unwrap(), vec![333, 666]);
+        assert_eq!(split_by_weights(1_000, &[0, 1], 0).unwrap(), vec![0, 1_000]);
+        assert_eq!(split_by_weights(1_000, &[1, 2], 1).unwrap(), vec![250, 500]);
+        assert!(split_by_weights(1_000, &[0, 0], 0).is_err());
+    }
+
+    #[test]
+    fn test_fund_tokens_for_value() {
+        assert_eq!(fund_tokens_for_value(100, 1_000, 990).unwrap(), 99);
+        assert_eq!(fund_tokens_for_value(100, 1_000, 9).unwrap(), 0);
+        assert!(fund_tokens_for_value(0, 1_000, 1_000).is_err());
+        assert!(fund_tokens_for_value(100, 0, 1_000).is_err());
+        assert!(fund_tokens_for_value(u64::MAX, 1, 2).is_err());
+    }
+
+    #[test]
+    fn test_created_amount() {
+        assert_eq!(created_amount(770, 998, 1_100).unwrap(), 699);
+        assert_eq!(created_amount(0, 998, 1_100).unwrap(), 0);
+        assert!(created_amount(770, 1, 0).is_err());
+        assert!(created_amount(u64::MAX, 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_redeemed_amounts() {
+        assert_eq!(redeemed_amounts(&[770, 363], 998, 1_100).unwrap(), vec![698, 329]);
+        assert_eq!(redeemed_amounts(&[770, 0], 1_100, 1_100).unwrap(), vec![770, 0]);
+        assert!(redeemed_amounts(&[770], 1, 0).is_err());
+        assert!(redeemed_amounts(&[770], 1_101, 1_100).is_err());
+    }
+
+    #[test]
+    fn test_swap_basket() {
+        // Asset 0 is above its target by 100 and asset 1 below it by 100, both worth 1 basic asset token
+        let exchange_rates = vec![ExchangeRate::from_reserves(1_000, 1_000).unwrap(); 2];
+        let valuation = Valuation::new(&[1, 1], 0, &[600, 400], 0, exchange_rates).unwrap();
+        let fee_rate = 2_500;
+
+        // The fee is 0.25% of the deposited value and the withdrawn value is rounded up
+        assert_eq!(swap_basket(&valuation, &[0, 100], &[98, 0], fee_rate).unwrap(), vec![
+            -98, 100
+        ]);
+        assert!(swap_basket(&valuation, &[0, 100], &[99, 0], fee_rate).is_err());
+
+        // Away from the target weights
+        assert!(swap_basket(&valuation, &[10, 0], &[0, 9], fee_rate).is_err());
+        // Past the target weights
+        assert!(swap_basket(&valuation, &[0, 101], &[98, 0], fee_rate).is_err());
+        assert!(swap_basket(&valuation, &[0, 100], &[100, 0], 0).is_err());
+        // Both ways and incomplete requests
+        assert!(swap_basket(&valuation, &[1, 100], &[98, 0], fee_rate).is_err());
+        assert!(swap_basket(&valuation, &[0, 100], &[98], fee_rate).is_err());
+    }
+
+    #[test]
+    fn test_oracle_exchange_rate() {
+        let asset_oracle = AssetOracle {
+            asset_decimals: 6,
+            basic_asset_decimals: 6,
+            max_price_age: 10,
+            max_confidence_bps: 100,
+            ..Default::default()
+        };
+        let price = Price {
+            expo: -2,
+            price: 250,
+            conf: 2,
+            pub_slot: 100,
+            ..Default::default()
+        };
+        let venue_rate = ExchangeRate::from_reserves(1_000, 2_600).unwrap();
+
+        let rate = oracle_exchange_rate(&price, &asset_oracle, &venue_rate, 500, 110).unwrap();
+        assert_eq!(rate.to_basic_asset(100).unwrap(), 250);
+        assert!(oracle_exchange_rate(&price, &asset_oracle, &venue_rate, 100, 110).is_err());
+        // Published 11 slots ago
+        assert!(oracle_exchange_rate(&price, &asset_oracle, &venue_rate, 500, 111).is_err());
+        // The confidence interval is 120 bps of the price
+        let uncertain_price = Price { conf: 3, ..price };
+        assert!(oracle_exchange_rate(&uncertain_price, &asset_oracle, &venue_rate, 500, 110).is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    dex::{DexProgram, SerumDexVenue},
+    error::FundError,
+    price::ExchangeRate,
+    processor::parse_token_account,
+    state::FundState,
+    token_swap::TokenSwapVenue,
+};
+
+/// Venue trading a fund asset against the basic asset. The rebalance values and trades the assets through their
+/// venues only, a new kind of venue reads its own accounts and is selected by the fund state of the asset in
+/// `VenuePrograms::next_venue` and `next_venue_quote`.
+pub trait SwapVenue<'a, 'b> {
+    /// Reads and checks the `Rebalance` venue accounts of the asset `index`.
+    fn next(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        programs: &VenuePrograms<'a, 'b>,
+        fund_state: &FundState,
+        index: usize,
+        asset_mint: &Pubkey,
+    ) -> Result<Self, ProgramError>
+    where
+        Self: Sized;
+
+    /// Spot rate of the asset `index` from its `StartRebalance` venue accounts.
+    fn next_quote(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        fund_state: &FundState,
+        index: usize,
+        asset_mint: &Pubkey,
+    ) -> Result<ExchangeRate, ProgramError>
+    where
+        Self: Sized;
+
+    /// Spot rate of the asset to the basic asset on the venue.
+    fn quote(&self) -> Result<ExchangeRate, ProgramError>;
+
+    /// Value in the basic asset of the fund tokens the venue keeps outside the asset vault, e.g. the intermediate
+    /// tokens of a two-hop route.
+    fn held_value(&self) -> Result<u64, ProgramError> {
+        Ok(0)
+    }
+
+    /// Sells `amount_in` tokens of the asset vault for at least `minimum_amount_out` tokens of the basic asset.
+    fn sell(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        asset_vault: &AccountInfo<'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult;
+
+    /// Buys at least `minimum_amount_out` tokens of the asset for `amount_in` tokens of the basic asset vault.
+    fn buy(
+        &self,
+        context: &SwapContext<'_, 'b>,
+        asset_vault: &AccountInfo<'b>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult;
+}
+
+/// Fund accounts shared by the trades of a fund instruction.
+pub struct SwapContext<'a, 'b> {
+    pub pool_account: &'a AccountInfo<'b>,
+    pub vault_signer: &'a AccountInfo<'b>,
+    pub vault_signer_nonce: u8,
+    pub basic_asset_vault: &'a AccountInfo<'b>,
+    pub spl_token_program: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> SwapContext<'a, 'b> {
+    /// Seeds of the pool vault signer, the owner of the fund vaults.
+    pub fn signer_seeds(&self) -> [&[u8]; 2] {
+        [
+            self.pool_account.key.as_ref(),
+            std::slice::from_ref(&self.vault_signer_nonce),
+        ]
+    }
+}
+
+/// Programs of the venues traded by a fund instruction.
+pub struct VenuePrograms<'a, 'b> {
+    pub spl_token_swap_program: &'a AccountInfo<'b>,
+    /// Only if one of the traded assets has a Serum DEX market.
+    pub dex_program: Option<DexProgram<'a, 'b>>,
+}
+
+impl<'a, 'b: 'a> VenuePrograms<'a, 'b> {
+    /// Reads the spl-token-swap program account, then the Serum DEX program accounts if `with_dex_program`.
+    pub fn next(
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        with_dex_program: bool,
+    ) -> Result<Self, ProgramError> {
+        let spl_token_swap_program = next_account_info(accounts_iter)?;
+        let dex_program = if with_dex_program {
+            Some(DexProgram::next(accounts_iter)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            spl_token_swap_program,
+            dex_program,
+        })
+    }
+
+    /// Reads and checks the venue accounts of the asset `index`, the accounts of its Serum DEX market if it has one,
+    /// otherwise the accounts of its token-swap route.
+    pub fn next_venue(
+        &self,
+        accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+        fund_state: &FundState,
+        index: usize,
+        asset_mint: &Pubkey,
+    ) -> Result<Box<dyn SwapVenue<'a, 'b> + 'a>, ProgramError> {
+        Ok(match &fund_state.asset_markets[index] {
+            Some(_) => Box::new(SerumDexVenue::next(accounts_iter, self, fund_state, index, asset_mint)?),
+            None => Box::new(TokenSwapVenue::next(
+                accounts_iter,
+                self,
+                fund_state,
+                index,
+                asset_mint,
+            )?),
+        })
+    }
+}
+
+/// Spot rate of the asset `index` from the `StartRebalance` accounts of its venue, the market account and the order
+/// book of its Serum DEX market if it has one, otherwise the reserve accounts of its token-swap route.
+pub fn next_venue_quote<'a, 'b: 'a>(
+    accounts_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    fund_state: &FundState,
+    index: usize,
+    asset_mint: &Pubkey,
+) -> Result<ExchangeRate, ProgramError> {
+    match &fund_state.asset_markets[index] {
+        Some(_) => SerumDexVenue::next_quote(accounts_iter, fund_state, index, asset_mint),
+        None => TokenSwapVenue::next_quote(accounts_iter, fund_state, index, asset_mint),
+    }
+}
+
+/// Checks that the vault received at least `minimum_amount_out` tokens since it held `balance`.
+pub fn check_amount_out(vault: &AccountInfo, balance: u64, minimum_amount_out: u64) -> ProgramResult {
+    let amount_out = parse_token_account(vault)?.amount.saturating_sub(balance);
+    if amount_out < minimum_amount_out {
+        msg!("Received {} tokens, the minimum is {}", amount_out, minimum_amount_out);
+        return Err(FundError::MinimumAmountOut.into());
+    }
+    Ok(())
+}
@@ -202,6 +202,7 @@ pub enum PoolRequestInner {
     /// - `[writable]` Referrer fee vault
     /// - `[]` spl-token program
     /// - `[]/[writable]` Accounts in `PoolState::account_params`
+    /// - `[]/[writable]` Any additional accounts needed by the pool program for the action
     Execute(PoolAction),
 }
 
@@ -232,7 +232,7 @@ impl<'a, 'b> RetbufAccounts<'a, 'b> {
 
     // data is a Vec whose first 8 bytes are the little-endian offset at which to
     // write the remaining bytes
-    pub(crate) fn write_data(&self, data: Vec<u8>) -> Result<(), ProgramError> {
+    pub fn write_data(&self, data: Vec<u8>) -> Result<(), ProgramError> {
         msg!(&base64::encode(&data[8..]));
         let instruction = Instruction {
             program_id: *self.program.key,
@@ -479,7 +479,7 @@ impl<'a, 'b> PoolContext<'a, 'b> {
     }
 
     /// Burns pool tokens from the requester for a redemption request.
-    pub(crate) fn burn_tokens_and_collect_fees(&self, redemption_size: u64, fees: Fees) -> Result<(), ProgramError> {
+    pub fn burn_tokens_and_collect_fees(&self, redemption_size: u64, fees: Fees) -> Result<(), ProgramError> {
         let user_accounts = self.user_accounts.as_ref().ok_or(ProgramError::InvalidArgument)?;
         let fee_accounts = self.fee_accounts.as_ref().ok_or(ProgramError::InvalidArgument)?;
         let spl_token_program = self.spl_token_program.ok_or(ProgramError::InvalidArgument)?;
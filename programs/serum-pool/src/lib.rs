@@ -109,12 +109,16 @@ impl<'a, 'b, P: Pool> PoolProcessor<'a, 'b, P> {
         BorshSerialize::serialize(&state, &mut buf)
             .map_err(|_| ProgramError::AccountDataTooSmall)?;
 
-        if account.data_len() != buf.len() {
-            msg!("Actual account data len {} does not match the required {}", buf.len(), account.data_len());
+        // The account may be allocated with room for the state to grow, the unused tail is kept zeroed.
+        if account.data_len() < buf.len() {
+            msg!("Actual account data len {} is less than the required {}", account.data_len(), buf.len());
             return Err(ProgramError::InvalidAccountData);
         }
 
-        account.try_borrow_mut_data()?.copy_from_slice(&buf);
+        let mut data = account.try_borrow_mut_data()?;
+        let (state_data, tail) = data.split_at_mut(buf.len());
+        state_data.copy_from_slice(&buf);
+        tail.iter_mut().for_each(|byte| *byte = 0);
         Ok(())
     }
 